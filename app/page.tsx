@@ -8,6 +8,7 @@ import React, {
 } from "react";
 import Image from "next/image";
 import SearchBox from "./search_box";
+import StartupBanner from "./startup_banner";
 import { remark } from "remark";
 import html from "remark-html";
 // import sift_logo from "../src-tauri/icons/sift_logo.png";
@@ -553,6 +554,8 @@ useEffect(() => {
           </div>
         </Dialog>
       </div>
+      {/* Startup progress, or what failed to start */}
+      <StartupBanner />
       {/* Main content area */}
       <div className="flex-1 flex overflow-hidden">
         {/* File tree view */}
@@ -0,0 +1,65 @@
+import React, { useEffect, useState } from "react";
+import { AlertTriangle, Loader2, RotateCw } from "lucide-react";
+import {
+  StartupReport,
+  onStartupProgress,
+  retryStartup,
+  startupStatus,
+} from "@/lib/startup";
+
+// Shows startup progress, and what isn't working when startup ended degraded
+const StartupBanner: React.FC = () => {
+  const [report, setReport] = useState<StartupReport | null>(null);
+
+  useEffect(() => {
+    // Listen first so no event slips in between the two
+    const unlisten = onStartupProgress(setReport);
+    startupStatus().then(setReport).catch(console.error);
+    return () => {
+      unlisten.then((stop) => stop());
+    };
+  }, []);
+
+  if (!report || report.phase === "ready") {
+    return null;
+  }
+
+  if (report.phase === "starting") {
+    const running = report.stages.find((s) => s.state === "running");
+    return (
+      <div className="mx-2 mb-2 px-3 py-2 flex items-center gap-2 rounded-lg text-sm text-muted-foreground bg-gray-100 dark:bg-white/5">
+        <Loader2 className="animate-spin" size={14} />
+        <span>Starting {running ? running.label.toLowerCase() : "up"}…</span>
+      </div>
+    );
+  }
+
+  const problems = report.stages.filter(
+    (s) => s.state === "failed" || s.state === "skipped"
+  );
+  return (
+    <div className="mx-2 mb-2 px-3 py-2 flex items-start gap-3 rounded-lg text-sm border border-orange-500 bg-orange-50 dark:bg-orange-500/10 text-black dark:text-white">
+      <AlertTriangle className="mt-0.5 text-orange-500 flex-shrink-0" size={16} />
+      <div className="flex-1 min-w-0">
+        <p className="font-medium">Some features are unavailable</p>
+        <ul className="mt-1 space-y-0.5 text-gray-600 dark:text-gray-300">
+          {problems.map((s) => (
+            <li key={s.stage} className="truncate">
+              <strong>{s.label}:</strong>{" "}
+              {"reason" in s ? s.reason : ""}
+            </li>
+          ))}
+        </ul>
+      </div>
+      <button
+        className="flex items-center gap-1 px-2 py-1 rounded-md hover:bg-orange-100 dark:hover:bg-white/10 transition-colors duration-150 ease-in-out"
+        onClick={() => retryStartup().then(setReport).catch(console.error)}
+      >
+        <RotateCw size={14} />
+        <span>Retry</span>
+      </button>
+    </div>
+  );
+};
+
+export default StartupBanner;
@@ -1 +0,0 @@
-kill -9 $(lsof -i :35436) && kill -9 $(lsof -i :35443)
\ No newline at end of file
@@ -1,6 +1,26 @@
 import { getClient, ResponseType } from "@tauri-apps/api/http";
+import { invoke } from "@tauri-apps/api/tauri";
 
-let CHROMA_QUERY_BASE = "http://localhost:35443/query/"
+export type Service = "chroma" | "files" | "query" | "github_oauth" | "slack_oauth";
+
+export interface ServiceEndpoint {
+    service: Service;
+    port: number;
+    url: string;
+    relocated: boolean;
+  }
+
+// Ports are picked at startup, ask the backend where each service ended up
+let endpoints: Promise<ServiceEndpoint[]> | null = null;
+
+export async function serviceUrl(service: Service) {
+    endpoints ??= invoke<ServiceEndpoint[]>("service_ports");
+    const endpoint = (await endpoints).find((e) => e.service === service);
+    if (!endpoint) {
+        throw new Error(`Unknown service ${service}`);
+    }
+    return endpoint.url;
+}
 
 export interface ChromaFile {
     id: string;
@@ -10,31 +30,100 @@ export interface ChromaFile {
       location: string;
     };
     distance: number;
+    snippet?: Snippet | null;
+  }
+
+export type SearchMode = "semantic" | "keyword" | "hybrid";
+
+export interface Snippet {
+    text: string;
+    // [start, end) in characters (code points), slice with Array.from(text)
+    highlights: [number, number][];
+    start_line: number | null;
+    end_line: number | null;
+    page: number | null;
+    truncated: boolean;
+  }
+
+export interface SearchHit {
+    id: string;
+    score: number;
+    document: string | null;
+    metadata: ChromaFile["metadata"] | null;
+    semantic: { rank: number; distance: number | null } | null;
+    keyword: { rank: number; score: number; matched: string[] } | null;
+    snippet: Snippet | null;
   }
-  
 
-export async function queryChroma(query: string) {
-    const response = fetch(
-        CHROMA_QUERY_BASE + query + '/20',
+export interface DocumentHit {
+    id: string;
+    score: number;
+    metadata: ChromaFile["metadata"] | null;
+    matched_chunks: number;
+    passages: SearchHit[];
+  }
+
+export interface SearchPage {
+    results: ChromaFile[];
+    // Pass to `searchPage` for the following page, null on the last one
+    nextCursor: string | null;
+    total: number;
+    totalIsEstimate: boolean;
+    // Result counts per source (local, gdrive...)
+    sources: Record<string, number>;
+  }
+
+// First page of a query, or the page after `cursor`. Later pages come out
+// of the server's copy of the results so the order stays stable.
+export async function searchPage(
+    query: string,
+    k = 20,
+    mode: SearchMode = "hybrid",
+    cursor: string | null = null
+): Promise<SearchPage> {
+    const response = await fetch(
+        `${await serviceUrl("query")}/search`,
         {
-            method: 'GET',
+            method: 'POST',
             headers: {
                 'Content-Type': 'application/json'
-            }
+            },
+            body: JSON.stringify(cursor ? { cursor, k } : { query, k, mode })
         }
     )
 
-    const data = (await response).json()
-    const result = await data;
+    const result = await response.json();
+
+    if (!response.ok) {
+        throw new Error(result.error?.message ?? `Search failed (${response.status})`);
+    }
+
+    // One result per file, shown through its best passage
+    const results: ChromaFile[] = result.results.map((doc: DocumentHit) => {
+        const hit = doc.passages[0];
+        return {
+            id: doc.id,
+            document: hit?.document ?? "",
+            metadata: doc.metadata ?? hit?.metadata,
+            distance: hit?.semantic?.distance ?? 0,
+            snippet: hit?.snippet
+        };
+      });
+
+    return {
+        results,
+        nextCursor: result.next_cursor ?? null,
+        total: result.total ?? results.length,
+        totalIsEstimate: result.total_is_estimate ?? false,
+        sources: result.sources ?? {}
+    };
+}
 
-    console.log(result);
-    
-    const results: ChromaFile[] = result.results.ids[0].map((id: any, index: string | number) => ({
-        id,
-        document: result.results.documents[0][index],
-        metadata: result.results.metadatas[0][index],
-        distance: result.results.distances[0][index]
-      }));
+export async function queryChroma(query: string, k = 20, mode: SearchMode = "hybrid") {
+    if (!query.trim()) {
+        return [];
+    }
 
-    return results;
-}
\ No newline at end of file
+    const page = await searchPage(query, k, mode);
+    return page.results;
+}
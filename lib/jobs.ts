@@ -0,0 +1,69 @@
+import { invoke } from "@tauri-apps/api/tauri";
+import { listen, UnlistenFn } from "@tauri-apps/api/event";
+
+export type SyncSource = "github" | "notion" | "slack" | "google";
+
+export type JobKind =
+  | { type: "local_index" }
+  | {
+      type: "sync";
+      source: SyncSource;
+      trigger: "scheduled" | "catch_up" | "manual";
+    };
+
+export type JobState =
+  | "running"
+  | "pausing"
+  | "paused"
+  | "cancelling"
+  | "completed"
+  | "failed"
+  | "cancelled";
+
+export interface Job {
+  id: string;
+  kind: JobKind;
+  label: string;
+  state: JobState;
+  progress: { done: number; total: number | null };
+  current: string | null;
+  errors: string[];
+  error_count: number;
+  resumable: boolean;
+  // Seconds since the epoch
+  created_at: number;
+  updated_at: number;
+  finished_at: number | null;
+}
+
+// Running jobs and the last finished ones, newest first
+export function listJobs() {
+  return invoke<Job[]>("list_jobs");
+}
+
+export function indexLocal() {
+  return invoke<Job>("index_local");
+}
+
+export function syncNow(source: SyncSource) {
+  return invoke<Job>("sync_now", { source });
+}
+
+export function cancelJob(id: string) {
+  return invoke<Job>("cancel_job", { id });
+}
+
+export function pauseJob(id: string) {
+  return invoke<Job>("pause_job", { id });
+}
+
+// Continues from the last checkpoint, a failed job can be resumed too
+export function resumeJob(id: string) {
+  return invoke<Job>("resume_job", { id });
+}
+
+export function onJobProgress(
+  callback: (job: Job) => void
+): Promise<UnlistenFn> {
+  return listen<Job>("jobs://progress", (event) => callback(event.payload));
+}
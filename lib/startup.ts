@@ -0,0 +1,46 @@
+import { invoke } from "@tauri-apps/api/tauri";
+import { listen, UnlistenFn } from "@tauri-apps/api/event";
+
+export type StartupStage =
+  | "config"
+  | "chroma"
+  | "collection"
+  | "file_server"
+  | "query_server";
+
+export type StageStatus =
+  | { state: "pending" }
+  | { state: "running" }
+  | { state: "done" }
+  | { state: "failed"; reason: string }
+  | { state: "skipped"; reason: string }
+  // Nothing to do with the current settings, e.g. Chroma with the embedded store
+  | { state: "unneeded"; reason: string };
+
+export type StageReport = StageStatus & {
+  stage: StartupStage;
+  label: string;
+  elapsed_ms: number | null;
+};
+
+export interface StartupReport {
+  phase: "starting" | "ready" | "degraded";
+  stages: StageReport[];
+}
+
+export function startupStatus() {
+  return invoke<StartupReport>("startup_status");
+}
+
+// Runs the failed stages again, progress arrives through `onStartupProgress`
+export function retryStartup() {
+  return invoke<StartupReport>("retry_startup");
+}
+
+export function onStartupProgress(
+  callback: (report: StartupReport) => void
+): Promise<UnlistenFn> {
+  return listen<StartupReport>("startup://progress", (event) =>
+    callback(event.payload)
+  );
+}
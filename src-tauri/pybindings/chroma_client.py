@@ -0,0 +1,8 @@
+import chromadb
+from urllib.parse import urlparse
+
+
+def connect(url):
+    """Client for the app's `chroma run` server at `url`, the only process that opens the datastore."""
+    server = urlparse(url)
+    return chromadb.HttpClient(host=server.hostname, port=server.port)
@@ -1,106 +1,101 @@
 import chromadb
 from chromadb import Settings
-from sys import argv 
-import re
+from sys import argv, stdin, stderr
+import json
 from chromadb.utils.embedding_functions.open_clip_embedding_function import OpenCLIPEmbeddingFunction
 from chromadb.utils.data_loaders import ImageLoader
 from PIL import Image 
 from numpy import asarray
 import open_clip
 
+from chroma_client import connect
+
 if len(argv) < 3:
-    print("Usage: python chroma_sdk.py <path> [ get_or_create | add | query ] <args>")
+    print("Usage: python chroma_sdk.py <chroma url> [ add | upsert | query ] <args>")
     exit(1)
 
 
 file = argv[0]
-db_path = argv[1]
+chroma_url = argv[1]
 
 embedder = OpenCLIPEmbeddingFunction()
 data_loader = ImageLoader()
-client = chromadb.PersistentClient(
-    path="/Users/ashwa/Desktop/sift_datastore",
-)
-
-def parse_file_metadata(metadata_string):
-    # Define a regex pattern to extract key-value pairs
-    pattern = r'FileMetadata\s*\{\s*filepath:\s*"([^"]+)",\s*filename:\s*"([^"]+)",\s*extension:\s*"([^"]+)",\s*size:\s*(\d+)\s*\}'
-    
-    # Use regex to search for the pattern
-    match = re.search(pattern, metadata_string)
-    
-    if match:
-        filepath, filename, extension, size = match.groups()
-        
-        return [{
-            "filepath": filepath,
-            "extension": extension,
-            "size": int(size)
-        }]
-    else:
-        return [] 
+client = connect(chroma_url)
 
 
-def get_or_create():
-    try:
-        collection = client.get_or_create_collection(
-            name=argv[3],
-            embedding_function=embedder,
-            data_loader=data_loader
-        )
-        # print("{ \"status\": \"Success: " + str(collection) + "\" }")
-    except Exception as e:
-        print("{ \"status\": \"Failed with error: " + str(e) + "\" }")
+# Structured actions read one JSON request from stdin and print one JSON
+# response line, failures go to stderr with a non-zero exit code
+
+def read_request():
+    return json.load(stdin)
+
+
+def respond(result):
+    # Query results can carry numpy arrays (embeddings)
+    print(json.dumps(result, default=lambda o: o.tolist() if hasattr(o, "tolist") else str(o)))
+
+
+def collection_for(request):
+    return client.get_or_create_collection(
+        name=request["collection_name"],
+        embedding_function=embedder,
+        data_loader=data_loader
+    )
+
+
+def filters(request):
+    # Chroma rejects empty filters, only pass the ones that were given
+    return {key: request[key] for key in ("where", "where_document", "include") if request.get(key)}
+
+
+def columns(result, keys):
+    return {key: result.get(key) for key in keys}
 
 
 def add():
-    try: 
-        coll_name = argv[3]
-        collection = client.get_or_create_collection(name=coll_name, 
-            embedding_function=embedder, data_loader=data_loader
-        )
-
-        if argv[4] == "--images":
-            images = [asarray(Image.open(path)) for path in eval(argv[5])]
-            ids = eval(argv[6])
-            if len(argv) == 7:
-                metadata = None
-            else: 
-                metadata = parse_file_metadata(argv[7])
-            collection.add(images=images, ids=ids, metadatas=metadata)
-        else:
-            docs = eval(argv[4])
-            ids = eval(argv[5])
-            if len(argv) == 6:
-                metadata = None
-            else:
-                metadata = parse_file_metadata(argv[6])
-            collection.add(documents=docs, ids=ids, metadatas=metadata)
-        # print("{ \"status\": \"Success: " + str(collection) + "\" }")
-    except Exception as e:
-        print("{ \"status\": \"Failed with error: " + str(e) + "\" }")
+    # Batches of chunks are too large for argv, images are loaded from their path
+    request = read_request()
+    collection = collection_for(request)
+    if "images" in request:
+        images = [asarray(Image.open(path)) for path in request["images"]]
+        collection.add(images=images, ids=request["ids"], metadatas=request["metadatas"])
+    else:
+        collection.add(documents=request["documents"], ids=request["ids"], metadatas=request["metadatas"])
+    respond(None)
 
 
-def query_text():
-    try:
-        coll_name = argv[3]
-        query_text = argv[4]
-        n_results = int(argv[5])
-        collection = client.get_or_create_collection(name=coll_name, embedding_function=embedder, data_loader=data_loader)
-        results = collection.query(query_texts=[query_text], n_results=n_results)
+def upsert():
+    request = read_request()
+    collection_for(request).upsert(
+        ids=request["ids"],
+        documents=request.get("documents"),
+        metadatas=request.get("metadatas")
+    )
+    respond(None)
 
-        print(results) 
-    except Exception as e:
-        print("{ \"status\": \"Failed with error: " + str(e) + "\" }")
-    
 
-action = {
-    "get_or_create": get_or_create,
+def query():
+    request = read_request()
+    results = collection_for(request).query(
+        query_texts=request["query_texts"],
+        n_results=request["n_results"],
+        **filters(request)
+    )
+    respond(columns(results, ("ids", "documents", "metadatas", "distances")))
+
+
+structured = {
     "add": add,
-    "query": query_text
+    "upsert": upsert,
+    "query": query
 }
 
 
 if __name__ == "__main__":
     action_id = argv[2]
-    action[action_id]()
\ No newline at end of file
+
+    try:
+        structured[action_id]()
+    except Exception as e:
+        print(json.dumps({"error": str(e)}), file=stderr)
+        exit(1)
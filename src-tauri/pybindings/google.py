@@ -1,15 +1,24 @@
-
+from sys import argv
+import json
+import os
 
 import requests
 
-# Your OAuth 2.0 access token
-ACCESS_TOKEN = 'your_token'
+# chroma url = argv[1], config file = argv[2], datastore dir = argv[3]
+with open(argv[2], 'r') as jf:
+    data = json.load(jf)
+
+# The OAuth 2.0 access token saved by the app
+ACCESS_TOKEN = data["google_token"]
+# Downloads go under the datastore, not wherever the script was started
+download_dir = os.path.join(argv[3], "google")
+os.makedirs(download_dir, exist_ok=True)
+
 # Google Drive API endpoint to list files
 url = "https://www.googleapis.com/drive/v3/files"
 
-# Optional parameters to filter files, adjust 'q' to fit your search (e.g., search by name)
 params = {
-    "q": "name contains 'your_filename'",  # Replace 'your_filename' with the actual name
+    "q": "trashed = false",
     "fields": "files(id, name, mimeType)"  # Adjust fields to fetch more metadata if needed
 }
 
@@ -21,26 +30,25 @@ headers = {
 # Make the request to list files
 response = requests.get(url, headers=headers, params=params)
 
-# Check if the request was successful
-if response.status_code == 200:
-    files = response.json().get('files', [])
-    if not files:
-        print("No files found.")
+if response.status_code != 200:
+    # A non-zero exit marks the sync as failed
+    raise SystemExit(f"Error listing files: {response.status_code}, {response.text}")
+
+files = response.json().get('files', [])
+if not files:
+    print("No files found.")
+
+for file in files:
+    print(f"Found file: {file['name']} (ID: {file['id']})")
+    file_id = file['id']
+    download_url = f"https://www.googleapis.com/drive/v3/files/{file_id}?alt=media"
+    file_response = requests.get(download_url, headers=headers)
+
+    if file_response.status_code == 200:
+        # Named by id, two Drive files can share a name
+        target = os.path.join(download_dir, f"{file_id}-{os.path.basename(file['name'])}")
+        with open(target, 'wb') as f:
+            f.write(file_response.content)
+        print(f"Downloaded file: {file['name']}")
     else:
-        for file in files:
-            print(f"Found file: {file['name']} (ID: {file['id']})")
-            file_id = file['id']
-            # Optionally, download or read the file content
-            download_url = f"https://www.googleapis.com/drive/v3/files/{file_id}?alt=media"
-            file_response = requests.get(download_url, headers=headers)
-
-            if file_response.status_code == 200:
-                # Save or process the file content
-                file_content = file_response.content
-                with open(file['name'], 'wb') as f:
-                    f.write(file_content)
-                print(f"Downloaded file: {file['name']}")
-            else:
-                print(f"Error downloading file: {file_response.status_code}, {file_response.text}")
-else:
-    print(f"Error: {response.status_code}, {response.text}")
+        print(f"Error downloading file: {file_response.status_code}, {file_response.text}")
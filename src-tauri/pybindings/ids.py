@@ -0,0 +1,10 @@
+from hashlib import sha256
+
+# Must match src/util/ids.rs so every source lands in the same id space
+HASH_CHARS = 32
+
+
+def document_id(source, locator):
+    """Deterministic `{source}-{hash}` id for the document at `locator`."""
+    digest = sha256(f"{source}\n{locator}".encode("utf-8")).hexdigest()
+    return f"{source}-{digest[:HASH_CHARS]}"
@@ -8,6 +8,10 @@ import requests
 import json 
 import urllib.parse
 
+from ids import document_id
+from chroma_client import connect
+import jobs
+
 # Initialize embedders and data loaders for ChromaDB
 embedder = OpenCLIPEmbeddingFunction()
 data_loader = ImageLoader()
@@ -15,21 +19,15 @@ data_loader = ImageLoader()
 # Track the starting time for performance measurement
 start = time() 
 
-# chroma dir = argv[1] (path to persistent directory)
-appdata_dir = "/Users/ashwa/Desktop/sift.config.json"  # Path to appdata (credentials JSON)
-
-with open(appdata_dir, 'r') as jf:
+# chroma url = argv[1], config file = argv[2], datastore dir = argv[3]
+with open(argv[2], 'r') as jf:
     data = json.load(jf)
 
 key = data["github_token"]
 username = data["github_username"]
 
-cur_file_id = 0
-
-# Create and reset ChromaDB client
-client = chromadb.PersistentClient(
-    path="/Users/ashwa/Desktop/sift_datastore",
-)
+# The app's Chroma server, chroma url = argv[1]
+client = connect(argv[1])
 
 
 coll = client.get_or_create_collection(
@@ -66,7 +64,7 @@ def process_repo(repo):
         })
 
         if req.status_code != 200:
-            print(f"Error fetching repo contents at path {path}: {req.text}")
+            jobs.error(f"Error fetching {repo_name}/{path}: {req.status_code}")
             return
         
         contents = req.json()
@@ -145,28 +143,37 @@ def get_file_contents(fp, repo_name):
 
 
 def embed_file_to_chromadb(filename, file_path, file_content):
-    global cur_file_id
-    coll.add(
+    coll.upsert(
         documents=[file_content],
-        ids=[f"gh{cur_file_id}"],
+        ids=[document_id("github", file_path)],
         metadatas=[{
             'filepath': file_path,
             "location": "github"
         }]
     )
-    cur_file_id += 1
 
 
 
 def gh_pipeline():
     repos = get_repositories()
     if "error" in repos:
-        print(repos["error"])
-        return
+        # A non-zero exit marks the sync as failed
+        raise SystemExit(repos["error"])
+
+    # Repos finished before a pause or crash aren't fetched again
+    state = jobs.checkpoint()
+    repos_done = state.get("repos_done", [])
 
     for repo in repos:
+        jobs.report(len(repos_done), len(repos), repo['name'])
+        if repo['name'] in repos_done:
+            continue
         print(f"Processing {repo['name']}...")
         process_repo(repo)
+        repos_done.append(repo['name'])
+        jobs.save({"repos_done": repos_done})
+
+    jobs.report(len(repos_done), len(repos))
 
     print("Complete!")
 
@@ -8,6 +8,10 @@ import requests
 import json
 import urllib.parse
 
+from ids import document_id
+from chroma_client import connect
+import jobs
+
 # Initialize embedders and data loaders for ChromaDB
 embedder = OpenCLIPEmbeddingFunction()
 data_loader = ImageLoader()
@@ -15,23 +19,17 @@ data_loader = ImageLoader()
 # Track the starting time for performance measurement
 start = time()
 
-# chroma dir = argv[1] (path to persistent directory)
-appdata_dir = "/Users/ashwa/Desktop/sift.config.json"  # Path to appdata (credentials JSON)
-
-with open(appdata_dir, 'r') as jf:
+# chroma url = argv[1], config file = argv[2], datastore dir = argv[3]
+with open(argv[2], 'r') as jf:
     data = json.load(jf)
 
 key = data["notion_token"]
 # username = data["github_username"]
 
-cur_file_id = 5
-
 start = time()
 
-# Create and reset ChromaDB client
-client = chromadb.PersistentClient(
-    path="/Users/ashwa/Desktop/sift_datastore",
-)
+# The app's Chroma server, chroma url = argv[1]
+client = connect(argv[1])
 
 coll = client.get_or_create_collection(
     name="siftfiles",
@@ -56,7 +54,7 @@ def get_notion_pages():
     })
 
     if req.status_code != 200:
-        print("Error getting pages")
+        raise SystemExit(f"Error getting pages: {req.text}")
 
     data = req.json()
 
@@ -79,7 +77,7 @@ def process_page(page):
     })
 
     if req.status_code != 200:
-        print("Error fetching page contents")
+        jobs.error(f"Error fetching {page['url']}: {req.status_code}")
         return
     
     data = req.json()
@@ -91,13 +89,11 @@ def process_page(page):
             text += block["paragraph"]["rich_text"][0]["plain_text"] + "\n"
 
 
-    global cur_file_id 
     # print(text)
-    coll.add(documents=[text], ids=[f"noti{cur_file_id}"], metadatas=[{
+    coll.upsert(documents=[text], ids=[document_id("notion", page["url"])], metadatas=[{
         "filepath": page["url"],
         "location": "notion"
     }])
-    cur_file_id += 1
 
 
 
@@ -174,8 +170,19 @@ def extract_text_from_block(block):
 def notion_flow():
     pages = get_notion_pages()
 
+    # Pages finished before a pause or crash aren't fetched again
+    state = jobs.checkpoint()
+    pages_done = state.get("pages_done", [])
+
     for page in pages:
+        jobs.report(len(pages_done), len(pages), page["url"])
+        if page["id"] in pages_done:
+            continue
         process_page(page)
+        pages_done.append(page["id"])
+        jobs.save({"pages_done": pages_done})
+
+    jobs.report(len(pages_done), len(pages))
 
 
 notion_flow()
@@ -0,0 +1,27 @@
+import json
+import os
+
+# Must match src/jobs/process.rs, which reads the `JOB:` lines from stdout
+CHECKPOINT_ENV = "SIFT_JOB_CHECKPOINT"
+
+
+def checkpoint():
+    """The state saved by the last run of a resumed job, `{}` on a fresh start."""
+    saved = os.environ.get(CHECKPOINT_ENV)
+    return json.loads(saved) if saved else {}
+
+
+def report(done, total=None, current=None):
+    """Progress so far, `total` is None until it's known."""
+    line = json.dumps({"done": done, "total": total, "current": current})
+    print(f"JOB:PROGRESS {line}", flush=True)
+
+
+def save(state):
+    """Saves `state`, handed back through `checkpoint()` when the job resumes."""
+    print(f"JOB:CHECKPOINT {json.dumps(state)}", flush=True)
+
+
+def error(message):
+    """An error that doesn't stop the job."""
+    print(f"JOB:ERROR {message}", flush=True)
@@ -4,6 +4,8 @@ use actix_web::{middleware::Logger, web, App, HttpRequest, HttpResponse, HttpSer
 use std::env;
 use std::path::{Path, PathBuf};
 
+use crate::util::{PortRegistry, Service};
+
 // use std::path::PathBuf;
 
 async fn get_file(req: HttpRequest, path: web::Path<PathBuf>) -> impl Responder {
@@ -52,6 +54,7 @@ async fn get_file(req: HttpRequest, path: web::Path<PathBuf>) -> impl Responder
 // }
 
 pub async fn serve() -> std::io::Result<()> {
+    let port = PortRegistry::shared().port(Service::Files);
     let server = HttpServer::new(|| {
         let cors = Cors::default()
             .allow_any_origin() // Allow any origin
@@ -62,9 +65,9 @@ pub async fn serve() -> std::io::Result<()> {
             .wrap(cors)
             .service(web::resource("/{path:.*}").route(web::get().to(get_file)))
     })
-    .bind("127.0.0.1:35438")?;
+    .bind(("127.0.0.1", port))?;
 
-    println!("File Services Running on http://localhost:35438");
+    println!("File Services Running on http://localhost:{}", port);
     server.run().await
     // tokio::select! {
     //     _ = server.run() => {
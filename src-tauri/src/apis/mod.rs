@@ -1,3 +1,4 @@
 pub mod fileserv;
+pub mod queryserv;
 
 // pub use fileserv::serve;
\ No newline at end of file
@@ -0,0 +1,386 @@
+use actix_cors::Cors;
+use actix_web::error::{ErrorInternalServerError, JsonPayloadError};
+use actix_web::http::header::{self, ContentType};
+use actix_web::http::StatusCode;
+use actix_web::web::{self, Bytes};
+use actix_web::{middleware::Logger, App, HttpRequest, HttpResponse, HttpServer};
+use futures::{future, stream, StreamExt};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::error::Error;
+
+use crate::chroma::{ChromaError, Filter};
+use crate::files::indexer::COLLECTION_NAME;
+use crate::search::page::{CursorError, Page, MAX_RESULTS};
+use crate::search::{self, ParseError, SearchMode, SearchRequest};
+use crate::util::{PortRegistry, Service};
+
+// Upper bound for `k`, more than this is a bulk export rather than a search
+const MAX_K: usize = 1_000;
+
+// Chunks per file when grouping, beyond that a file is better opened
+const MAX_PASSAGES: usize = 20;
+
+// Hits serialized per streamed chunk
+const STREAM_CHUNK: usize = 32;
+
+const NDJSON: &str = "application/x-ndjson";
+
+fn default_k() -> usize {
+    20
+}
+
+fn default_passages() -> usize {
+    3
+}
+
+fn default_group() -> bool {
+    true
+}
+
+fn default_collection() -> String {
+    COLLECTION_NAME.to_string()
+}
+
+/// Body of `POST /search`
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct QueryBody {
+    /// Not needed when following a `cursor`
+    #[serde(default)]
+    pub query: String,
+    #[serde(default = "default_k")]
+    pub k: usize,
+    /// Results to skip
+    #[serde(default)]
+    pub offset: usize,
+    /// `next_cursor` of the previous page, the other fields but `k` are
+    /// then ignored
+    #[serde(default)]
+    pub cursor: Option<String>,
+    /// Collapse chunks into one result per file, `k` then counts files
+    #[serde(default = "default_group")]
+    pub group: bool,
+    /// Best chunks kept per file when grouping
+    #[serde(default = "default_passages")]
+    pub passages: usize,
+    #[serde(default)]
+    pub filters: Filter,
+    #[serde(default)]
+    pub mode: SearchMode,
+    #[serde(default = "default_collection")]
+    pub collection: String,
+}
+
+#[derive(Serialize, Debug)]
+struct ErrorBody {
+    code: &'static str,
+    message: String,
+    /// Character offset into the query, for query syntax errors
+    #[serde(skip_serializing_if = "Option::is_none")]
+    position: Option<usize>,
+}
+
+#[derive(Serialize, Debug)]
+struct ErrorResponse {
+    error: ErrorBody,
+}
+
+fn error_response(status: StatusCode, code: &'static str, message: String) -> HttpResponse {
+    error_response_at(status, code, message, None)
+}
+
+fn error_response_at(
+    status: StatusCode,
+    code: &'static str,
+    message: String,
+    position: Option<usize>,
+) -> HttpResponse {
+    eprintln!("QUERY:WARN: {} ({}): {}", code, status.as_u16(), message);
+    HttpResponse::build(status).json(ErrorResponse {
+        error: ErrorBody {
+            code,
+            message,
+            position,
+        },
+    })
+}
+
+// Store errors are boxed by the time they get here, look at what's inside
+fn classify(e: &(dyn Error + 'static)) -> (StatusCode, &'static str) {
+    if e.is::<ParseError>() {
+        return (StatusCode::BAD_REQUEST, "invalid_query");
+    }
+    match e.downcast_ref::<CursorError>() {
+        Some(CursorError::Expired) => return (StatusCode::GONE, "cursor_expired"),
+        Some(CursorError::Invalid) => return (StatusCode::BAD_REQUEST, "invalid_cursor"),
+        None => {}
+    }
+
+    match e.downcast_ref::<ChromaError>() {
+        Some(ChromaError::CollectionNotFound(_)) => (StatusCode::NOT_FOUND, "collection_not_found"),
+        Some(ChromaError::Unreachable { .. } | ChromaError::Timeout(_)) => {
+            (StatusCode::SERVICE_UNAVAILABLE, "store_unavailable")
+        }
+        Some(ChromaError::Rejected { .. }) => (StatusCode::BAD_REQUEST, "invalid_request"),
+        Some(_) => (StatusCode::BAD_GATEWAY, "store_error"),
+        None if e.to_string().starts_with("Collection not found") => {
+            (StatusCode::NOT_FOUND, "collection_not_found")
+        }
+        None if e.downcast_ref::<reqwest::Error>().is_some() => {
+            (StatusCode::SERVICE_UNAVAILABLE, "embedder_unavailable")
+        }
+        None => (StatusCode::INTERNAL_SERVER_ERROR, "search_failed"),
+    }
+}
+
+fn json_error(e: JsonPayloadError, _req: &HttpRequest) -> actix_web::Error {
+    let (status, code) = match &e {
+        JsonPayloadError::ContentType => {
+            (StatusCode::UNSUPPORTED_MEDIA_TYPE, "unsupported_media_type")
+        }
+        JsonPayloadError::Overflow { .. } | JsonPayloadError::OverflowKnownLength { .. } => {
+            (StatusCode::PAYLOAD_TOO_LARGE, "payload_too_large")
+        }
+        _ => (StatusCode::BAD_REQUEST, "invalid_request"),
+    };
+    actix_web::error::InternalError::from_response(
+        e.to_string(),
+        error_response(status, code, e.to_string()),
+    )
+    .into()
+}
+
+fn validate(body: &QueryBody) -> Result<(), String> {
+    if body.cursor.is_some() {
+        if !body.group {
+            return Err("cursors are only available for grouped results".to_string());
+        }
+        if body.offset > 0 {
+            return Err("offset can't be combined with a cursor".to_string());
+        }
+    } else if body.query.trim().is_empty() {
+        return Err("query must not be empty".to_string());
+    }
+    if !(1..=MAX_K).contains(&body.k) {
+        return Err(format!("k must be between 1 and {}", MAX_K));
+    }
+    if body.offset + body.k > MAX_K {
+        return Err(format!("offset + k must not exceed {}", MAX_K));
+    }
+    if body.group && body.offset >= MAX_RESULTS {
+        return Err(format!("offset must be below {}", MAX_RESULTS));
+    }
+    if !(1..=MAX_PASSAGES).contains(&body.passages) {
+        return Err(format!("passages must be between 1 and {}", MAX_PASSAGES));
+    }
+    Ok(())
+}
+
+fn wants_ndjson(req: &HttpRequest) -> bool {
+    req.headers()
+        .get(header::ACCEPT)
+        .and_then(|v| v.to_str().ok())
+        .map(|v| v.contains(NDJSON))
+        .unwrap_or(false)
+}
+
+fn serialize_batch<T: Serialize>(
+    batch: &[T],
+    first: bool,
+    ndjson: bool,
+) -> Result<Bytes, actix_web::Error> {
+    let mut chunk = String::new();
+    for (i, hit) in batch.iter().enumerate() {
+        if !ndjson && (i > 0 || !first) {
+            chunk.push(',');
+        }
+        chunk.push_str(&serde_json::to_string(hit).map_err(ErrorInternalServerError)?);
+        if ndjson {
+            chunk.push('\n');
+        }
+    }
+    Ok(Bytes::from(chunk))
+}
+
+// Where a grouped search stands, next to the results
+#[derive(Serialize, Debug)]
+struct Paging {
+    next_cursor: Option<String>,
+    total: usize,
+    total_is_estimate: bool,
+    sources: BTreeMap<String, usize>,
+}
+
+// Hits are serialized a batch at a time as the client reads them. `ndjson`
+// writes one hit per line with the paging in `X-Next-Cursor` and
+// `X-Total-Count` headers, otherwise the batches make up
+// `{"query", "mode", "offset", "grouped", "count", ...paging, "results": [...]}`.
+fn stream_hits<T: Serialize + 'static>(
+    body: &QueryBody,
+    hits: Vec<T>,
+    paging: Option<Paging>,
+    ndjson: bool,
+) -> HttpResponse {
+    let mut response = HttpResponse::Ok();
+    let (head, tail) = if ndjson {
+        response.content_type(NDJSON);
+        if let Some(paging) = &paging {
+            if let Some(cursor) = &paging.next_cursor {
+                response.insert_header(("X-Next-Cursor", cursor.as_str()));
+            }
+            response.insert_header(("X-Total-Count", paging.total.to_string()));
+        }
+        (String::new(), "")
+    } else {
+        response.content_type(ContentType::json());
+        let mut head = serde_json::json!({
+            "query": body.query,
+            "mode": body.mode,
+            "offset": body.offset,
+            "grouped": body.group,
+            "count": hits.len(),
+        });
+        if let (Some(head), Ok(Value::Object(paging))) =
+            (head.as_object_mut(), serde_json::to_value(&paging))
+        {
+            head.extend(paging);
+        }
+        // Reopen the object to append the results array
+        let head = head.to_string();
+        (
+            format!("{},\"results\":[", head.trim_end_matches('}')),
+            "]}",
+        )
+    };
+
+    let mut batches = Vec::new();
+    let mut hits = hits.into_iter().peekable();
+    while hits.peek().is_some() {
+        batches.push(hits.by_ref().take(STREAM_CHUNK).collect::<Vec<_>>());
+    }
+
+    let stream = stream::once(future::ok(Bytes::from(head)))
+        .chain(
+            stream::iter(batches.into_iter().enumerate())
+                .map(move |(i, batch)| serialize_batch(&batch, i == 0, ndjson)),
+        )
+        .chain(stream::once(future::ok(Bytes::from_static(
+            tail.as_bytes(),
+        ))));
+
+    response.streaming(stream)
+}
+
+type SearchFailure = (StatusCode, &'static str, String, Option<usize>);
+
+// Box<dyn Error> isn't Send, so it is classified on the worker thread
+async fn run_search<T, F>(search: F) -> Result<T, SearchFailure>
+where
+    T: Send + 'static,
+    F: FnOnce() -> Result<T, Box<dyn Error>> + Send + 'static,
+{
+    let result = web::block(move || {
+        search().map_err(|e| {
+            let (status, code) = classify(e.as_ref());
+            let position = e.downcast_ref::<ParseError>().map(|e| e.position);
+            (status, code, e.to_string(), position)
+        })
+    })
+    .await;
+
+    match result {
+        Ok(result) => result,
+        Err(e) => Err((
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "search_failed",
+            e.to_string(),
+            None,
+        )),
+    }
+}
+
+fn failure_response((status, code, message, position): SearchFailure) -> HttpResponse {
+    error_response_at(status, code, message, position)
+}
+
+async fn search_handler(req: HttpRequest, body: web::Json<QueryBody>) -> HttpResponse {
+    let body = body.into_inner();
+    if let Err(message) = validate(&body) {
+        return error_response(StatusCode::BAD_REQUEST, "invalid_request", message);
+    }
+
+    let request = SearchRequest {
+        collection_name: body.collection.clone(),
+        query: body.query.clone(),
+        n_results: body.k,
+        offset: body.offset,
+        passages: body.passages,
+        filter: body.filters.clone(),
+        mode: body.mode,
+    };
+    let ndjson = wants_ndjson(&req);
+
+    if !body.group {
+        return match run_search(move || search::search(&request)).await {
+            Ok(hits) => stream_hits(&body, hits, None, ndjson),
+            Err(failure) => failure_response(failure),
+        };
+    }
+
+    let page = match body.cursor.clone() {
+        Some(cursor) => {
+            let k = body.k;
+            run_search(move || Ok(search::next_page(&cursor, k)?)).await
+        }
+        None => run_search(move || search::first_page(&request)).await,
+    };
+
+    match page {
+        Ok(Page {
+            results,
+            next_cursor,
+            total,
+            total_is_estimate,
+            sources,
+        }) => {
+            let paging = Paging {
+                next_cursor,
+                total,
+                total_is_estimate,
+                sources,
+            };
+            stream_hits(&body, results, Some(paging), ndjson)
+        }
+        Err(failure) => failure_response(failure),
+    }
+}
+
+async fn not_found() -> HttpResponse {
+    error_response(
+        StatusCode::NOT_FOUND,
+        "not_found",
+        "Unknown endpoint, search with POST /search".to_string(),
+    )
+}
+
+pub async fn serve() -> std::io::Result<()> {
+    let port = PortRegistry::shared().port(Service::Query);
+    let server = HttpServer::new(|| {
+        let cors = Cors::default()
+            .allow_any_origin()
+            .allow_any_method()
+            .allow_any_header();
+        App::new()
+            .wrap(Logger::default())
+            .wrap(cors)
+            .app_data(web::JsonConfig::default().error_handler(json_error))
+            .route("/search", web::post().to(search_handler))
+            .default_service(web::to(not_found))
+    })
+    .bind(("127.0.0.1", port))?;
+
+    println!("Query Services Running on http://localhost:{}", port);
+    server.run().await
+}
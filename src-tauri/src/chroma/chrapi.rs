@@ -1,29 +1,166 @@
 use serde::{Deserialize, Serialize};
 use std::env;
+use serde_json::{json, Map, Value};
 use std::error::Error;
-use std::process::Command;
+use std::io::Write;
+use std::process::{Command, Output, Stdio};
 use std::str;
 
-// use crate::files::FileMetadata;
+use crate::chroma::chrust::chroma_url;
+use crate::util;
+
+pub type Metadata = Map<String, Value>;
+
+/// Chroma's `where` and `where_document` filters, passed through as is, e.g.
+/// `{"extension": {"$eq": "pdf"}}` or `{"$contains": "invoice"}`
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct Filter {
+    #[serde(rename = "where", default, skip_serializing_if = "Option::is_none")]
+    pub where_metadata: Option<Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub where_document: Option<Value>,
+}
+
+fn default_n_results() -> usize {
+    10
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct QueryRequest {
+    pub collection_name: String,
+    pub query_texts: Vec<String>,
+    #[serde(default = "default_n_results")]
+    pub n_results: usize,
+    #[serde(flatten)]
+    pub filter: Filter,
+    /// Any of `documents`, `metadatas`, `distances`, `embeddings`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub include: Option<Vec<String>>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct GetRequest {
+    pub collection_name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ids: Option<Vec<String>>,
+    #[serde(flatten)]
+    pub filter: Filter,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub limit: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub offset: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub include: Option<Vec<String>>,
+}
+
+/// Deletes the given ids, everything matching the filter, or the
+/// intersection when both are set
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct DeleteRequest {
+    pub collection_name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ids: Option<Vec<String>>,
+    #[serde(flatten)]
+    pub filter: Filter,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct UpsertRequest {
+    pub collection_name: String,
+    pub ids: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub documents: Option<Vec<String>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub metadatas: Option<Vec<Metadata>>,
+}
 
 #[derive(Debug)]
 pub enum Action {
-    GetOrCreate {
+    Add {
         collection_name: String,
+        documents: Vec<String>,
+        ids: Vec<String>,
+        metadatas: Vec<Map<String, Value>>,
     },
+    AddImage {
+        collection_name: String,
+        image_path: String,
+        id: String,
+        metadata: Metadata,
+    },
+    // Text has to be embedded on the Python side, everything else goes
+    // through the native client in `chrust`
+    Upsert(UpsertRequest),
+    Query(QueryRequest),
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+/// Column-oriented like Chroma itself, one inner list per query text
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
 pub struct QueryResult {
-    pub results: Vec<String>,
+    pub ids: Vec<Vec<String>>,
+    #[serde(default)]
+    pub documents: Option<Vec<Vec<Option<String>>>>,
+    #[serde(default)]
+    pub metadatas: Option<Vec<Vec<Option<Metadata>>>>,
+    #[serde(default)]
+    pub distances: Option<Vec<Vec<f32>>>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct GetResult {
+    pub ids: Vec<String>,
+    #[serde(default)]
+    pub documents: Option<Vec<Option<String>>>,
+    #[serde(default)]
+    pub metadatas: Option<Vec<Option<Metadata>>>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CollectionInfo {
+    pub name: String,
+    #[serde(default)]
+    pub metadata: Option<Metadata>,
+}
+
+#[derive(Debug)]
+pub enum Response {
+    Done,
+    Query(QueryResult),
+}
+
+// Runs the SDK with `payload` written to stdin, the form every structured
+// action uses since documents and filters don't fit safely in argv
+fn run_with_stdin(command: &mut Command, payload: &Value) -> Result<Output, Box<dyn Error>> {
+    let mut child = command
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(payload.to_string().as_bytes())?;
+    }
+    Ok(child.wait_with_output()?)
 }
 
+// Libraries on the Python side occasionally log to stdout, the response is
+// always the last line
+fn parse_response<T: serde::de::DeserializeOwned>(stdout: &str) -> Result<T, Box<dyn Error>> {
+    let line = stdout
+        .lines()
+        .rev()
+        .find(|line| !line.trim().is_empty())
+        .ok_or("Python SDK returned no response")?;
+    Ok(serde_json::from_str(line)?)
+}
+
+/// Runs one action through `pybindings/chroma_sdk.py`, which talks to the
+/// same `chroma run` server as `ChromaClient`
 pub fn run_python_sdk(
-    db_path: &str,
     action: &Action, // Borrow the action instead of moving it
     mute: bool
-) -> Result<Option<QueryResult>, Box<dyn Error>> {
-    let sdkpath = "./pybindings/chroma_sdk.py";
+) -> Result<Response, Box<dyn Error>> {
+    let sdkpath = util::pybinding("chroma_sdk.py");
 
     if !mute {
         println!("Running Python SDK with action: {:?}", action);
@@ -36,13 +173,51 @@ pub fn run_python_sdk(
         "python3"
     };
 
+    let mut command = Command::new(python_cmd);
+    command.arg(sdkpath).arg(chroma_url());
+
     let output = match action {
-        Action::GetOrCreate { collection_name } => Command::new(python_cmd)
-            .arg(sdkpath)
-            .arg(db_path)
-            .arg("get_or_create")
-            .arg(&collection_name) // Borrow the collection name
-            .output()?,
+        Action::Add {
+            collection_name,
+            documents,
+            ids,
+            metadatas,
+        } => {
+            let payload = json!({
+                "collection_name": collection_name,
+                "documents": documents,
+                "ids": ids,
+                "metadatas": metadatas,
+            });
+
+            command.arg("add");
+            run_with_stdin(&mut command, &payload)?
+        }
+        // Images are loaded from their path on the Python side
+        Action::AddImage {
+            collection_name,
+            image_path,
+            id,
+            metadata,
+        } => {
+            let payload = json!({
+                "collection_name": collection_name,
+                "images": [image_path],
+                "ids": [id],
+                "metadatas": [metadata],
+            });
+
+            command.arg("add");
+            run_with_stdin(&mut command, &payload)?
+        }
+        Action::Upsert(request) => {
+            command.arg("upsert");
+            run_with_stdin(&mut command, &serde_json::to_value(request)?)?
+        }
+        Action::Query(request) => {
+            command.arg("query");
+            run_with_stdin(&mut command, &serde_json::to_value(request)?)?
+        }
     };
 
     if output.status.success() {
@@ -52,14 +227,11 @@ pub fn run_python_sdk(
             println!("Python SDK output: {}", stdout);
         }
 
-        // if let Action::Query { .. } = action {
-        //     let results: QueryResult = serde_json::from_str(stdout)?;
-        //     Ok(Some(results))
-        // } else {
-        //     println!("Python SDK output: {}", stdout);
-        //     Ok(None)
-        // }
-        Ok(None)
+        let response = match action {
+            Action::Query(_) => Response::Query(parse_response(stdout)?),
+            _ => Response::Done,
+        };
+        Ok(response)
     } else {
         let stderr = str::from_utf8(&output.stderr)?;
         // let stdout: String = str::from_utf8(&output.stdout)?.to_string();
@@ -69,3 +241,19 @@ pub fn run_python_sdk(
         Err(Box::from(stderr))
     }
 }
+
+fn unexpected(response: Response) -> Box<dyn Error> {
+    Box::from(format!("Unexpected Python SDK response: {:?}", response))
+}
+
+pub fn query(request: QueryRequest) -> Result<QueryResult, Box<dyn Error>> {
+    match run_python_sdk(&Action::Query(request), true)? {
+        Response::Query(result) => Ok(result),
+        other => Err(unexpected(other)),
+    }
+}
+
+pub fn upsert(request: UpsertRequest) -> Result<(), Box<dyn Error>> {
+    run_python_sdk(&Action::Upsert(request), true)?;
+    Ok(())
+}
@@ -1,25 +1,382 @@
-// use chromadb::v1::client::{ChromaClient, ChromaClientOptions};
-// use chromadb::v1::collection::{ChromaCollection, GetResult, CollectionEntries};
-// use chromadb::v1::embeddings::EmbeddingFunction;
-// use serde_json::json;
-
-
-// pub fn create_client() -> ChromaClient { 
-//     let client: ChromaClient = ChromaClient::new(ChromaClientOptions 
-//         { url: "http://localhost:35436".to_string(), auth: Default::default() }
-// );
-//     client
-// }
-
-// pub fn get_or_create_collection(client: &ChromaClient, collection_name: &str) -> ChromaCollection {
-//     let collection = 
-//         client.create_collection(collection_name, None, true).unwrap();
-//     collection
-// }
-
-
-// pub fn insert_document(collection: &ChromaCollection, document: serde_json::Value) {
-//     let result = collection.add(collection_entries, embedding_function)
-//     let result = collection.insert(document).unwrap();
-//     println!("Inserted document: {:?}", result);
-// }
\ No newline at end of file
+use reqwest::blocking::Client;
+use reqwest::Method;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::sync::{Mutex, OnceLock};
+use std::thread;
+use std::time::Duration;
+use thiserror::Error;
+
+use crate::chroma::chrapi::{
+    CollectionInfo, DeleteRequest, Filter, GetRequest, GetResult, Metadata, QueryResult,
+};
+use crate::util::{PortRegistry, Service};
+
+/// Where the `chroma run` sidecar listens, see `util::ports`
+pub fn chroma_url() -> String {
+    PortRegistry::shared().url(Service::Chroma)
+}
+
+const API_PREFIX: &str = "/api/v1";
+
+const MAX_ATTEMPTS: u32 = 4;
+
+/// Whether a request may be sent again after a timeout or a server error.
+/// One that may have been applied before failing, like deleting a
+/// collection, only gets one attempt, and so do callers that poll anyway.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Retry {
+    Idempotent,
+    Once,
+}
+
+// Doubles after every failed attempt
+const RETRY_DELAY: Duration = Duration::from_millis(250);
+
+#[derive(Debug, Error)]
+pub enum ChromaError {
+    #[error("Chroma server at {url} is unreachable: {source}")]
+    Unreachable {
+        url: String,
+        #[source]
+        source: reqwest::Error,
+    },
+    #[error("Chroma request timed out: {0}")]
+    Timeout(#[source] reqwest::Error),
+    #[error("Collection not found: {0}")]
+    CollectionNotFound(String),
+    #[error("Chroma rejected the request ({status}): {message}")]
+    Rejected { status: u16, message: String },
+    #[error("Chroma server error ({status}): {message}")]
+    Server { status: u16, message: String },
+    #[error("Unexpected response from Chroma: {0}")]
+    Decode(String),
+}
+
+impl ChromaError {
+    /// Connection problems and server-side failures are worth another attempt,
+    /// bad requests are not
+    pub fn is_retryable(&self) -> bool {
+        matches!(
+            self,
+            ChromaError::Unreachable { .. } | ChromaError::Timeout(_) | ChromaError::Server { .. }
+        )
+    }
+
+    fn from_response(status: u16, body: &str) -> ChromaError {
+        // Chroma answers with `{"error": ..., "message": ...}` or a bare string
+        let message = serde_json::from_str::<Value>(body)
+            .ok()
+            .and_then(|value| {
+                value
+                    .get("message")
+                    .or_else(|| value.get("error"))
+                    .and_then(Value::as_str)
+                    .map(str::to_string)
+            })
+            .unwrap_or_else(|| body.to_string());
+
+        // Older servers report a missing collection as a 500 ValueError
+        if status == 404 || message.contains("does not exist") {
+            return ChromaError::CollectionNotFound(message);
+        }
+
+        match status {
+            429 | 500..=599 => ChromaError::Server { status, message },
+            _ => ChromaError::Rejected { status, message },
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Collection {
+    pub id: String,
+    pub name: String,
+    #[serde(default)]
+    pub metadata: Option<Metadata>,
+}
+
+/// Records with precomputed embeddings, the server never embeds on its own
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct EmbeddedRecords {
+    pub ids: Vec<String>,
+    pub embeddings: Vec<Vec<f32>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub documents: Option<Vec<String>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub metadatas: Option<Vec<Metadata>>,
+}
+
+/// Blocking client for the Chroma REST API. Connections are pooled by the
+/// underlying HTTP client and collection ids are cached by name, so a single
+/// instance should be shared (see `shared`). Must not be called from inside
+/// an async task, use `spawn_blocking` there.
+pub struct ChromaClient {
+    http: Client,
+    base_url: String,
+    collection_ids: Mutex<HashMap<String, String>>,
+}
+
+impl ChromaClient {
+    pub fn new(base_url: &str) -> Result<ChromaClient, ChromaError> {
+        let http = Client::builder()
+            .pool_max_idle_per_host(8)
+            .connect_timeout(Duration::from_secs(2))
+            .timeout(Duration::from_secs(60))
+            .build()
+            .map_err(|e| ChromaError::Unreachable {
+                url: base_url.to_string(),
+                source: e,
+            })?;
+
+        Ok(ChromaClient {
+            http,
+            base_url: base_url.trim_end_matches('/').to_string(),
+            collection_ids: Mutex::new(HashMap::new()),
+        })
+    }
+
+    /// Process-wide client for the local Chroma server
+    pub fn shared() -> &'static ChromaClient {
+        static CLIENT: OnceLock<ChromaClient> = OnceLock::new();
+        CLIENT
+            .get_or_init(|| ChromaClient::new(&chroma_url()).expect("Failed to build HTTP client"))
+    }
+
+    fn send_once<T: DeserializeOwned>(
+        &self,
+        method: Method,
+        url: &str,
+        body: Option<&Value>,
+    ) -> Result<T, ChromaError> {
+        let mut request = self.http.request(method, url);
+        if let Some(body) = body {
+            request = request.json(body);
+        }
+
+        let response = request.send().map_err(|e| {
+            if e.is_timeout() {
+                ChromaError::Timeout(e)
+            } else {
+                ChromaError::Unreachable {
+                    url: self.base_url.clone(),
+                    source: e,
+                }
+            }
+        })?;
+
+        let status = response.status();
+        let text = response
+            .text()
+            .map_err(|e| ChromaError::Decode(e.to_string()))?;
+
+        if !status.is_success() {
+            return Err(ChromaError::from_response(status.as_u16(), &text));
+        }
+
+        // Some endpoints answer with an empty body
+        let text = if text.trim().is_empty() {
+            "null"
+        } else {
+            &text
+        };
+        serde_json::from_str(text).map_err(|e| ChromaError::Decode(e.to_string()))
+    }
+
+    fn send<T: DeserializeOwned>(
+        &self,
+        method: Method,
+        path: &str,
+        body: Option<&Value>,
+        retry: Retry,
+    ) -> Result<T, ChromaError> {
+        let url = format!("{}{}{}", self.base_url, API_PREFIX, path);
+        let max_attempts = match retry {
+            Retry::Idempotent => MAX_ATTEMPTS,
+            Retry::Once => 1,
+        };
+        let mut delay = RETRY_DELAY;
+        let mut attempt = 1;
+
+        loop {
+            match self.send_once(method.clone(), &url, body) {
+                Err(e) if e.is_retryable() && attempt < max_attempts => {
+                    eprintln!(
+                        "CHROMA:WARN: {} (attempt {}/{}), retrying in {:?}",
+                        e, attempt, MAX_ATTEMPTS, delay
+                    );
+                    thread::sleep(delay);
+                    delay *= 2;
+                    attempt += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+
+    /// Server time in nanoseconds, doubles as a liveness check. Tried once,
+    /// the callers poll it on their own schedule.
+    pub fn heartbeat(&self) -> Result<u64, ChromaError> {
+        let response: Value = self.send(Method::GET, "/heartbeat", None, Retry::Once)?;
+        response
+            .get("nanosecond heartbeat")
+            .and_then(Value::as_u64)
+            .ok_or_else(|| ChromaError::Decode(response.to_string()))
+    }
+
+    pub fn get_or_create_collection(&self, name: &str) -> Result<Collection, ChromaError> {
+        self.create_collection(name, None)
+    }
+
+    /// Creates the collection, or returns it if it already exists
+    pub fn create_collection(
+        &self,
+        name: &str,
+        metadata: Option<&Metadata>,
+    ) -> Result<Collection, ChromaError> {
+        let mut body = json!({ "name": name, "get_or_create": true });
+        if let Some(metadata) = metadata.filter(|m| !m.is_empty()) {
+            body["metadata"] = json!(metadata);
+        }
+
+        let collection: Collection =
+            self.send(Method::POST, "/collections", Some(&body), Retry::Idempotent)?;
+        self.remember(&collection);
+        Ok(collection)
+    }
+
+    pub fn get_collection(&self, name: &str) -> Result<Collection, ChromaError> {
+        let path = format!("/collections/{}", name);
+        let collection: Collection = self.send(Method::GET, &path, None, Retry::Idempotent)?;
+        self.remember(&collection);
+        Ok(collection)
+    }
+
+    pub fn list_collections(&self) -> Result<Vec<CollectionInfo>, ChromaError> {
+        let collections: Vec<Collection> =
+            self.send(Method::GET, "/collections", None, Retry::Idempotent)?;
+        Ok(collections
+            .into_iter()
+            .map(|c| CollectionInfo {
+                name: c.name,
+                metadata: c.metadata,
+            })
+            .collect())
+    }
+
+    pub fn delete_collection(&self, name: &str) -> Result<(), ChromaError> {
+        self.forget(name);
+        let path = format!("/collections/{}", name);
+        let _: Value = self.send(Method::DELETE, &path, None, Retry::Once)?;
+        Ok(())
+    }
+
+    fn remember(&self, collection: &Collection) {
+        if let Ok(mut ids) = self.collection_ids.lock() {
+            ids.insert(collection.name.clone(), collection.id.clone());
+        }
+    }
+
+    fn forget(&self, name: &str) {
+        if let Ok(mut ids) = self.collection_ids.lock() {
+            ids.remove(name);
+        }
+    }
+
+    fn collection_id(&self, name: &str) -> Result<String, ChromaError> {
+        let cached = self
+            .collection_ids
+            .lock()
+            .ok()
+            .and_then(|ids| ids.get(name).cloned());
+
+        match cached {
+            Some(id) => Ok(id),
+            None => Ok(self.get_collection(name)?.id),
+        }
+    }
+
+    // Runs a request against a collection's id, looking the id up again once
+    // if the collection was dropped and recreated since it was cached. Every
+    // endpoint used here can be repeated safely: reads, `upsert` and `delete`.
+    fn on_collection<T: DeserializeOwned>(
+        &self,
+        name: &str,
+        method: Method,
+        endpoint: &str,
+        body: Option<&Value>,
+    ) -> Result<T, ChromaError> {
+        let id = self.collection_id(name)?;
+        let path = format!("/collections/{}/{}", id, endpoint);
+
+        match self.send(method.clone(), &path, body, Retry::Idempotent) {
+            Err(ChromaError::CollectionNotFound(_)) => {
+                self.forget(name);
+                let id = self.collection_id(name)?;
+                let path = format!("/collections/{}/{}", id, endpoint);
+                self.send(method, &path, body, Retry::Idempotent)
+            }
+            result => result,
+        }
+    }
+
+    pub fn count(&self, name: &str) -> Result<usize, ChromaError> {
+        self.on_collection(name, Method::GET, "count", None)
+    }
+
+    pub fn upsert(&self, name: &str, records: &EmbeddedRecords) -> Result<(), ChromaError> {
+        let body = to_body(records)?;
+        let _: Value = self.on_collection(name, Method::POST, "upsert", Some(&body))?;
+        Ok(())
+    }
+
+    pub fn get(&self, request: &GetRequest) -> Result<GetResult, ChromaError> {
+        let body = to_body(request)?;
+        self.on_collection(&request.collection_name, Method::POST, "get", Some(&body))
+    }
+
+    pub fn peek(&self, name: &str, limit: usize) -> Result<GetResult, ChromaError> {
+        let body = json!({ "limit": limit });
+        self.on_collection(name, Method::POST, "get", Some(&body))
+    }
+
+    pub fn delete(&self, request: &DeleteRequest) -> Result<(), ChromaError> {
+        let body = to_body(request)?;
+        let _: Value = self.on_collection(
+            &request.collection_name,
+            Method::POST,
+            "delete",
+            Some(&body),
+        )?;
+        Ok(())
+    }
+
+    pub fn query(
+        &self,
+        name: &str,
+        query_embeddings: &[Vec<f32>],
+        n_results: usize,
+        filter: &Filter,
+        include: Option<&[String]>,
+    ) -> Result<QueryResult, ChromaError> {
+        let mut body = to_body(filter)?;
+        body["query_embeddings"] = json!(query_embeddings);
+        body["n_results"] = json!(n_results);
+        if let Some(include) = include {
+            body["include"] = json!(include);
+        }
+
+        self.on_collection(name, Method::POST, "query", Some(&body))
+    }
+}
+
+// The collection is addressed by the URL, not the body
+fn to_body<T: Serialize>(value: &T) -> Result<Value, ChromaError> {
+    let mut body = serde_json::to_value(value).map_err(|e| ChromaError::Decode(e.to_string()))?;
+    if let Value::Object(map) = &mut body {
+        map.remove("collection_name");
+    }
+    Ok(body)
+}
@@ -1,5 +1,9 @@
 pub mod chrapi;
-// pub mod chrust;
+pub mod chrust;
 
 pub use chrapi::run_python_sdk;
 pub use chrapi::Action;
+pub use chrust::{ChromaClient, ChromaError};
+pub use chrapi::{query, upsert};
+pub use chrapi::{CollectionInfo, GetResult, Metadata, QueryResult};
+pub use chrapi::{DeleteRequest, Filter, GetRequest, QueryRequest, UpsertRequest};
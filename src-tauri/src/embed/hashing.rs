@@ -0,0 +1,65 @@
+use sha2::{Digest, Sha256};
+use std::error::Error;
+
+use crate::embed::{normalize, Embedder};
+
+pub const DEFAULT_DIMENSION: usize = 256;
+
+/// Feature hashing over lowercased words and their character trigrams.
+/// Identical input always gives identical vectors and texts sharing words
+/// land close together, which is all tests need from an embedding.
+pub struct HashingEmbedder {
+    model: String,
+    dimension: usize,
+}
+
+impl HashingEmbedder {
+    pub fn new(dimension: usize) -> HashingEmbedder {
+        let dimension = dimension.max(1);
+        HashingEmbedder {
+            model: format!("hashing-{}", dimension),
+            dimension,
+        }
+    }
+
+    fn add_feature(&self, vector: &mut [f32], feature: &str, weight: f32) {
+        let digest = Sha256::digest(feature.as_bytes());
+        let bucket = u64::from_le_bytes(digest[..8].try_into().unwrap());
+        let sign = if digest[8] & 1 == 0 { 1.0 } else { -1.0 };
+        vector[(bucket % self.dimension as u64) as usize] += sign * weight;
+    }
+
+    fn embed_one(&self, text: &str) -> Vec<f32> {
+        let mut vector = vec![0.0; self.dimension];
+
+        for word in text
+            .split(|c: char| !c.is_alphanumeric())
+            .filter(|w| !w.is_empty())
+        {
+            let word = word.to_lowercase();
+            self.add_feature(&mut vector, &word, 1.0);
+
+            let padded: Vec<char> = format!("^{}$", word).chars().collect();
+            for trigram in padded.windows(3) {
+                self.add_feature(&mut vector, &trigram.iter().collect::<String>(), 0.5);
+            }
+        }
+
+        normalize(&mut vector);
+        vector
+    }
+}
+
+impl Embedder for HashingEmbedder {
+    fn model(&self) -> &str {
+        &self.model
+    }
+
+    fn dimension(&self) -> usize {
+        self.dimension
+    }
+
+    fn embed(&self, texts: &[String]) -> Result<Vec<Vec<f32>>, Box<dyn Error>> {
+        Ok(texts.iter().map(|t| self.embed_one(t)).collect())
+    }
+}
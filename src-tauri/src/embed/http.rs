@@ -0,0 +1,129 @@
+use reqwest::blocking::Client;
+use serde::Deserialize;
+use serde_json::json;
+use std::error::Error;
+use std::time::Duration;
+
+use crate::embed::Embedder;
+use crate::util::EmbedderConfig;
+
+#[derive(Deserialize)]
+struct EmbeddingData {
+    index: usize,
+    embedding: Vec<f32>,
+}
+
+#[derive(Deserialize)]
+struct EmbeddingResponse {
+    data: Vec<EmbeddingData>,
+}
+
+/// Client for an OpenAI-compatible `POST {url}/embeddings` endpoint, which
+/// covers OpenAI itself as well as local Ollama and llama.cpp servers
+pub struct HttpEmbedder {
+    http: Client,
+    url: String,
+    model: String,
+    api_key: String,
+    batch_size: usize,
+    dimension: usize,
+}
+
+impl HttpEmbedder {
+    /// Asks the server for one embedding when no dimension is configured, so
+    /// this fails if the server isn't reachable
+    pub fn new(config: &EmbedderConfig) -> Result<HttpEmbedder, Box<dyn Error>> {
+        let http = Client::builder()
+            .connect_timeout(Duration::from_secs(2))
+            .timeout(Duration::from_secs(120))
+            .build()?;
+
+        let mut embedder = HttpEmbedder {
+            http,
+            url: config.url.trim_end_matches('/').to_string(),
+            model: config.model.clone(),
+            api_key: config.api_key.clone(),
+            batch_size: config.batch_size.max(1),
+            dimension: config.dimension.unwrap_or(0),
+        };
+
+        if embedder.dimension == 0 {
+            let probe = embedder.request(&["dimension probe".to_string()])?;
+            embedder.dimension = probe.first().map(Vec::len).unwrap_or(0);
+        }
+
+        if embedder.dimension == 0 {
+            return Err(Box::from(format!(
+                "{} returned an empty embedding for {}",
+                embedder.url, embedder.model
+            )));
+        }
+
+        Ok(embedder)
+    }
+
+    fn request(&self, texts: &[String]) -> Result<Vec<Vec<f32>>, Box<dyn Error>> {
+        let mut request = self
+            .http
+            .post(format!("{}/embeddings", self.url))
+            .json(&json!({ "model": self.model, "input": texts }));
+        if !self.api_key.is_empty() {
+            request = request.bearer_auth(&self.api_key);
+        }
+
+        let response = request.send()?;
+        let status = response.status();
+        if !status.is_success() {
+            return Err(Box::from(format!(
+                "Embedding request to {} failed ({}): {}",
+                self.url,
+                status,
+                response.text().unwrap_or_default()
+            )));
+        }
+
+        let mut data = response.json::<EmbeddingResponse>()?.data;
+        if data.len() != texts.len() {
+            return Err(Box::from(format!(
+                "Asked {} for {} embeddings, got {}",
+                self.url,
+                texts.len(),
+                data.len()
+            )));
+        }
+
+        // The spec doesn't promise the input order
+        data.sort_by_key(|d| d.index);
+        Ok(data.into_iter().map(|d| d.embedding).collect())
+    }
+}
+
+impl Embedder for HttpEmbedder {
+    fn model(&self) -> &str {
+        &self.model
+    }
+
+    fn dimension(&self) -> usize {
+        self.dimension
+    }
+
+    fn embed(&self, texts: &[String]) -> Result<Vec<Vec<f32>>, Box<dyn Error>> {
+        let mut embeddings = Vec::with_capacity(texts.len());
+
+        for batch in texts.chunks(self.batch_size) {
+            for embedding in self.request(batch)? {
+                if embedding.len() != self.dimension {
+                    return Err(Box::from(format!(
+                        "{} returned a {}-dimensional embedding, expected {}",
+                        self.model,
+                        embedding.len(),
+                        self.dimension
+                    )));
+                }
+                embeddings.push(embedding);
+            }
+        }
+
+        Ok(embeddings)
+    }
+}
@@ -0,0 +1,132 @@
+pub mod hashing;
+pub mod http;
+#[cfg(feature = "onnx")]
+pub mod onnx;
+#[cfg(feature = "onnx")]
+pub mod wordpiece;
+
+use serde_json::{json, Value};
+use std::error::Error;
+use std::sync::{Arc, Mutex};
+
+use crate::chroma::Metadata;
+use crate::store::VectorStore;
+use crate::util::{EmbedderConfig, EmbedderProvider};
+
+pub use hashing::HashingEmbedder;
+pub use http::HttpEmbedder;
+#[cfg(feature = "onnx")]
+pub use onnx::OnnxEmbedder;
+
+/// Collection metadata keys recording which model wrote the vectors
+pub const MODEL_KEY: &str = "embedding:model";
+pub const DIMENSION_KEY: &str = "embedding:dimension";
+
+/// Turns text into vectors. Implementations are blocking and must not be
+/// called from inside an async task, use `spawn_blocking` there.
+pub trait Embedder: Send + Sync {
+    /// Identifies the model, stored with every collection it writes to
+    fn model(&self) -> &str;
+
+    fn dimension(&self) -> usize;
+
+    /// One vector per text, in order
+    fn embed(&self, texts: &[String]) -> Result<Vec<Vec<f32>>, Box<dyn Error>>;
+}
+
+pub fn normalize(vector: &mut [f32]) {
+    let norm = vector.iter().map(|x| x * x).sum::<f32>().sqrt();
+    if norm > 0.0 {
+        vector.iter_mut().for_each(|x| *x /= norm);
+    }
+}
+
+/// Builds the configured embedder, `None` for the Python OpenCLIP path
+pub fn open_embedder(config: &EmbedderConfig) -> Result<Option<Arc<dyn Embedder>>, Box<dyn Error>> {
+    Ok(match config.provider {
+        EmbedderProvider::Clip => None,
+        EmbedderProvider::Http => Some(Arc::new(HttpEmbedder::new(config)?)),
+        EmbedderProvider::Hashing => Some(Arc::new(HashingEmbedder::new(
+            config.dimension.unwrap_or(hashing::DEFAULT_DIMENSION),
+        ))),
+        #[cfg(feature = "onnx")]
+        EmbedderProvider::Onnx => Some(Arc::new(OnnxEmbedder::new(config)?)),
+        #[cfg(not(feature = "onnx"))]
+        EmbedderProvider::Onnx => {
+            return Err(Box::from(
+                "This build has no ONNX support, rebuild with `--features onnx`",
+            ))
+        }
+    })
+}
+
+/// The embedder picked in the config. A failed build (e.g. the HTTP server
+/// isn't up yet) is retried on the next call instead of being cached.
+pub fn shared() -> Result<Option<Arc<dyn Embedder>>, Box<dyn Error>> {
+    static EMBEDDER: Mutex<Option<Option<Arc<dyn Embedder>>>> = Mutex::new(None);
+    let mut embedder = EMBEDDER.lock().unwrap_or_else(|e| e.into_inner());
+
+    if let Some(embedder) = embedder.as_ref() {
+        return Ok(embedder.clone());
+    }
+
+    let config = crate::util::load_config()
+        .map(|cfg| cfg.embedder)
+        .unwrap_or_default();
+    let opened = open_embedder(&config)?;
+    if let Some(opened) = &opened {
+        println!(
+            "EMBED:INFO: Using {} ({} dimensions)",
+            opened.model(),
+            opened.dimension()
+        );
+    }
+
+    *embedder = Some(opened.clone());
+    Ok(opened)
+}
+
+pub fn collection_metadata(embedder: &dyn Embedder) -> Metadata {
+    let mut metadata = Metadata::new();
+    metadata.insert(MODEL_KEY.to_string(), json!(embedder.model()));
+    metadata.insert(DIMENSION_KEY.to_string(), json!(embedder.dimension()));
+    metadata
+}
+
+/// Creates `collection` tagged with the embedder's model, or checks that an
+/// existing one was written by the same model. Vectors from different models
+/// aren't comparable, so a mismatch has to be fixed by re-indexing.
+pub fn check_collection(
+    store: &dyn VectorStore,
+    collection: &str,
+    embedder: &dyn Embedder,
+) -> Result<(), Box<dyn Error>> {
+    let info = store.get_or_create_collection(collection, &collection_metadata(embedder))?;
+    let metadata = info.metadata.unwrap_or_default();
+
+    let model = metadata.get(MODEL_KEY).and_then(Value::as_str);
+    let dimension = metadata
+        .get(DIMENSION_KEY)
+        .and_then(Value::as_u64)
+        .map(|d| d as usize);
+
+    match (model, dimension) {
+        (Some(model), Some(dimension))
+            if model == embedder.model() && dimension == embedder.dimension() =>
+        {
+            Ok(())
+        }
+        (Some(model), dimension) => Err(Box::from(format!(
+            "Collection {} was embedded with {} ({} dimensions) but {} ({} dimensions) is configured, delete the collection to re-index",
+            collection,
+            model,
+            dimension.map(|d| d.to_string()).unwrap_or_else(|| "?".to_string()),
+            embedder.model(),
+            embedder.dimension()
+        ))),
+        (None, _) => Err(Box::from(format!(
+            "Collection {} was not written by a configured embedder, delete the collection to re-index",
+            collection
+        ))),
+    }
+}
@@ -0,0 +1,157 @@
+use ort::session::Session;
+use ort::value::Tensor;
+use std::error::Error;
+use std::path::Path;
+use std::sync::Mutex;
+
+use crate::embed::wordpiece::WordPiece;
+use crate::embed::{normalize, Embedder};
+use crate::util::EmbedderConfig;
+
+// BERT-style models can't attend past this many positions
+const MAX_TOKENS: usize = 512;
+
+/// Sentence-transformers style model (e.g. all-MiniLM-L6-v2) run on the CPU.
+/// Token embeddings are mean-pooled over the attention mask unless the model
+/// already has a `sentence_embedding` output, and vectors are normalized.
+pub struct OnnxEmbedder {
+    // `Session::run` needs `&mut`, inference is serialized per model
+    session: Mutex<Session>,
+    tokenizer: WordPiece,
+    model: String,
+    batch_size: usize,
+    dimension: usize,
+}
+
+impl OnnxEmbedder {
+    pub fn new(config: &EmbedderConfig) -> Result<OnnxEmbedder, Box<dyn Error>> {
+        let model_path = Path::new(&config.model);
+        let vocab_path = if config.vocab.is_empty() {
+            model_path.with_file_name("vocab.txt")
+        } else {
+            config.vocab.clone().into()
+        };
+
+        let session = Session::builder()?
+            .with_intra_threads(num_cpus::get())?
+            .commit_from_file(model_path)?;
+
+        let mut embedder = OnnxEmbedder {
+            session: Mutex::new(session),
+            tokenizer: WordPiece::from_file(&vocab_path)?,
+            model: model_path
+                .file_stem()
+                .map(|s| s.to_string_lossy().to_string())
+                .unwrap_or_else(|| config.model.clone()),
+            batch_size: config.batch_size.max(1),
+            dimension: 0,
+        };
+
+        embedder.dimension = match config.dimension {
+            Some(dimension) => dimension,
+            None => embedder
+                .run(&["dimension probe".to_string()])?
+                .first()
+                .map(Vec::len)
+                .unwrap_or(0),
+        };
+
+        Ok(embedder)
+    }
+
+    fn run(&self, texts: &[String]) -> Result<Vec<Vec<f32>>, Box<dyn Error>> {
+        let encoded: Vec<Vec<i64>> = texts
+            .iter()
+            .map(|t| self.tokenizer.encode(t, MAX_TOKENS))
+            .collect();
+        let batch = encoded.len();
+        let length = encoded.iter().map(Vec::len).max().unwrap_or(0);
+
+        // Right-padded with zeros, which the mask hides from the model
+        let mut input_ids = vec![0i64; batch * length];
+        let mut attention_mask = vec![0i64; batch * length];
+        for (row, ids) in encoded.iter().enumerate() {
+            input_ids[row * length..row * length + ids.len()].copy_from_slice(ids);
+            attention_mask[row * length..row * length + ids.len()].fill(1);
+        }
+
+        let shape = [batch as i64, length as i64];
+        let mut session = self.session.lock().unwrap_or_else(|e| e.into_inner());
+        let mut inputs = ort::inputs! {
+            "input_ids" => Tensor::from_array((shape, input_ids))?,
+            "attention_mask" => Tensor::from_array((shape, attention_mask.clone()))?,
+        };
+        if session.inputs.iter().any(|i| i.name == "token_type_ids") {
+            inputs.push((
+                "token_type_ids".into(),
+                Tensor::from_array((shape, vec![0i64; batch * length]))?.into(),
+            ));
+        }
+
+        let outputs = session.run(inputs)?;
+        let output = match outputs.get("sentence_embedding") {
+            Some(output) => output,
+            None => &outputs[0],
+        };
+        let (dims, values) = output.try_extract_tensor::<f32>()?;
+
+        let mut embeddings = Vec::with_capacity(batch);
+        match **dims {
+            // Already pooled, `[batch, dimension]`
+            [_, dimension] => {
+                for row in values.chunks(dimension as usize) {
+                    embeddings.push(row.to_vec());
+                }
+            }
+            // Token embeddings, `[batch, length, dimension]`
+            [_, tokens, dimension] => {
+                let (tokens, dimension) = (tokens as usize, dimension as usize);
+                for row in 0..batch {
+                    let mut pooled = vec![0.0; dimension];
+                    let mut count = 0.0;
+                    for token in 0..tokens {
+                        if attention_mask[row * length + token] == 0 {
+                            continue;
+                        }
+                        let offset = (row * tokens + token) * dimension;
+                        for (sum, value) in
+                            pooled.iter_mut().zip(&values[offset..offset + dimension])
+                        {
+                            *sum += value;
+                        }
+                        count += 1.0;
+                    }
+                    pooled.iter_mut().for_each(|x| *x /= f32::max(count, 1.0));
+                    embeddings.push(pooled);
+                }
+            }
+            _ => {
+                return Err(Box::from(format!(
+                    "Unexpected output shape {:?} from {}",
+                    &**dims, self.model
+                )))
+            }
+        }
+
+        embeddings.iter_mut().for_each(|e| normalize(e));
+        Ok(embeddings)
+    }
+}
+
+impl Embedder for OnnxEmbedder {
+    fn model(&self) -> &str {
+        &self.model
+    }
+
+    fn dimension(&self) -> usize {
+        self.dimension
+    }
+
+    fn embed(&self, texts: &[String]) -> Result<Vec<Vec<f32>>, Box<dyn Error>> {
+        let mut embeddings = Vec::with_capacity(texts.len());
+        for batch in texts.chunks(self.batch_size) {
+            embeddings.extend(self.run(batch)?);
+        }
+        Ok(embeddings)
+    }
+}
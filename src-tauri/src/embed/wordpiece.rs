@@ -0,0 +1,122 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::path::Path;
+
+const MAX_WORD_CHARS: usize = 100;
+
+/// BERT's uncased WordPiece tokenizer, enough for the sentence-transformers
+/// models exported to ONNX along with their `vocab.txt`
+pub struct WordPiece {
+    vocab: HashMap<String, i64>,
+    cls: i64,
+    sep: i64,
+    unk: i64,
+}
+
+fn is_punctuation(c: char) -> bool {
+    c.is_ascii_punctuation() || (!c.is_alphanumeric() && !c.is_whitespace())
+}
+
+impl WordPiece {
+    pub fn from_file(path: &Path) -> Result<WordPiece, Box<dyn Error>> {
+        let vocab: HashMap<String, i64> = std::fs::read_to_string(path)?
+            .lines()
+            .enumerate()
+            .map(|(i, token)| (token.to_string(), i as i64))
+            .collect();
+
+        let special = |token: &str| {
+            vocab
+                .get(token)
+                .copied()
+                .ok_or_else(|| format!("{} has no {} token", path.display(), token))
+        };
+
+        Ok(WordPiece {
+            cls: special("[CLS]")?,
+            sep: special("[SEP]")?,
+            unk: special("[UNK]")?,
+            vocab,
+        })
+    }
+
+    // Whitespace separates words and every punctuation mark is its own word
+    fn words(text: &str) -> Vec<String> {
+        let mut words = Vec::new();
+        let mut current = String::new();
+
+        for c in text.chars().flat_map(char::to_lowercase) {
+            if c.is_whitespace() || c.is_control() || is_punctuation(c) {
+                if !current.is_empty() {
+                    words.push(std::mem::take(&mut current));
+                }
+                if is_punctuation(c) {
+                    words.push(c.to_string());
+                }
+            } else {
+                current.push(c);
+            }
+        }
+        if !current.is_empty() {
+            words.push(current);
+        }
+
+        words
+    }
+
+    // Greedy longest-prefix match, continuation pieces are prefixed `##`
+    fn pieces(&self, word: &str, ids: &mut Vec<i64>) {
+        let chars: Vec<char> = word.chars().collect();
+        if chars.len() > MAX_WORD_CHARS {
+            ids.push(self.unk);
+            return;
+        }
+
+        let mut pieces = Vec::new();
+        let mut start = 0;
+        while start < chars.len() {
+            let mut end = chars.len();
+            let mut found = None;
+
+            while start < end {
+                let piece: String = chars[start..end].iter().collect();
+                let piece = if start > 0 {
+                    format!("##{}", piece)
+                } else {
+                    piece
+                };
+                if let Some(&id) = self.vocab.get(&piece) {
+                    found = Some(id);
+                    break;
+                }
+                end -= 1;
+            }
+
+            match found {
+                Some(id) => pieces.push(id),
+                None => {
+                    ids.push(self.unk);
+                    return;
+                }
+            }
+            start = end;
+        }
+
+        ids.extend(pieces);
+    }
+
+    /// Token ids wrapped in `[CLS]` / `[SEP]`, at most `max_tokens` long
+    pub fn encode(&self, text: &str, max_tokens: usize) -> Vec<i64> {
+        let mut ids = vec![self.cls];
+        for word in Self::words(text) {
+            self.pieces(&word, &mut ids);
+            if ids.len() >= max_tokens - 1 {
+                break;
+            }
+        }
+
+        ids.truncate(max_tokens - 1);
+        ids.push(self.sep);
+        ids
+    }
+}
@@ -0,0 +1,610 @@
+use serde::Serialize;
+use std::ops::Range;
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Language {
+    Rust,
+    Python,
+    JavaScript,
+    TypeScript,
+    Go,
+    Java,
+    Kotlin,
+    C,
+    Cpp,
+    CSharp,
+    Swift,
+    Php,
+}
+
+impl Language {
+    pub fn from_extension(extension: &str) -> Option<Language> {
+        let language = match extension {
+            "rs" => Language::Rust,
+            "py" | "pyw" => Language::Python,
+            "js" | "jsx" | "mjs" | "cjs" => Language::JavaScript,
+            "ts" | "tsx" | "mts" | "cts" => Language::TypeScript,
+            "go" => Language::Go,
+            "java" => Language::Java,
+            "kt" | "kts" => Language::Kotlin,
+            "c" | "h" => Language::C,
+            "cc" | "cpp" | "cxx" | "hpp" | "hh" | "hxx" => Language::Cpp,
+            "cs" => Language::CSharp,
+            "swift" => Language::Swift,
+            "php" => Language::Php,
+            _ => return None,
+        };
+        Some(language)
+    }
+
+    pub fn from_name(name: &str) -> Option<Language> {
+        let language = match name {
+            "rust" => Language::Rust,
+            "python" => Language::Python,
+            "javascript" => Language::JavaScript,
+            "typescript" => Language::TypeScript,
+            "go" => Language::Go,
+            "java" => Language::Java,
+            "kotlin" => Language::Kotlin,
+            "c" => Language::C,
+            "cpp" => Language::Cpp,
+            "csharp" => Language::CSharp,
+            "swift" => Language::Swift,
+            "php" => Language::Php,
+            _ => return None,
+        };
+        Some(language)
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Language::Rust => "rust",
+            Language::Python => "python",
+            Language::JavaScript => "javascript",
+            Language::TypeScript => "typescript",
+            Language::Go => "go",
+            Language::Java => "java",
+            Language::Kotlin => "kotlin",
+            Language::C => "c",
+            Language::Cpp => "cpp",
+            Language::CSharp => "csharp",
+            Language::Swift => "swift",
+            Language::Php => "php",
+        }
+    }
+
+    /// Languages without a keyword in front of function definitions
+    fn has_c_style_functions(&self) -> bool {
+        matches!(
+            self,
+            Language::C | Language::Cpp | Language::Java | Language::CSharp
+        )
+    }
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct Symbol {
+    pub name: String,
+    pub kind: String,
+    pub language: String,
+}
+
+/// A byte range of the source, either a top-level item or the code between
+/// items (imports, constants, comments) when `symbol` is `None`
+#[derive(Debug, Clone)]
+pub struct Segment {
+    pub range: Range<usize>,
+    pub symbol: Option<Symbol>,
+}
+
+struct Line<'a> {
+    start: usize,
+    end: usize,
+    text: &'a str,
+    depth_before: i32,
+    depth_after: i32,
+    max_depth: i32,
+}
+
+const MODIFIERS: &[&str] = &[
+    "pub",
+    "export",
+    "default",
+    "async",
+    "unsafe",
+    "extern",
+    "\"C\"",
+    "static",
+    "public",
+    "private",
+    "protected",
+    "internal",
+    "abstract",
+    "final",
+    "sealed",
+    "open",
+    "override",
+    "virtual",
+    "inline",
+    "partial",
+    "data",
+    "declare",
+    "readonly",
+    "suspend",
+    "const",
+];
+
+const CONTROL_KEYWORDS: &[&str] = &[
+    "if", "else", "for", "while", "switch", "return", "do", "catch", "try", "case", "new", "throw",
+    "sizeof", "using", "typedef", "delete",
+];
+
+fn keyword_kind(language: Language, keyword: &str) -> Option<&'static str> {
+    let kind = match (language, keyword) {
+        (Language::Rust, "fn") => "function",
+        (Language::Rust, "impl") => "impl",
+        (Language::Rust, "mod") => "module",
+        (Language::Rust, "macro_rules!") => "macro",
+        (Language::Rust, "union") => "struct",
+        (Language::Go, "func") => "function",
+        (Language::Go, "type") => "type",
+        (Language::Kotlin, "fun") => "function",
+        (Language::Kotlin, "object") => "class",
+        (Language::Swift, "func") => "function",
+        (Language::Swift, "extension") => "impl",
+        (Language::Swift, "protocol") => "interface",
+        (Language::Php, "function") => "function",
+        (Language::JavaScript | Language::TypeScript, "function" | "function*") => "function",
+        (Language::TypeScript, "namespace" | "module") => "module",
+        (Language::Cpp | Language::CSharp, "namespace") => "module",
+        (_, "struct") => "struct",
+        (_, "enum") => "enum",
+        (_, "class") => "class",
+        (_, "interface") => "interface",
+        (_, "trait") => "trait",
+        (_, "record") => "class",
+        _ => return None,
+    };
+    Some(kind)
+}
+
+fn is_identifier_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_' || c == '$'
+}
+
+fn leading_identifier(text: &str) -> &str {
+    let end = text
+        .find(|c: char| !is_identifier_char(c))
+        .unwrap_or(text.len());
+    &text[..end]
+}
+
+/// Strips visibility and other modifiers off the front of a line, including
+/// Rust's `pub(crate)` and `extern "C"` forms. `const` is kept for JavaScript
+/// where it introduces a binding rather than qualifying a function.
+fn strip_modifiers(language: Language, mut text: &str) -> &str {
+    let keep_const = matches!(language, Language::JavaScript | Language::TypeScript);
+
+    loop {
+        let trimmed = text.trim_start();
+        let word = trimmed.split_whitespace().next().unwrap_or("");
+
+        if word.starts_with("pub(") {
+            match trimmed.find(')') {
+                Some(close) => text = &trimmed[close + 1..],
+                None => return trimmed,
+            }
+        } else if MODIFIERS.contains(&word) && !(keep_const && word == "const") {
+            text = &trimmed[word.len()..];
+        } else {
+            return trimmed;
+        }
+    }
+}
+
+/// Recognises the first line of an item, returning its kind and name
+fn parse_header(language: Language, line: &str) -> Option<(&'static str, String)> {
+    let trimmed = line.trim();
+    // Comments, `#[derive]` style attributes and C preprocessor lines
+    let is_hash_line = trimmed.starts_with('#') && language != Language::Python;
+    if trimmed.is_empty() || trimmed.starts_with("//") || is_hash_line {
+        return None;
+    }
+
+    let rest = strip_modifiers(language, trimmed);
+    let keyword = rest.split_whitespace().next().unwrap_or("");
+    let after = rest[keyword.len()..].trim_start();
+
+    if language == Language::Python {
+        // `async def` has already lost its `async` to `strip_modifiers`
+        return match keyword {
+            "def" => Some(("function", leading_identifier(after).to_string())),
+            "class" => Some(("class", leading_identifier(after).to_string())),
+            _ => None,
+        };
+    }
+
+    if let Some(kind) = keyword_kind(language, keyword) {
+        let name = match kind {
+            // `impl<T> Display for Wrapper<T>` reads best as the whole signature
+            "impl" => after
+                .split(['{', '\n'])
+                .next()
+                .unwrap_or("")
+                .split(" where ")
+                .next()
+                .unwrap_or("")
+                .trim()
+                .to_string(),
+            _ => {
+                // Go methods put the receiver first: `func (s *Server) Start()`
+                let after = if after.starts_with('(') {
+                    after
+                        .split_once(')')
+                        .map(|(_, r)| r.trim_start())
+                        .unwrap_or(after)
+                } else {
+                    after
+                };
+                leading_identifier(after.trim_start_matches('*')).to_string()
+            }
+        };
+        return Some((kind, name));
+    }
+
+    // `const handler = async (req) => {` and `const f = function () {`
+    if matches!(language, Language::JavaScript | Language::TypeScript)
+        && matches!(keyword, "let" | "var" | "const")
+    {
+        let name = leading_identifier(after);
+        let (_, value) = after.split_once('=')?;
+        if !name.is_empty() && (value.contains("=>") || value.trim_start().starts_with("function"))
+        {
+            return Some(("function", name.to_string()));
+        }
+        return None;
+    }
+
+    if language.has_c_style_functions() {
+        let (before, _) = rest.split_once('(')?;
+        if before.contains('=')
+            || CONTROL_KEYWORDS.contains(&keyword)
+            || rest.trim_end().ends_with(';')
+        {
+            return None;
+        }
+
+        let name: String = before
+            .trim_end()
+            .rsplit(|c: char| !(is_identifier_char(c) || c == ':' || c == '~'))
+            .next()
+            .unwrap_or("")
+            .to_string();
+        // A bare call like `foo(...)` has nothing in front of the name
+        if !name.is_empty() && before.trim_end().len() > name.len() {
+            return Some(("function", name));
+        }
+    }
+
+    None
+}
+
+#[derive(Default)]
+struct ScanState {
+    in_block_comment: bool,
+    in_template: bool,
+}
+
+/// Tracks brace depth through a line, skipping strings and comments
+fn scan_braces(
+    line: &str,
+    language: Language,
+    state: &mut ScanState,
+    depth: &mut i32,
+    max_depth: &mut i32,
+) {
+    let chars: Vec<char> = line.chars().collect();
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+        let next = chars.get(i + 1).copied();
+
+        if state.in_block_comment {
+            if c == '*' && next == Some('/') {
+                state.in_block_comment = false;
+                i += 1;
+            }
+        } else if state.in_template {
+            if c == '\\' {
+                i += 1;
+            } else if c == '`' {
+                state.in_template = false;
+            }
+        } else if c == '/' && next == Some('/') {
+            return;
+        } else if c == '/' && next == Some('*') {
+            state.in_block_comment = true;
+            i += 1;
+        } else if c == '`' && matches!(language, Language::JavaScript | Language::TypeScript) {
+            state.in_template = true;
+        } else if c == '"' || (c == '\'' && language != Language::Rust) {
+            // Skip to the closing quote on this line
+            i += 1;
+            while i < chars.len() && chars[i] != c {
+                if chars[i] == '\\' {
+                    i += 1;
+                }
+                i += 1;
+            }
+        } else if c == '\'' && language == Language::Rust {
+            // Char literals, but not lifetimes: 'a' and '\n' close within a few chars
+            if next == Some('\\') {
+                if let Some(close) = chars[i + 2..].iter().position(|&ch| ch == '\'') {
+                    i += close + 2;
+                }
+            } else if chars.get(i + 2) == Some(&'\'') {
+                i += 2;
+            }
+        } else if c == '{' {
+            *depth += 1;
+            *max_depth = (*max_depth).max(*depth);
+        } else if c == '}' {
+            *depth -= 1;
+        }
+
+        i += 1;
+    }
+}
+
+fn split_lines<'a>(text: &'a str, range: &Range<usize>, language: Language) -> Vec<Line<'a>> {
+    let mut lines = Vec::new();
+    let mut state = ScanState::default();
+    let mut depth = 0;
+    let mut offset = range.start;
+
+    for text_line in text[range.clone()].split_inclusive('\n') {
+        let depth_before = depth;
+        let mut max_depth = depth;
+        if language != Language::Python {
+            scan_braces(text_line, language, &mut state, &mut depth, &mut max_depth);
+        }
+
+        lines.push(Line {
+            start: offset,
+            end: offset + text_line.len(),
+            text: text_line,
+            depth_before,
+            depth_after: depth,
+            max_depth,
+        });
+        offset += text_line.len();
+    }
+
+    lines
+}
+
+fn indent_of(line: &str) -> usize {
+    line.len() - line.trim_start().len()
+}
+
+fn is_preamble(language: Language, line: &str) -> bool {
+    let trimmed = line.trim_start();
+    match language {
+        Language::Python => trimmed.starts_with('@'),
+        _ => {
+            trimmed.starts_with("///")
+                || trimmed.starts_with("//!")
+                || trimmed.starts_with("#[")
+                || trimmed.starts_with("/**")
+                || trimmed.starts_with("* ")
+                || trimmed == "*"
+                || trimmed.starts_with("*/")
+                || trimmed.starts_with('@')
+                || trimmed.starts_with("//")
+        }
+    }
+}
+
+/// Finds the last line of a brace-delimited item whose header is `lines[i]`
+fn brace_item_end(lines: &[Line], i: usize, depth: i32) -> Option<usize> {
+    // Signatures can wrap over a few lines before the opening brace
+    const MAX_HEADER_LINES: usize = 12;
+    let mut opened = false;
+
+    for (j, line) in lines.iter().enumerate().skip(i) {
+        opened |= line.max_depth > depth;
+
+        if line.depth_after <= depth && (opened || line.text.trim_end().ends_with(';')) {
+            return Some(j);
+        }
+        if !opened && j - i >= MAX_HEADER_LINES {
+            return None;
+        }
+    }
+
+    // Unbalanced braces, the item runs to the end of the file
+    opened.then(|| lines.len() - 1)
+}
+
+/// Finds the last line of an indentation-delimited (Python) item
+fn indent_item_end(lines: &[Line], i: usize, indent: usize) -> usize {
+    let mut end = i;
+
+    for (j, line) in lines.iter().enumerate().skip(i + 1) {
+        let trimmed = line.text.trim();
+        if trimmed.is_empty() {
+            continue;
+        }
+
+        // Closing brackets of a wrapped signature sit at the item's own indent
+        let continuation = trimmed.starts_with(')') || trimmed.starts_with(']');
+        if indent_of(line.text) <= indent && !continuation {
+            break;
+        }
+        end = j;
+    }
+
+    end
+}
+
+struct Item {
+    first: usize,
+    last: usize,
+    kind: &'static str,
+    name: String,
+}
+
+fn find_items(lines: &[Line], language: Language, level: usize) -> Vec<Item> {
+    let mut items: Vec<Item> = Vec::new();
+    let mut i = 0;
+
+    while i < lines.len() {
+        let line = &lines[i];
+        let at_level = match language {
+            Language::Python => !line.text.trim().is_empty() && indent_of(line.text) == level,
+            _ => line.depth_before == level as i32,
+        };
+
+        let header = if at_level {
+            parse_header(language, line.text)
+        } else {
+            None
+        };
+
+        let (kind, name) = match header {
+            Some(header) => header,
+            None => {
+                i += 1;
+                continue;
+            }
+        };
+
+        let last = match language {
+            Language::Python => Some(indent_item_end(lines, i, level)),
+            _ => brace_item_end(lines, i, level as i32),
+        };
+        let last = match last {
+            Some(last) => last,
+            None => {
+                i += 1;
+                continue;
+            }
+        };
+
+        // Pull doc comments, attributes and decorators in with the item
+        let floor = items.last().map(|item| item.last + 1).unwrap_or(0);
+        let mut first = i;
+        while first > floor && is_preamble(language, lines[first - 1].text) {
+            first -= 1;
+        }
+
+        items.push(Item {
+            first,
+            last,
+            kind,
+            name,
+        });
+        i = last + 1;
+    }
+
+    items
+}
+
+fn is_container(kind: &str) -> bool {
+    matches!(
+        kind,
+        "impl" | "class" | "trait" | "interface" | "module" | "struct"
+    )
+}
+
+/// Indentation of a Python class body, or the brace depth of any other
+/// language's item body
+fn body_level(lines: &[Line], item: &Item, language: Language, level: usize) -> usize {
+    match language {
+        Language::Python => lines[item.first..=item.last]
+            .iter()
+            .skip(1)
+            .filter(|l| !l.text.trim().is_empty())
+            .map(|l| indent_of(l.text))
+            .find(|&indent| indent > level)
+            .unwrap_or(level + 4),
+        _ => level + 1,
+    }
+}
+
+fn collect_segments(
+    lines: &[Line],
+    language: Language,
+    level: usize,
+    prefix: &str,
+    max_bytes: usize,
+    segments: &mut Vec<Segment>,
+) {
+    if lines.is_empty() {
+        return;
+    }
+
+    let mut cursor = 0;
+    for item in find_items(lines, language, level) {
+        if item.first > cursor {
+            segments.push(Segment {
+                range: lines[cursor].start..lines[item.first - 1].end,
+                symbol: None,
+            });
+        }
+
+        let name = if prefix.is_empty() {
+            item.name.clone()
+        } else {
+            format!("{}.{}", prefix, item.name)
+        };
+        let symbol = Symbol {
+            name: name.clone(),
+            kind: item.kind.to_string(),
+            language: language.name().to_string(),
+        };
+        let range = lines[item.first].start..lines[item.last].end;
+
+        // Oversized classes and impls are broken up into their members, the
+        // code between members stays attributed to the container
+        if range.len() > max_bytes && is_container(item.kind) {
+            let body = &lines[item.first..=item.last];
+            let mut members = Vec::new();
+            let inner_level = body_level(lines, &item, language, level);
+            collect_segments(body, language, inner_level, &name, max_bytes, &mut members);
+
+            for mut member in members {
+                if member.symbol.is_none() {
+                    member.symbol = Some(symbol.clone());
+                }
+                segments.push(member);
+            }
+        } else {
+            segments.push(Segment {
+                range,
+                symbol: Some(symbol),
+            });
+        }
+
+        cursor = item.last + 1;
+    }
+
+    if cursor < lines.len() {
+        segments.push(Segment {
+            range: lines[cursor].start..lines[lines.len() - 1].end,
+            symbol: None,
+        });
+    }
+}
+
+/// Splits source code into top-level items (functions, classes, impl
+/// blocks...) plus the code between them, in file order. Items bigger than
+/// `max_bytes` that contain other items are split into their members.
+pub fn code_segments(text: &str, language: Language, max_bytes: usize) -> Vec<Segment> {
+    let lines = split_lines(text, &(0..text.len()), language);
+    let mut segments = Vec::new();
+    collect_segments(&lines, language, 0, "", max_bytes, &mut segments);
+    segments
+}
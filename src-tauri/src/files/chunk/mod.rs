@@ -0,0 +1,311 @@
+use serde::Serialize;
+use std::ops::Range;
+
+use crate::files::extract::{Extraction, Section};
+use crate::util::ids::chunk_id;
+
+pub mod code;
+
+pub use code::{Language, Symbol};
+
+#[derive(Serialize, Debug, Clone)]
+pub struct ChunkOptions {
+    /// Upper bound on a chunk's length in bytes
+    pub max_bytes: usize,
+    /// How much trailing text of a chunk is repeated at the start of the next
+    pub overlap_bytes: usize,
+    /// Prefer to cut at blank lines instead of mid-paragraph
+    pub paragraph_boundaries: bool,
+    /// Start a new chunk at Markdown style headings once the current one is
+    /// at least a quarter full
+    pub heading_boundaries: bool,
+    /// Split source files along functions, classes and other top-level items
+    pub code_aware: bool,
+}
+
+impl Default for ChunkOptions {
+    fn default() -> Self {
+        ChunkOptions {
+            max_bytes: 1500,
+            overlap_bytes: 200,
+            paragraph_boundaries: true,
+            heading_boundaries: true,
+            code_aware: true,
+        }
+    }
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct Chunk {
+    pub parent_id: String,
+    pub index: usize,
+    pub text: String,
+    pub start_byte: usize,
+    pub end_byte: usize,
+    /// 1-based, inclusive
+    pub start_line: usize,
+    pub end_line: usize,
+    pub section: Option<Section>,
+    /// The function, class... the chunk belongs to, for code files
+    pub symbol: Option<Symbol>,
+}
+
+impl Chunk {
+    pub fn id(&self) -> String {
+        chunk_id(&self.parent_id, self.index)
+    }
+}
+
+/// Byte offsets of the start of every line, for offset -> line lookups
+pub struct LineIndex {
+    starts: Vec<usize>,
+}
+
+impl LineIndex {
+    pub fn new(text: &str) -> LineIndex {
+        let mut starts = vec![0];
+        starts.extend(text.match_indices('\n').map(|(i, _)| i + 1));
+        LineIndex { starts }
+    }
+
+    /// 1-based line containing `offset`
+    pub fn line_of(&self, offset: usize) -> usize {
+        self.starts.partition_point(|&start| start <= offset)
+    }
+}
+
+fn floor_char_boundary(text: &str, mut index: usize) -> usize {
+    index = index.min(text.len());
+    while !text.is_char_boundary(index) {
+        index -= 1;
+    }
+    index
+}
+
+fn is_heading(line: &str) -> bool {
+    let trimmed = line.trim_start();
+    trimmed.starts_with('#') && trimmed.trim_start_matches('#').starts_with(' ')
+}
+
+/// Splits `range` of `text` into pieces no longer than `max`, cutting after
+/// the last newline or whitespace inside the limit when there is one
+fn split_oversized(text: &str, range: Range<usize>, max: usize, units: &mut Vec<Range<usize>>) {
+    let mut start = range.start;
+
+    while range.end - start > max {
+        let limit = floor_char_boundary(text, start + max);
+        let window = &text[start..limit];
+
+        let cut = window
+            .rfind('\n')
+            .or_else(|| window.rfind(char::is_whitespace))
+            .map(|i| start + i + 1)
+            .filter(|&cut| cut > start)
+            .unwrap_or(limit.max(start + 1));
+        let cut = floor_char_boundary(text, cut).max(start + 1);
+
+        units.push(start..cut);
+        start = cut;
+    }
+
+    if start < range.end {
+        units.push(start..range.end);
+    }
+}
+
+/// Breaks a region into the smallest pieces chunks are assembled from:
+/// paragraphs (or lines), each at most `max_bytes` long
+fn units_of(text: &str, region: Range<usize>, options: &ChunkOptions) -> Vec<Range<usize>> {
+    let mut units = Vec::new();
+    let mut start = region.start;
+    let mut offset = region.start;
+
+    for line in text[region.clone()].split_inclusive('\n') {
+        let end = offset + line.len();
+        let blank = line.trim().is_empty();
+
+        let boundary = if options.paragraph_boundaries {
+            blank || is_heading(line)
+        } else {
+            true
+        };
+
+        // Headings open a new unit, blank lines close the current one
+        if boundary && is_heading(line) && offset > start {
+            split_oversized(text, start..offset, options.max_bytes, &mut units);
+            start = offset;
+        }
+
+        if boundary && (blank || !options.paragraph_boundaries) {
+            split_oversized(text, start..end, options.max_bytes, &mut units);
+            start = end;
+        }
+
+        offset = end;
+    }
+
+    if start < region.end {
+        split_oversized(text, start..region.end, options.max_bytes, &mut units);
+    }
+
+    units
+}
+
+/// Packs consecutive units into windows of at most `max_bytes`, starting
+/// each window so that it repeats up to `overlap_bytes` of the previous one
+fn pack_units(text: &str, units: &[Range<usize>], options: &ChunkOptions) -> Vec<Range<usize>> {
+    let mut windows = Vec::new();
+    let mut first = 0;
+
+    while first < units.len() {
+        let start = units[first].start;
+        let mut last = first;
+
+        while last + 1 < units.len() {
+            let next = &units[last + 1];
+            if next.end - start > options.max_bytes {
+                break;
+            }
+
+            let filled = units[last].end - start;
+            if options.heading_boundaries
+                && is_heading(&text[next.clone()])
+                && filled * 4 >= options.max_bytes
+            {
+                break;
+            }
+            last += 1;
+        }
+
+        let end = units[last].end;
+        windows.push(start..end);
+
+        if last + 1 >= units.len() {
+            break;
+        }
+
+        // Step back over whole units while they still fit in the overlap
+        let mut next_first = last + 1;
+        while next_first - 1 > first && end - units[next_first - 1].start <= options.overlap_bytes {
+            next_first -= 1;
+        }
+        first = next_first;
+    }
+
+    windows
+}
+
+fn push_chunk(
+    chunks: &mut Vec<Chunk>,
+    parent_id: &str,
+    text: &str,
+    lines: &LineIndex,
+    window: Range<usize>,
+    section: Option<&Section>,
+    symbol: Option<&Symbol>,
+) {
+    let chunk_text = &text[window.clone()];
+    if chunk_text.trim().is_empty() {
+        return;
+    }
+
+    chunks.push(Chunk {
+        parent_id: parent_id.to_string(),
+        index: chunks.len(),
+        text: chunk_text.to_string(),
+        start_byte: window.start,
+        end_byte: window.end,
+        start_line: lines.line_of(window.start),
+        end_line: lines.line_of(window.end.saturating_sub(1).max(window.start)),
+        section: section.cloned(),
+        symbol: symbol.cloned(),
+    });
+}
+
+/// One chunk per item where it fits, items that don't are windowed like
+/// prose but every piece keeps the item's symbol
+fn chunk_code(
+    parent_id: &str,
+    text: &str,
+    language: Language,
+    options: &ChunkOptions,
+) -> Vec<Chunk> {
+    let lines = LineIndex::new(text);
+    // `#` starts comments and preprocessor lines in code, not headings
+    let options = ChunkOptions {
+        heading_boundaries: false,
+        ..options.clone()
+    };
+
+    let mut chunks = Vec::new();
+    for segment in code::code_segments(text, language, options.max_bytes) {
+        let windows = if segment.range.len() <= options.max_bytes {
+            vec![segment.range.clone()]
+        } else {
+            let units = units_of(text, segment.range.clone(), &options);
+            pack_units(text, &units, &options)
+        };
+
+        for window in windows {
+            push_chunk(
+                &mut chunks,
+                parent_id,
+                text,
+                &lines,
+                window,
+                None,
+                segment.symbol.as_ref(),
+            );
+        }
+    }
+
+    chunks
+}
+
+/// Splits an extraction into overlapping chunks. Chunks never cross a
+/// section (page, slide, sheet) so each one keeps an exact locator. Source
+/// files with a recognised `language` are split along their items instead.
+pub fn chunk_extraction(
+    parent_id: &str,
+    extraction: &Extraction,
+    options: &ChunkOptions,
+) -> Vec<Chunk> {
+    let text = extraction.text.as_str();
+
+    let language = extraction
+        .metadata
+        .get("language")
+        .and_then(|name| Language::from_name(name));
+    if let (true, Some(language)) = (options.code_aware, language) {
+        return chunk_code(parent_id, text, language, options);
+    }
+
+    let lines = LineIndex::new(text);
+
+    // Regions between section starts, text before the first section has none
+    let mut regions: Vec<(Range<usize>, Option<&Section>)> = Vec::new();
+    let mut region_start = 0;
+    let mut current: Option<&Section> = None;
+    for section in &extraction.sections {
+        let offset = section.offset.min(text.len());
+        if offset > region_start {
+            regions.push((region_start..offset, current));
+        }
+        region_start = offset;
+        current = Some(section);
+    }
+    if region_start < text.len() {
+        regions.push((region_start..text.len(), current));
+    }
+
+    let mut chunks = Vec::new();
+    for (region, section) in regions {
+        let units = units_of(text, region, options);
+
+        for window in pack_units(text, &units, options) {
+            push_chunk(&mut chunks, parent_id, text, &lines, window, section, None);
+        }
+    }
+
+    chunks
+}
@@ -0,0 +1,79 @@
+use std::error::Error;
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+
+use super::{DocumentKind, Extraction, Extractor};
+
+// Enough of the file to find the dimensions in any sane PNG or JPEG header
+const HEADER_BYTES: u64 = 64 * 1024;
+
+fn png_dimensions(bytes: &[u8]) -> Option<(u32, u32)> {
+    if bytes.len() < 24 || &bytes[..8] != b"\x89PNG\r\n\x1a\n" || &bytes[12..16] != b"IHDR" {
+        return None;
+    }
+
+    let width = u32::from_be_bytes(bytes[16..20].try_into().ok()?);
+    let height = u32::from_be_bytes(bytes[20..24].try_into().ok()?);
+    Some((width, height))
+}
+
+fn jpeg_dimensions(bytes: &[u8]) -> Option<(u32, u32)> {
+    if bytes.len() < 4 || bytes[0] != 0xFF || bytes[1] != 0xD8 {
+        return None;
+    }
+
+    // Walk the marker segments until a start-of-frame, which holds the size
+    let mut i = 2;
+    while i + 9 < bytes.len() {
+        if bytes[i] != 0xFF {
+            return None;
+        }
+
+        let marker = bytes[i + 1];
+        let length = u16::from_be_bytes([bytes[i + 2], bytes[i + 3]]) as usize;
+        let is_frame = (0xC0..=0xCF).contains(&marker) && !matches!(marker, 0xC4 | 0xC8 | 0xCC);
+
+        if is_frame {
+            let height = u16::from_be_bytes([bytes[i + 5], bytes[i + 6]]) as u32;
+            let width = u16::from_be_bytes([bytes[i + 7], bytes[i + 8]]) as u32;
+            return Some((width, height));
+        }
+
+        i += 2 + length;
+    }
+
+    None
+}
+
+/// Images are embedded from their pixels by the store, so extraction only
+/// records what can be read cheaply from the file header.
+pub struct ImageExtractor;
+
+impl Extractor for ImageExtractor {
+    fn extensions(&self) -> &[&'static str] {
+        &["png", "jpg", "jpeg"]
+    }
+
+    fn extract(&self, path: &Path) -> Result<Extraction, Box<dyn Error>> {
+        let mut header = Vec::new();
+        File::open(path)?
+            .take(HEADER_BYTES)
+            .read_to_end(&mut header)?;
+
+        let mut extraction = Extraction::text(String::new());
+        extraction.kind = DocumentKind::Image;
+
+        if let Some((width, height)) = png_dimensions(&header).or_else(|| jpeg_dimensions(&header))
+        {
+            extraction
+                .metadata
+                .insert("width".to_string(), width.to_string());
+            extraction
+                .metadata
+                .insert("height".to_string(), height.to_string());
+        }
+
+        Ok(extraction)
+    }
+}
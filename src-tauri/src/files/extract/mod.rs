@@ -0,0 +1,143 @@
+use serde::Serialize;
+use std::collections::HashMap;
+use std::error::Error;
+use std::path::Path;
+
+pub mod image;
+pub mod office;
+pub mod pdf;
+pub mod text;
+
+pub use self::image::ImageExtractor;
+pub use office::{DocxExtractor, OdfExtractor, PptxExtractor, XlsxExtractor};
+pub use pdf::PdfExtractor;
+pub use text::PlainTextExtractor;
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum DocumentKind {
+    Text,
+    Pdf,
+    Office,
+    Image,
+}
+
+/// A structural unit of the extracted text (a PDF page, a slide, a sheet...)
+/// starting at byte `offset` of `Extraction::text` and running until the
+/// next section.
+#[derive(Serialize, Debug, Clone)]
+pub struct Section {
+    pub kind: String,
+    pub label: String,
+    pub title: Option<String>,
+    pub offset: usize,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct Extraction {
+    pub kind: DocumentKind,
+    pub text: String,
+    pub sections: Vec<Section>,
+    pub metadata: HashMap<String, String>,
+}
+
+impl Extraction {
+    pub fn text(text: String) -> Extraction {
+        Extraction {
+            kind: DocumentKind::Text,
+            text,
+            sections: Vec::new(),
+            metadata: HashMap::new(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.kind != DocumentKind::Image && self.text.trim().is_empty()
+    }
+}
+
+pub trait Extractor: Send + Sync {
+    /// MIME types handled by this extractor, `type/*` matches a whole family
+    fn mime_types(&self) -> &[&'static str] {
+        &[]
+    }
+
+    /// Extensions handled regardless of what `mime_guess` thinks of them
+    fn extensions(&self) -> &[&'static str] {
+        &[]
+    }
+
+    fn extract(&self, path: &Path) -> Result<Extraction, Box<dyn Error>>;
+}
+
+fn mime_matches(pattern: &str, mime: &str) -> bool {
+    match pattern.strip_suffix("/*") {
+        Some(family) => mime.split('/').next() == Some(family),
+        None => pattern == mime,
+    }
+}
+
+/// Picks an extractor for a file by extension first, then by guessed MIME
+/// type. Extractors registered later take precedence over earlier ones so
+/// built-in handlers can be overridden. Files nobody claims fall back to the
+/// plain text extractor, which rejects anything that isn't UTF-8.
+pub struct Extractors {
+    extractors: Vec<Box<dyn Extractor>>,
+    fallback: PlainTextExtractor,
+}
+
+impl Default for Extractors {
+    fn default() -> Self {
+        let mut registry = Extractors {
+            extractors: Vec::new(),
+            fallback: PlainTextExtractor,
+        };
+
+        registry.register(Box::new(PlainTextExtractor));
+        registry.register(Box::new(PdfExtractor));
+        registry.register(Box::new(ImageExtractor));
+        registry.register(Box::new(DocxExtractor));
+        registry.register(Box::new(PptxExtractor));
+        registry.register(Box::new(XlsxExtractor));
+        registry.register(Box::new(OdfExtractor));
+        registry
+    }
+}
+
+impl Extractors {
+    pub fn register(&mut self, extractor: Box<dyn Extractor>) {
+        self.extractors.push(extractor);
+    }
+
+    pub fn for_path(&self, path: &Path) -> &dyn Extractor {
+        let extension = path
+            .extension()
+            .map(|e| e.to_string_lossy().to_lowercase())
+            .unwrap_or_default();
+
+        if let Some(extractor) = self
+            .extractors
+            .iter()
+            .rev()
+            .find(|e| e.extensions().contains(&extension.as_str()))
+        {
+            return extractor.as_ref();
+        }
+
+        for mime in mime_guess::from_path(path).iter() {
+            if let Some(extractor) = self.extractors.iter().rev().find(|e| {
+                e.mime_types()
+                    .iter()
+                    .any(|pattern| mime_matches(pattern, mime.essence_str()))
+            }) {
+                return extractor.as_ref();
+            }
+        }
+
+        &self.fallback
+    }
+
+    pub fn extract(&self, path: &Path) -> Result<Extraction, Box<dyn Error>> {
+        self.for_path(path).extract(path)
+    }
+}
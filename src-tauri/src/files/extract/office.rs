@@ -0,0 +1,583 @@
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::Reader;
+use std::collections::HashMap;
+use std::error::Error;
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+use zip::ZipArchive;
+
+use super::{DocumentKind, Extraction, Extractor, Section};
+
+type Archive = ZipArchive<File>;
+
+fn open_archive(path: &Path) -> Result<Archive, Box<dyn Error>> {
+    Ok(ZipArchive::new(File::open(path)?)?)
+}
+
+fn read_entry(archive: &mut Archive, name: &str) -> Option<String> {
+    let mut entry = archive.by_name(name).ok()?;
+    let mut xml = String::new();
+    entry.read_to_string(&mut xml).ok()?;
+    Some(xml)
+}
+
+fn xml_reader(xml: &str) -> Reader<&[u8]> {
+    let mut reader = Reader::from_str(xml);
+    // Self-closing tags (`<w:tab/>`, `<text:s/>`) then arrive as a start/end pair
+    reader.expand_empty_elements(true);
+    reader
+}
+
+fn attr(e: &BytesStart, key: &[u8]) -> Option<String> {
+    let attribute = e.try_get_attribute(key).ok()??;
+    attribute.unescape_value().ok().map(|v| v.to_string())
+}
+
+fn office_extraction() -> Extraction {
+    let mut extraction = Extraction::text(String::new());
+    extraction.kind = DocumentKind::Office;
+    extraction
+}
+
+fn begin_section(extraction: &mut Extraction, kind: &str, label: String, title: Option<String>) {
+    extraction.sections.push(Section {
+        kind: kind.to_string(),
+        label,
+        title,
+        offset: extraction.text.len(),
+    });
+}
+
+fn end_line(text: &mut String) {
+    if !text.is_empty() && !text.ends_with('\n') {
+        text.push('\n');
+    }
+}
+
+/// Pulls title, author and modification date out of a Dublin Core style
+/// properties part (`docProps/core.xml` or ODF's `meta.xml`)
+fn read_properties(xml: &str, extraction: &mut Extraction) -> Result<(), Box<dyn Error>> {
+    let mut reader = xml_reader(xml);
+    let mut current: Option<&'static str> = None;
+
+    loop {
+        match reader.read_event()? {
+            Event::Start(e) => {
+                current = match e.name().as_ref() {
+                    b"dc:title" => Some("title"),
+                    b"dc:creator" | b"meta:initial-creator" => Some("author"),
+                    b"dcterms:modified" | b"dc:date" => Some("modified"),
+                    _ => None,
+                };
+            }
+            Event::Text(t) => {
+                if let Some(key) = current {
+                    let value = t.unescape()?.trim().to_string();
+                    if !value.is_empty() && !extraction.metadata.contains_key(key) {
+                        extraction.metadata.insert(key.to_string(), value);
+                    }
+                }
+            }
+            Event::End(_) => current = None,
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+
+    Ok(())
+}
+
+/// Maps relationship ids to the part they point at, resolved against `base`
+fn read_relationships(xml: &str, base: &str) -> Result<HashMap<String, String>, Box<dyn Error>> {
+    let mut reader = xml_reader(xml);
+    let mut relationships = HashMap::new();
+
+    loop {
+        match reader.read_event()? {
+            Event::Start(e) if e.local_name().as_ref() == b"Relationship" => {
+                if let (Some(id), Some(target)) = (attr(&e, b"Id"), attr(&e, b"Target")) {
+                    relationships.insert(id, resolve_part(base, &target));
+                }
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+
+    Ok(relationships)
+}
+
+fn resolve_part(base: &str, target: &str) -> String {
+    if let Some(absolute) = target.strip_prefix('/') {
+        return absolute.to_string();
+    }
+
+    let mut parts: Vec<&str> = base.split('/').filter(|p| !p.is_empty()).collect();
+    for segment in target.split('/') {
+        match segment {
+            ".." => {
+                parts.pop();
+            }
+            "." | "" => {}
+            _ => parts.push(segment),
+        }
+    }
+    parts.join("/")
+}
+
+/// Numbered parts like `ppt/slides/slide12.xml`, in numeric order
+fn numbered_parts(archive: &Archive, prefix: &str) -> Vec<(usize, String)> {
+    let mut parts: Vec<(usize, String)> = archive
+        .file_names()
+        .filter_map(|name| {
+            let number = name.strip_prefix(prefix)?.strip_suffix(".xml")?;
+            Some((number.parse().ok()?, name.to_string()))
+        })
+        .collect();
+    parts.sort();
+    parts
+}
+
+pub struct DocxExtractor;
+
+impl DocxExtractor {
+    fn read_body(xml: &str, extraction: &mut Extraction) -> Result<(), Box<dyn Error>> {
+        let mut reader = xml_reader(xml);
+        let mut in_text = false;
+        let mut page = 1;
+
+        begin_section(extraction, "page", page.to_string(), None);
+
+        loop {
+            match reader.read_event()? {
+                Event::Start(e) => match e.name().as_ref() {
+                    b"w:t" => in_text = true,
+                    b"w:tab" => extraction.text.push('\t'),
+                    b"w:br" if attr(&e, b"w:type").as_deref() != Some("page") => {
+                        extraction.text.push('\n')
+                    }
+                    b"w:br" | b"w:lastRenderedPageBreak" => {
+                        // An explicit break is usually followed by a rendered one, count it once
+                        let at_section_start = extraction
+                            .sections
+                            .last()
+                            .map(|s| s.offset == extraction.text.len())
+                            .unwrap_or(false);
+                        if !at_section_start {
+                            page += 1;
+                            begin_section(extraction, "page", page.to_string(), None);
+                        }
+                    }
+                    _ => {}
+                },
+                Event::End(e) => match e.name().as_ref() {
+                    b"w:t" => in_text = false,
+                    b"w:p" => end_line(&mut extraction.text),
+                    _ => {}
+                },
+                Event::Text(t) if in_text => extraction.text.push_str(&t.unescape()?),
+                Event::Eof => break,
+                _ => {}
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl Extractor for DocxExtractor {
+    fn extensions(&self) -> &[&'static str] {
+        &["docx", "docm"]
+    }
+
+    fn extract(&self, path: &Path) -> Result<Extraction, Box<dyn Error>> {
+        let mut archive = open_archive(path)?;
+        let mut extraction = office_extraction();
+
+        let body =
+            read_entry(&mut archive, "word/document.xml").ok_or("Missing word/document.xml")?;
+        Self::read_body(&body, &mut extraction)?;
+
+        if let Some(core) = read_entry(&mut archive, "docProps/core.xml") {
+            read_properties(&core, &mut extraction)?;
+        }
+
+        Ok(extraction)
+    }
+}
+
+struct Shape {
+    placeholder: Option<String>,
+    text: String,
+}
+
+pub struct PptxExtractor;
+
+impl PptxExtractor {
+    fn read_shapes(xml: &str) -> Result<Vec<Shape>, Box<dyn Error>> {
+        let mut reader = xml_reader(xml);
+        let mut shapes = Vec::new();
+        let mut current: Option<Shape> = None;
+        let mut in_text = false;
+
+        loop {
+            match reader.read_event()? {
+                Event::Start(e) => match e.name().as_ref() {
+                    b"p:sp" => {
+                        current = Some(Shape {
+                            placeholder: None,
+                            text: String::new(),
+                        })
+                    }
+                    b"p:ph" => {
+                        if let Some(shape) = current.as_mut() {
+                            // Placeholders without a type are generic content boxes
+                            shape.placeholder =
+                                Some(attr(&e, b"type").unwrap_or_else(|| "obj".to_string()));
+                        }
+                    }
+                    b"a:t" => in_text = true,
+                    _ => {}
+                },
+                Event::End(e) => match e.name().as_ref() {
+                    b"p:sp" => shapes.extend(current.take()),
+                    b"a:t" => in_text = false,
+                    b"a:p" => {
+                        if let Some(shape) = current.as_mut() {
+                            end_line(&mut shape.text);
+                        }
+                    }
+                    _ => {}
+                },
+                Event::Text(t) if in_text => {
+                    if let Some(shape) = current.as_mut() {
+                        shape.text.push_str(&t.unescape()?);
+                    }
+                }
+                Event::Eof => break,
+                _ => {}
+            }
+        }
+
+        Ok(shapes)
+    }
+
+    fn read_notes(
+        archive: &mut Archive,
+        slide_part: &str,
+    ) -> Result<Option<String>, Box<dyn Error>> {
+        let (dir, file) = slide_part.rsplit_once('/').unwrap_or(("", slide_part));
+        let rels = match read_entry(archive, &format!("{}/_rels/{}.rels", dir, file)) {
+            Some(rels) => rels,
+            None => return Ok(None),
+        };
+
+        let notes_part = read_relationships(&rels, dir)?
+            .into_values()
+            .find(|target| target.contains("notesSlides/"));
+        let notes = match notes_part.and_then(|part| read_entry(archive, &part)) {
+            Some(notes) => notes,
+            None => return Ok(None),
+        };
+
+        // The notes page also carries the slide thumbnail and number, only the body is notes
+        let text: String = Self::read_shapes(&notes)?
+            .into_iter()
+            .filter(|s| s.placeholder.as_deref() == Some("body"))
+            .map(|s| s.text)
+            .collect();
+
+        Ok(Some(text).filter(|t| !t.trim().is_empty()))
+    }
+}
+
+impl Extractor for PptxExtractor {
+    fn extensions(&self) -> &[&'static str] {
+        &["pptx", "pptm"]
+    }
+
+    fn extract(&self, path: &Path) -> Result<Extraction, Box<dyn Error>> {
+        let mut archive = open_archive(path)?;
+        let mut extraction = office_extraction();
+
+        for (number, part) in numbered_parts(&archive, "ppt/slides/slide") {
+            let xml = match read_entry(&mut archive, &part) {
+                Some(xml) => xml,
+                None => continue,
+            };
+            let shapes = Self::read_shapes(&xml)?;
+
+            let title = shapes
+                .iter()
+                .find(|s| matches!(s.placeholder.as_deref(), Some("title") | Some("ctrTitle")))
+                .map(|s| s.text.trim().replace('\n', " "))
+                .filter(|t| !t.is_empty());
+
+            begin_section(&mut extraction, "slide", number.to_string(), title);
+            for shape in shapes {
+                extraction.text.push_str(&shape.text);
+                end_line(&mut extraction.text);
+            }
+
+            if let Some(notes) = Self::read_notes(&mut archive, &part)? {
+                extraction.text.push_str("Notes:\n");
+                extraction.text.push_str(&notes);
+                end_line(&mut extraction.text);
+            }
+        }
+
+        if let Some(core) = read_entry(&mut archive, "docProps/core.xml") {
+            read_properties(&core, &mut extraction)?;
+        }
+
+        extraction
+            .metadata
+            .insert("slides".to_string(), extraction.sections.len().to_string());
+        Ok(extraction)
+    }
+}
+
+pub struct XlsxExtractor;
+
+impl XlsxExtractor {
+    fn read_shared_strings(xml: &str) -> Result<Vec<String>, Box<dyn Error>> {
+        let mut reader = xml_reader(xml);
+        let mut strings = Vec::new();
+        let mut current = String::new();
+        let mut in_text = false;
+        let mut in_phonetic = false;
+
+        loop {
+            match reader.read_event()? {
+                Event::Start(e) => match e.local_name().as_ref() {
+                    b"t" => in_text = true,
+                    b"rPh" => in_phonetic = true,
+                    _ => {}
+                },
+                Event::End(e) => match e.local_name().as_ref() {
+                    b"t" => in_text = false,
+                    b"rPh" => in_phonetic = false,
+                    b"si" => strings.push(std::mem::take(&mut current)),
+                    _ => {}
+                },
+                Event::Text(t) if in_text && !in_phonetic => current.push_str(&t.unescape()?),
+                Event::Eof => break,
+                _ => {}
+            }
+        }
+
+        Ok(strings)
+    }
+
+    fn read_sheets(xml: &str) -> Result<Vec<(String, String)>, Box<dyn Error>> {
+        let mut reader = xml_reader(xml);
+        let mut sheets = Vec::new();
+
+        loop {
+            match reader.read_event()? {
+                Event::Start(e) if e.local_name().as_ref() == b"sheet" => {
+                    if let (Some(name), Some(id)) = (attr(&e, b"name"), attr(&e, b"r:id")) {
+                        sheets.push((name, id));
+                    }
+                }
+                Event::Eof => break,
+                _ => {}
+            }
+        }
+
+        Ok(sheets)
+    }
+
+    fn read_cells(xml: &str, shared: &[String], text: &mut String) -> Result<(), Box<dyn Error>> {
+        let mut reader = xml_reader(xml);
+        let mut row: Vec<String> = Vec::new();
+        let mut cell_type = String::new();
+        let mut value = String::new();
+        let mut in_value = false;
+
+        loop {
+            match reader.read_event()? {
+                Event::Start(e) => match e.local_name().as_ref() {
+                    b"c" => {
+                        cell_type = attr(&e, b"t").unwrap_or_default();
+                        value.clear();
+                    }
+                    b"v" | b"t" => in_value = true,
+                    _ => {}
+                },
+                Event::End(e) => match e.local_name().as_ref() {
+                    b"v" | b"t" => in_value = false,
+                    b"c" => {
+                        let cell = match cell_type.as_str() {
+                            "s" => value
+                                .trim()
+                                .parse::<usize>()
+                                .ok()
+                                .and_then(|i| shared.get(i).cloned())
+                                .unwrap_or_default(),
+                            "b" => if value == "1" { "TRUE" } else { "FALSE" }.to_string(),
+                            _ => std::mem::take(&mut value),
+                        };
+                        row.push(cell);
+                    }
+                    b"row" => {
+                        let line = row.join("\t");
+                        if !line.trim().is_empty() {
+                            text.push_str(line.trim_end());
+                            text.push('\n');
+                        }
+                        row.clear();
+                    }
+                    _ => {}
+                },
+                Event::Text(t) if in_value => value.push_str(&t.unescape()?),
+                Event::Eof => break,
+                _ => {}
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl Extractor for XlsxExtractor {
+    fn extensions(&self) -> &[&'static str] {
+        &["xlsx", "xlsm"]
+    }
+
+    fn extract(&self, path: &Path) -> Result<Extraction, Box<dyn Error>> {
+        let mut archive = open_archive(path)?;
+        let mut extraction = office_extraction();
+
+        let shared = match read_entry(&mut archive, "xl/sharedStrings.xml") {
+            Some(xml) => Self::read_shared_strings(&xml)?,
+            None => Vec::new(),
+        };
+        let workbook =
+            read_entry(&mut archive, "xl/workbook.xml").ok_or("Missing xl/workbook.xml")?;
+        let relationships = match read_entry(&mut archive, "xl/_rels/workbook.xml.rels") {
+            Some(xml) => read_relationships(&xml, "xl")?,
+            None => HashMap::new(),
+        };
+
+        for (name, id) in Self::read_sheets(&workbook)? {
+            let xml = match relationships
+                .get(&id)
+                .and_then(|part| read_entry(&mut archive, part))
+            {
+                Some(xml) => xml,
+                None => continue,
+            };
+
+            begin_section(&mut extraction, "sheet", name.clone(), Some(name.clone()));
+            extraction.text.push_str(&name);
+            extraction.text.push('\n');
+            Self::read_cells(&xml, &shared, &mut extraction.text)?;
+        }
+
+        if let Some(core) = read_entry(&mut archive, "docProps/core.xml") {
+            read_properties(&core, &mut extraction)?;
+        }
+
+        extraction
+            .metadata
+            .insert("sheets".to_string(), extraction.sections.len().to_string());
+        Ok(extraction)
+    }
+}
+
+/// OpenDocument text, presentations and spreadsheets all keep their content
+/// in `content.xml`, so one walker handles the three of them.
+pub struct OdfExtractor;
+
+impl OdfExtractor {
+    fn read_content(
+        xml: &str,
+        mimetype: &str,
+        extraction: &mut Extraction,
+    ) -> Result<(), Box<dyn Error>> {
+        let mut reader = xml_reader(xml);
+        let is_presentation = mimetype.ends_with("presentation");
+        let is_spreadsheet = mimetype.ends_with("spreadsheet");
+        let mut in_body = false;
+        let mut cell_depth = 0;
+        let mut slide = 0;
+
+        loop {
+            match reader.read_event()? {
+                Event::Start(e) => match e.name().as_ref() {
+                    b"office:body" => in_body = true,
+                    b"draw:page" if is_presentation => {
+                        slide += 1;
+                        begin_section(
+                            extraction,
+                            "slide",
+                            slide.to_string(),
+                            attr(&e, b"draw:name"),
+                        );
+                    }
+                    b"table:table" if is_spreadsheet => {
+                        let name = attr(&e, b"table:name").unwrap_or_default();
+                        begin_section(extraction, "sheet", name.clone(), Some(name.clone()));
+                        extraction.text.push_str(&name);
+                        extraction.text.push('\n');
+                    }
+                    b"presentation:notes" => {
+                        end_line(&mut extraction.text);
+                        extraction.text.push_str("Notes:\n");
+                    }
+                    b"table:table-cell" => cell_depth += 1,
+                    b"text:s" => extraction.text.push(' '),
+                    b"text:tab" => extraction.text.push('\t'),
+                    b"text:line-break" => extraction.text.push('\n'),
+                    _ => {}
+                },
+                Event::End(e) => match e.name().as_ref() {
+                    b"office:body" => in_body = false,
+                    b"text:p" | b"text:h" if cell_depth > 0 => extraction.text.push(' '),
+                    b"text:p" | b"text:h" => end_line(&mut extraction.text),
+                    b"table:table-cell" => {
+                        cell_depth -= 1;
+                        let trimmed = extraction.text.trim_end_matches(' ').len();
+                        extraction.text.truncate(trimmed);
+                        extraction.text.push('\t');
+                    }
+                    b"table:table-row" => {
+                        // Sheets pad rows with long runs of empty cells
+                        let trimmed = extraction.text.trim_end_matches([' ', '\t']).len();
+                        extraction.text.truncate(trimmed);
+                        end_line(&mut extraction.text);
+                    }
+                    _ => {}
+                },
+                Event::Text(t) if in_body => extraction.text.push_str(&t.unescape()?),
+                Event::Eof => break,
+                _ => {}
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl Extractor for OdfExtractor {
+    fn extensions(&self) -> &[&'static str] {
+        &["odt", "odp", "ods"]
+    }
+
+    fn extract(&self, path: &Path) -> Result<Extraction, Box<dyn Error>> {
+        let mut archive = open_archive(path)?;
+        let mut extraction = office_extraction();
+
+        let mimetype = read_entry(&mut archive, "mimetype").unwrap_or_default();
+        let content = read_entry(&mut archive, "content.xml").ok_or("Missing content.xml")?;
+        Self::read_content(&content, mimetype.trim(), &mut extraction)?;
+
+        if let Some(meta) = read_entry(&mut archive, "meta.xml") {
+            read_properties(&meta, &mut extraction)?;
+        }
+
+        Ok(extraction)
+    }
+}
@@ -0,0 +1,123 @@
+use pdf_extract::{ConvertToFmt, MediaBox, OutputDev, OutputError, PlainTextOutput, Transform};
+use std::cell::RefCell;
+use std::error::Error;
+use std::fmt;
+use std::panic;
+use std::path::Path;
+use std::rc::Rc;
+
+use super::{DocumentKind, Extraction, Extractor, Section};
+
+/// `PlainTextOutput` owns its writer, so the text is shared through an
+/// `Rc` to let `PagedOutput` read the offset each page starts at.
+#[derive(Clone, Default)]
+struct SharedText(Rc<RefCell<String>>);
+
+impl fmt::Write for SharedText {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.0.borrow_mut().push_str(s);
+        Ok(())
+    }
+}
+
+impl ConvertToFmt for SharedText {
+    type Writer = SharedText;
+
+    fn convert(self) -> Self::Writer {
+        self
+    }
+}
+
+struct PagedOutput {
+    inner: PlainTextOutput<SharedText>,
+    text: SharedText,
+    pages: Vec<Section>,
+}
+
+impl OutputDev for PagedOutput {
+    fn begin_page(
+        &mut self,
+        page_num: u32,
+        media_box: &MediaBox,
+        art_box: Option<(f64, f64, f64, f64)>,
+    ) -> Result<(), OutputError> {
+        self.pages.push(Section {
+            kind: "page".to_string(),
+            label: page_num.to_string(),
+            title: None,
+            offset: self.text.0.borrow().len(),
+        });
+        self.inner.begin_page(page_num, media_box, art_box)
+    }
+
+    fn end_page(&mut self) -> Result<(), OutputError> {
+        self.inner.end_page()?;
+        // Keep pages apart even when the last line of one runs into the next
+        self.text.0.borrow_mut().push('\n');
+        Ok(())
+    }
+
+    fn output_character(
+        &mut self,
+        trm: &Transform,
+        width: f64,
+        spacing: f64,
+        font_size: f64,
+        char: &str,
+    ) -> Result<(), OutputError> {
+        self.inner
+            .output_character(trm, width, spacing, font_size, char)
+    }
+
+    fn begin_word(&mut self) -> Result<(), OutputError> {
+        self.inner.begin_word()
+    }
+
+    fn end_word(&mut self) -> Result<(), OutputError> {
+        self.inner.end_word()
+    }
+
+    fn end_line(&mut self) -> Result<(), OutputError> {
+        self.inner.end_line()
+    }
+}
+
+fn extract_pages(path: &Path) -> Result<(String, Vec<Section>), Box<dyn Error>> {
+    let doc = pdf_extract::Document::load(path)?;
+    if doc.is_encrypted() {
+        return Err(Box::from("Encrypted PDFs are not supported"));
+    }
+
+    let text = SharedText::default();
+    let mut output = PagedOutput {
+        inner: PlainTextOutput::new(text.clone()),
+        text: text.clone(),
+        pages: Vec::new(),
+    };
+    pdf_extract::output_doc(&doc, &mut output)?;
+
+    let text = text.0.borrow().clone();
+    Ok((text, output.pages))
+}
+
+pub struct PdfExtractor;
+
+impl Extractor for PdfExtractor {
+    fn mime_types(&self) -> &[&'static str] {
+        &["application/pdf"]
+    }
+
+    fn extract(&self, path: &Path) -> Result<Extraction, Box<dyn Error>> {
+        // pdf-extract panics on some malformed documents instead of erroring
+        let (text, pages) = panic::catch_unwind(|| extract_pages(path).map_err(|e| e.to_string()))
+            .map_err(|_| "PDF parser panicked")??;
+
+        let mut extraction = Extraction::text(text);
+        extraction.kind = DocumentKind::Pdf;
+        extraction
+            .metadata
+            .insert("pages".to_string(), pages.len().to_string());
+        extraction.sections = pages;
+        Ok(extraction)
+    }
+}
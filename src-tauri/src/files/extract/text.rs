@@ -0,0 +1,36 @@
+use std::error::Error;
+use std::path::Path;
+
+use super::{Extraction, Extractor};
+use crate::files::chunk::Language;
+
+pub struct PlainTextExtractor;
+
+impl Extractor for PlainTextExtractor {
+    fn mime_types(&self) -> &[&'static str] {
+        &[
+            "text/*",
+            "application/json",
+            "application/javascript",
+            "application/toml",
+        ]
+    }
+
+    fn extract(&self, path: &Path) -> Result<Extraction, Box<dyn Error>> {
+        // Anything that isn't valid UTF-8 is treated as a binary file
+        let text = std::fs::read_to_string(path)?;
+        let mut extraction = Extraction::text(text);
+
+        let extension = path
+            .extension()
+            .map(|e| e.to_string_lossy().to_lowercase())
+            .unwrap_or_default();
+        if let Some(language) = Language::from_extension(&extension) {
+            extraction
+                .metadata
+                .insert("language".to_string(), language.name().to_string());
+        }
+
+        Ok(extraction)
+    }
+}
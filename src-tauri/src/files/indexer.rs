@@ -0,0 +1,607 @@
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+use std::collections::HashSet;
+use std::error::Error;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::chroma;
+use crate::chroma::chrust::EmbeddedRecords;
+use crate::embed::{self, Embedder};
+use crate::files::chunk::Chunk;
+use crate::files::chunk::{chunk_extraction, ChunkOptions};
+use crate::files::extract::{DocumentKind, Extraction, Extractors};
+use crate::files::manifest::{file_mtime, hash_file, FileState, ManifestEntry};
+use crate::files::rules::IndexRules;
+use crate::files::{FileMetadata, Manifest};
+use crate::jobs::JobControl;
+use crate::search::{self, KeywordStore, SearchCache};
+use crate::store;
+use crate::util::ids::{document_id, file_locator};
+use crate::util::{load_config, IndexingConfig};
+
+pub const COLLECTION_NAME: &str = "siftfiles";
+
+/// Id namespace for documents that come from the local filesystem
+pub const SOURCE: &str = "local";
+
+// Keeps a single huge extraction (a dump, a giant CSV) from flooding the
+// collection with thousands of chunks
+const MAX_DOCUMENT_BYTES: usize = 2_000_000;
+
+// How many newly indexed files to process between manifest checkpoints
+const SAVE_EVERY: usize = 100;
+
+// The manifest full runs and the watcher both work on, loaded on first use.
+// Locked a file at a time so a full run doesn't hold up the watcher.
+static MANIFEST: Mutex<Option<Manifest>> = Mutex::new(None);
+
+/// Everything that decides how files are turned into store documents
+#[derive(Default)]
+pub struct Indexer {
+    pub extractors: Extractors,
+    pub chunking: ChunkOptions,
+    pub rules: IndexRules,
+}
+
+#[derive(Serialize, Debug, Clone, Default)]
+pub struct SyncSummary {
+    pub indexed: Vec<String>,
+    pub removed: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct IndexProgress {
+    pub root: String,
+    pub current: String,
+    pub files_seen: usize,
+    pub files_indexed: usize,
+    pub files_skipped: usize,
+    pub files_unchanged: usize,
+    pub files_removed: usize,
+    pub files_failed: usize,
+    /// Why the last failed file couldn't be indexed
+    pub last_failure: Option<String>,
+    pub done: bool,
+    /// Set when the run couldn't start, e.g. the embedder is unreachable
+    pub error: Option<String>,
+}
+
+/// Where a full run that was stopped picks up again. Roots are done or not,
+/// a root that was cut short is walked again from the start, which is cheap
+/// as its files indexed so far are unchanged by then.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct IndexCheckpoint {
+    pub roots_done: Vec<String>,
+    /// The counters as they were when the first root not done started
+    pub progress: IndexProgress,
+}
+
+// Runs `f` on the shared manifest. An unreadable one is replaced with an empty
+// manifest, which re-indexes everything.
+fn with_manifest<T>(f: impl FnOnce(&mut Manifest) -> T) -> T {
+    let mut manifest = MANIFEST.lock().unwrap_or_else(|e| e.into_inner());
+    let manifest = manifest.get_or_insert_with(|| {
+        Manifest::load().unwrap_or_else(|e| {
+            eprintln!(
+                "FILE:WARN: Unreadable manifest, re-indexing everything: {}",
+                e
+            );
+            Manifest::default()
+        })
+    });
+    f(manifest)
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+fn save_manifest(manifest: &Manifest) {
+    if let Err(e) = manifest.save() {
+        eprintln!("FILE:WARN: Failed to save index manifest: {}", e);
+    }
+}
+
+fn truncate_document(text: &mut String) {
+    if text.len() > MAX_DOCUMENT_BYTES {
+        let mut end = MAX_DOCUMENT_BYTES;
+        while !text.is_char_boundary(end) {
+            end -= 1;
+        }
+        text.truncate(end);
+    }
+}
+
+/// Chroma metadata values have to be scalars, so the chunk's locators are
+/// flattened next to the file's own metadata. `mtime` is in unix seconds
+/// and backs `modified:` in search queries.
+fn image_metadata(file: &FileMetadata, mtime: u64) -> Map<String, Value> {
+    let mut map = file.to_map();
+    map.insert("location".to_string(), json!("local"));
+    map.insert("mtime".to_string(), json!(mtime));
+    map
+}
+
+fn chunk_metadata(
+    file: &FileMetadata,
+    extraction: &Extraction,
+    chunk: &Chunk,
+    chunk_count: usize,
+    mtime: u64,
+) -> Map<String, Value> {
+    let mut map = file.to_map();
+    map.insert("location".to_string(), json!("local"));
+    map.insert("mtime".to_string(), json!(mtime));
+    map.insert("parent_id".to_string(), json!(chunk.parent_id));
+    map.insert("chunk_index".to_string(), json!(chunk.index));
+    map.insert("chunk_count".to_string(), json!(chunk_count));
+    map.insert("start_byte".to_string(), json!(chunk.start_byte));
+    map.insert("end_byte".to_string(), json!(chunk.end_byte));
+    map.insert("start_line".to_string(), json!(chunk.start_line));
+    map.insert("end_line".to_string(), json!(chunk.end_line));
+
+    if let Some(section) = &chunk.section {
+        map.insert("section_kind".to_string(), json!(section.kind));
+        map.insert("section_label".to_string(), json!(section.label));
+        if let Some(title) = &section.title {
+            map.insert("section_title".to_string(), json!(title));
+        }
+    }
+
+    if let Some(symbol) = &chunk.symbol {
+        map.insert("symbol".to_string(), json!(symbol.name));
+        map.insert("symbol_kind".to_string(), json!(symbol.kind));
+    }
+
+    for (key, value) in &extraction.metadata {
+        map.entry(key.clone()).or_insert_with(|| json!(value));
+    }
+
+    map
+}
+
+enum Outcome {
+    Indexed,
+    Unchanged,
+    Skipped,
+}
+
+fn delete_ids(ids: Vec<String>) -> Result<(), Box<dyn Error>> {
+    if ids.is_empty() {
+        return Ok(());
+    }
+
+    store::shared().delete(&chroma::DeleteRequest {
+        collection_name: COLLECTION_NAME.to_string(),
+        ids: Some(ids.clone()),
+        filter: chroma::Filter::default(),
+    })?;
+    KeywordStore::shared().delete(COLLECTION_NAME, &ids)?;
+    SearchCache::shared().bump_generation();
+    Ok(())
+}
+
+/// Resolves the configured embedder and makes sure the collection can take
+/// its vectors. `None` means documents are embedded by the Python SDK.
+fn prepare() -> Result<Option<Arc<dyn Embedder>>, Box<dyn Error>> {
+    let store = store::shared();
+
+    if !KeywordStore::shared().exists(COLLECTION_NAME) {
+        match search::backfill(store, COLLECTION_NAME, &chroma::Filter::default()) {
+            Ok(0) => {}
+            Ok(count) => println!(
+                "FILE:INFO: Added {} stored chunks to the keyword index",
+                count
+            ),
+            Err(e) => eprintln!("FILE:WARN: Failed to build the keyword index: {}", e),
+        }
+    }
+
+    match embed::shared()? {
+        Some(embedder) => {
+            embed::check_collection(store, COLLECTION_NAME, embedder.as_ref())?;
+            Ok(Some(embedder))
+        }
+        None if store.name() != "chroma" => Err(Box::from(format!(
+            "The {} store can't use the clip embedder, pick another embedder.provider",
+            store.name()
+        ))),
+        None => Ok(None),
+    }
+}
+
+/// Drops the store entries for `missing` paths, which no longer exist on disk
+fn remove_entries(missing: Vec<String>, manifest: &mut Manifest) -> usize {
+    let ids: Vec<String> = missing
+        .iter()
+        .filter_map(|p| manifest.get(Path::new(p)))
+        .flat_map(|e| e.store_ids())
+        .collect();
+
+    if let Err(e) = delete_ids(ids) {
+        // Keep the entries so the next run retries the delete
+        eprintln!(
+            "FILE:WARN: Failed to remove deleted files from the index: {}",
+            e
+        );
+        return 0;
+    }
+
+    for path in &missing {
+        manifest.remove(Path::new(path));
+    }
+    missing.len()
+}
+
+impl Indexer {
+    pub fn from_config(config: &IndexingConfig) -> Result<Indexer, Box<dyn Error>> {
+        Ok(Indexer {
+            rules: IndexRules::from_config(config)?,
+            ..Indexer::default()
+        })
+    }
+
+    /// Built from the `indexing` section of the saved config, the defaults
+    /// when it's missing or invalid
+    pub fn configured() -> Indexer {
+        let indexing = load_config().map(|cfg| cfg.indexing).unwrap_or_default();
+
+        Indexer::from_config(&indexing).unwrap_or_else(|e| {
+            eprintln!("Invalid indexing config, using defaults: {}", e);
+            Indexer::default()
+        })
+    }
+
+    fn index_file(
+        &self,
+        path: &Path,
+        manifest: &mut Manifest,
+        embedder: Option<&dyn Embedder>,
+    ) -> Result<Outcome, Box<dyn Error>> {
+        let metadata = FileMetadata::from_path(path)?;
+        let mtime = file_mtime(&std::fs::metadata(path)?);
+        let id = document_id(SOURCE, &file_locator(path));
+        let model = embedder.map(|e| e.model().to_string());
+
+        // Entries written before ids were content-addressed, or by another
+        // embedding model, are re-indexed even when the file hasn't changed
+        let outdated = manifest
+            .get(path)
+            .map(|entry| {
+                entry.id.as_ref().map(|old| *old != id).unwrap_or(false) || entry.model != model
+            })
+            .unwrap_or(false);
+
+        let hash = match manifest.check(path, metadata.size, mtime)? {
+            FileState::Unchanged | FileState::Touched if outdated => hash_file(path)?,
+            FileState::Unchanged => return Ok(Outcome::Unchanged),
+            FileState::Touched => {
+                if let Some(entry) = manifest.entries.get_mut(&metadata.filepath) {
+                    entry.size = metadata.size;
+                    entry.mtime = mtime;
+                    entry.indexed_at = unix_now();
+                }
+                return Ok(Outcome::Unchanged);
+            }
+            FileState::Changed { hash } => hash,
+            FileState::New => hash_file(path)?,
+        };
+
+        // Drop whatever was stored for the previous version before re-adding
+        if let Some(entry) = manifest.get(path) {
+            delete_ids(entry.store_ids())?;
+        }
+
+        let mut extraction = match self.extractors.extract(path) {
+            // Images need OpenCLIP, text embedders can't place them
+            Ok(extraction) if extraction.kind == DocumentKind::Image && embedder.is_some() => {
+                manifest.insert(
+                    path,
+                    ManifestEntry {
+                        id: None,
+                        chunks: 0,
+                        size: metadata.size,
+                        mtime,
+                        hash,
+                        model,
+                        indexed_at: unix_now(),
+                    },
+                );
+                return Ok(Outcome::Skipped);
+            }
+            Ok(extraction) if !extraction.is_empty() => extraction,
+            _ => {
+                // Remember the file so it isn't re-read on every run until it changes
+                manifest.insert(
+                    path,
+                    ManifestEntry {
+                        id: None,
+                        chunks: 0,
+                        size: metadata.size,
+                        mtime,
+                        hash,
+                        model,
+                        indexed_at: unix_now(),
+                    },
+                );
+                return Ok(Outcome::Skipped);
+            }
+        };
+
+        let chunks = match extraction.kind {
+            DocumentKind::Image => {
+                let action = chroma::Action::AddImage {
+                    collection_name: COLLECTION_NAME.to_string(),
+                    image_path: metadata.filepath.clone(),
+                    id: id.clone(),
+                    metadata: image_metadata(&metadata, mtime),
+                };
+                chroma::run_python_sdk(&action, true)?;
+                0
+            }
+            _ => {
+                truncate_document(&mut extraction.text);
+                let chunks = chunk_extraction(&id, &extraction, &self.chunking);
+                let documents: Vec<String> = chunks.iter().map(|c| c.text.clone()).collect();
+                let ids: Vec<String> = chunks.iter().map(|c| c.id()).collect();
+                let metadatas: Vec<_> = chunks
+                    .iter()
+                    .map(|c| chunk_metadata(&metadata, &extraction, c, chunks.len(), mtime))
+                    .collect();
+
+                match embedder {
+                    Some(embedder) => {
+                        let records = EmbeddedRecords {
+                            ids: ids.clone(),
+                            embeddings: embedder.embed(&documents)?,
+                            documents: Some(documents.clone()),
+                            metadatas: Some(metadatas),
+                        };
+                        store::shared().upsert(COLLECTION_NAME, &records)?;
+                    }
+                    None => {
+                        let action = chroma::Action::Add {
+                            collection_name: COLLECTION_NAME.to_string(),
+                            documents: documents.clone(),
+                            ids: ids.clone(),
+                            metadatas,
+                        };
+                        chroma::run_python_sdk(&action, true)?;
+                    }
+                }
+                KeywordStore::shared().upsert(COLLECTION_NAME, &ids, &documents)?;
+                chunks.len()
+            }
+        };
+        SearchCache::shared().bump_generation();
+
+        manifest.insert(
+            path,
+            ManifestEntry {
+                id: Some(id),
+                chunks,
+                size: metadata.size,
+                mtime,
+                hash,
+                model,
+                indexed_at: unix_now(),
+            },
+        );
+        Ok(Outcome::Indexed)
+    }
+
+    /// Walks every configured root (honouring the include/exclude rules and
+    /// ignore files) and brings the `siftfiles` collection in line with it.
+    /// Only new or changed files are embedded, and files deleted since the
+    /// last run are removed. `on_progress` is called after every file and
+    /// once more when the walk is complete, with the checkpoint to resume
+    /// from; returning false stops the run early, leaving `done` unset.
+    pub fn index_local_files<F>(
+        &self,
+        checkpoint: IndexCheckpoint,
+        mut on_progress: F,
+    ) -> IndexProgress
+    where
+        F: FnMut(&IndexProgress, &IndexCheckpoint) -> bool,
+    {
+        let mut checkpoint = checkpoint;
+        let mut progress = checkpoint.progress.clone();
+        let mut last_saved = progress.files_indexed;
+
+        let embedder = match prepare() {
+            Ok(embedder) => embedder,
+            Err(e) => {
+                eprintln!("FILE:WARN: Not indexing: {}", e);
+                progress.error = Some(e.to_string());
+                progress.done = true;
+                on_progress(&progress, &checkpoint);
+                return progress;
+            }
+        };
+
+        for root in &self.rules.roots() {
+            let root_name = root.display().to_string();
+            if checkpoint.roots_done.contains(&root_name) {
+                continue;
+            }
+            progress.root = root_name;
+            println!("FILE:INFO: Indexing {}", progress.root);
+
+            let mut seen = HashSet::new();
+            let walk_started = unix_now();
+            let walker = self.rules.walker(root).build();
+
+            for entry in walker {
+                let entry = match entry {
+                    Ok(entry) => entry,
+                    Err(e) => {
+                        eprintln!("FILE:WARN: {}", e);
+                        continue;
+                    }
+                };
+
+                if !entry.file_type().map(|t| t.is_file()).unwrap_or(false) {
+                    continue;
+                }
+
+                progress.files_seen += 1;
+                progress.current = entry.path().display().to_string();
+                seen.insert(progress.current.clone());
+
+                let outcome = with_manifest(|manifest| {
+                    self.index_file(entry.path(), manifest, embedder.as_deref())
+                });
+                match outcome {
+                    Ok(Outcome::Indexed) => progress.files_indexed += 1,
+                    Ok(Outcome::Unchanged) => progress.files_unchanged += 1,
+                    Ok(Outcome::Skipped) => progress.files_skipped += 1,
+                    Err(e) => {
+                        eprintln!("FILE:WARN: Failed to index {}: {}", progress.current, e);
+                        progress.files_failed += 1;
+                        progress.last_failure = Some(format!("{}: {}", progress.current, e));
+                    }
+                }
+
+                if progress.files_indexed > last_saved && progress.files_indexed % SAVE_EVERY == 0 {
+                    with_manifest(|manifest| save_manifest(manifest));
+                    last_saved = progress.files_indexed;
+                }
+
+                // `seen` is incomplete, so nothing under the root is removed
+                if !on_progress(&progress, &checkpoint) {
+                    with_manifest(|manifest| save_manifest(manifest));
+                    return progress;
+                }
+            }
+
+            progress.files_removed += with_manifest(|manifest| {
+                // Files the watcher indexed during the walk weren't `seen`,
+                // their mtime can be older than the walk when copied in
+                let missing = manifest
+                    .missing_under(root, &seen)
+                    .into_iter()
+                    .filter(|p| {
+                        manifest
+                            .get(Path::new(p))
+                            .map_or(true, |entry| entry.indexed_at < walk_started)
+                    })
+                    .collect();
+                let removed = remove_entries(missing, manifest);
+                save_manifest(manifest);
+                removed
+            });
+            checkpoint.roots_done.push(progress.root.clone());
+            checkpoint.progress = progress.clone();
+        }
+
+        progress.current = String::new();
+        progress.done = true;
+        on_progress(&progress, &checkpoint);
+
+        progress
+    }
+
+    // Every file beneath `dir` that the rules let through
+    fn files_under(&self, dir: &Path) -> Vec<PathBuf> {
+        self.rules
+            .walker(dir)
+            .build()
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| entry.file_type().map(|t| t.is_file()).unwrap_or(false))
+            .map(|entry| entry.into_path())
+            .collect()
+    }
+
+    /// Applies a set of individually changed paths to the index. Existing files
+    /// are (re-)indexed when their content changed, directories have every
+    /// file beneath them indexed, and paths that are gone are removed along
+    /// with anything that was indexed beneath them.
+    pub fn sync_paths(&self, paths: &[PathBuf]) -> SyncSummary {
+        let mut summary = SyncSummary::default();
+        let embedder = match prepare() {
+            Ok(embedder) => embedder,
+            Err(e) => {
+                eprintln!("FILE:WARN: Not syncing changes: {}", e);
+                return summary;
+            }
+        };
+        let mut index = |path: &Path, manifest: &mut Manifest| {
+            let outcome = self.index_file(path, manifest, embedder.as_deref());
+            match outcome {
+                Ok(Outcome::Indexed) => summary.indexed.push(path.display().to_string()),
+                Ok(_) => {}
+                Err(e) => eprintln!("FILE:WARN: Failed to index {}: {}", path.display(), e),
+            }
+        };
+
+        for path in paths {
+            if path.is_file() {
+                with_manifest(|manifest| index(path, manifest));
+            } else if path.is_dir() {
+                // A directory created or moved into a root only reports itself
+                for file in self.files_under(path) {
+                    with_manifest(|manifest| index(&file, manifest));
+                }
+            } else if !path.exists() {
+                let removed = with_manifest(|manifest| {
+                    let missing = manifest.missing_under(path, &HashSet::new());
+                    remove_entries(missing, manifest)
+                });
+                if removed > 0 {
+                    summary.removed.push(path.display().to_string());
+                }
+            }
+        }
+
+        with_manifest(|manifest| save_manifest(manifest));
+        summary
+    }
+}
+
+/// A full run of the configured indexer as a job, see `jobs`. The checkpoint
+/// is saved after every root and when the job is stopped.
+pub fn index_job(control: &JobControl, checkpoint: Option<Value>) -> Result<(), String> {
+    let checkpoint: IndexCheckpoint = match checkpoint {
+        Some(value) => serde_json::from_value(value).map_err(|e| e.to_string())?,
+        None => IndexCheckpoint::default(),
+    };
+    let mut roots_done = checkpoint.roots_done.len();
+    let mut failed = checkpoint.progress.files_failed;
+
+    let progress = Indexer::configured().index_local_files(checkpoint, |progress, checkpoint| {
+        control.progress(
+            progress.files_seen as u64,
+            progress.done.then_some(progress.files_seen as u64),
+        );
+        control.current(&progress.current);
+
+        if progress.files_failed > failed {
+            failed = progress.files_failed;
+            if let Some(failure) = &progress.last_failure {
+                control.error(failure.clone());
+            }
+        }
+        if checkpoint.roots_done.len() > roots_done {
+            roots_done = checkpoint.roots_done.len();
+            control.checkpoint(checkpoint);
+        }
+
+        match control.stopping() {
+            Some(_) => {
+                control.checkpoint(checkpoint);
+                false
+            }
+            None => true,
+        }
+    });
+
+    match progress.error {
+        Some(error) => Err(error),
+        None => Ok(()),
+    }
+}
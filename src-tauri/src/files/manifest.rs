@@ -0,0 +1,257 @@
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::{HashMap, HashSet};
+use std::error::Error;
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+use crate::util::db_path;
+use crate::util::ids::{chunk_id, parse_chunk_id};
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ManifestEntry {
+    /// `None` for files that were looked at but had nothing to index
+    pub id: Option<String>,
+    /// Number of `{id}:{n}` chunks stored for the file, 0 for images which
+    /// are stored whole under `id`
+    #[serde(default)]
+    pub chunks: usize,
+    pub size: u64,
+    pub mtime: u64,
+    pub hash: String,
+    /// Embedding model the file was stored with, `None` for OpenCLIP
+    #[serde(default)]
+    pub model: Option<String>,
+    /// When the indexer last wrote this entry, in seconds since the epoch
+    #[serde(default)]
+    pub indexed_at: u64,
+}
+
+impl ManifestEntry {
+    pub fn store_ids(&self) -> Vec<String> {
+        match (&self.id, self.chunks) {
+            (None, _) => Vec::new(),
+            (Some(id), 0) => vec![id.clone()],
+            (Some(id), n) => (0..n).map(|i| chunk_id(id, i)).collect(),
+        }
+    }
+}
+
+/// Record of every document the local indexer has written to the store,
+/// keyed by absolute file path. Persisted next to the Chroma datastore.
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct Manifest {
+    pub entries: HashMap<String, ManifestEntry>,
+}
+
+/// The result of comparing a file on disk against its manifest entry
+#[derive(Debug, PartialEq)]
+pub enum FileState {
+    New,
+    Changed { hash: String },
+    Touched,
+    Unchanged,
+}
+
+pub fn manifest_path() -> PathBuf {
+    db_path().join("manifest.json")
+}
+
+pub fn hash_file(path: &Path) -> io::Result<String> {
+    let mut file = File::open(path)?;
+    let mut hasher = Sha256::new();
+    let mut buffer = [0u8; 64 * 1024];
+
+    loop {
+        let read = file.read(&mut buffer)?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+    }
+
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+pub fn file_mtime(metadata: &std::fs::Metadata) -> u64 {
+    metadata
+        .modified()
+        .ok()
+        .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+impl Manifest {
+    pub fn load() -> Result<Manifest, Box<dyn Error>> {
+        let path = manifest_path();
+
+        if !path.exists() {
+            return Ok(Manifest::default());
+        }
+
+        let file = File::open(path)?;
+        let manifest: Manifest = serde_json::from_reader(file)?;
+        Ok(manifest)
+    }
+
+    pub fn save(&self) -> Result<(), Box<dyn Error>> {
+        let path = manifest_path();
+        let tmp_path = path.with_extension("json.tmp");
+
+        // Write to a sibling file first so a crash mid-write never leaves a torn manifest
+        let json_data = serde_json::to_string(self)?;
+        let mut file = File::create(&tmp_path)?;
+        file.write_all(json_data.as_bytes())?;
+        std::fs::rename(tmp_path, path)?;
+
+        Ok(())
+    }
+
+    /// Cheap size/mtime comparison first, only hashing the file when those differ
+    pub fn check(&self, path: &Path, size: u64, mtime: u64) -> io::Result<FileState> {
+        let key = path.display().to_string();
+
+        let entry = match self.entries.get(&key) {
+            Some(entry) => entry,
+            None => return Ok(FileState::New),
+        };
+
+        if entry.size == size && entry.mtime == mtime {
+            return Ok(FileState::Unchanged);
+        }
+
+        let hash = hash_file(path)?;
+        if hash == entry.hash {
+            Ok(FileState::Touched)
+        } else {
+            Ok(FileState::Changed { hash })
+        }
+    }
+
+    pub fn get(&self, path: &Path) -> Option<&ManifestEntry> {
+        self.entries.get(&path.display().to_string())
+    }
+
+    pub fn insert(&mut self, path: &Path, entry: ManifestEntry) {
+        self.entries.insert(path.display().to_string(), entry);
+    }
+
+    pub fn remove(&mut self, path: &Path) -> Option<ManifestEntry> {
+        self.entries.remove(&path.display().to_string())
+    }
+
+    /// Looks up the file behind a document or chunk id
+    pub fn find(&self, id: &str) -> Option<(&String, &ManifestEntry)> {
+        let (document, _) = parse_chunk_id(id);
+        self.entries
+            .iter()
+            .find(|(_, entry)| entry.id.as_deref() == Some(document))
+    }
+
+    /// Paths under `root` that are in the manifest but weren't in `seen`
+    pub fn missing_under(&self, root: &Path, seen: &HashSet<String>) -> Vec<String> {
+        self.entries
+            .keys()
+            .filter(|p| Path::new(p).starts_with(root) && !seen.contains(*p))
+            .cloned()
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(id: Option<&str>, chunks: usize, size: u64, mtime: u64, hash: &str) -> ManifestEntry {
+        ManifestEntry {
+            id: id.map(str::to_string),
+            chunks,
+            size,
+            mtime,
+            hash: hash.to_string(),
+            model: None,
+            indexed_at: 0,
+        }
+    }
+
+    fn manifest(paths: &[&str]) -> Manifest {
+        let mut manifest = Manifest::default();
+        for (i, path) in paths.iter().enumerate() {
+            let id = format!("doc{}", i);
+            manifest.insert(Path::new(path), entry(Some(&id), 2, 1, 1, "h"));
+        }
+        manifest
+    }
+
+    #[test]
+    fn check_compares_size_and_mtime_before_hashing() {
+        let path = std::env::temp_dir().join(format!("sift-manifest-{}.txt", std::process::id()));
+        std::fs::write(&path, "hello").unwrap();
+        let hash = hash_file(&path).unwrap();
+
+        let mut manifest = Manifest::default();
+        assert_eq!(manifest.check(&path, 5, 10).unwrap(), FileState::New);
+
+        manifest.insert(&path, entry(Some("doc"), 1, 5, 10, &hash));
+        assert_eq!(manifest.check(&path, 5, 10).unwrap(), FileState::Unchanged);
+        assert_eq!(manifest.check(&path, 5, 11).unwrap(), FileState::Touched);
+
+        std::fs::write(&path, "hello there").unwrap();
+        let changed = manifest.check(&path, 11, 12).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(
+            changed,
+            FileState::Changed {
+                hash: format!("{:x}", Sha256::digest(b"hello there"))
+            }
+        );
+    }
+
+    #[test]
+    fn missing_under_only_looks_beneath_the_root() {
+        let manifest = manifest(&[
+            "/docs/a.txt",
+            "/docs/sub/b.txt",
+            "/docs2/c.txt",
+            "/other/d.txt",
+        ]);
+        let seen = HashSet::from(["/docs/a.txt".to_string()]);
+
+        let mut missing = manifest.missing_under(Path::new("/docs"), &seen);
+        missing.sort();
+        assert_eq!(missing, ["/docs/sub/b.txt"]);
+
+        let mut gone = manifest.missing_under(Path::new("/docs/sub"), &HashSet::new());
+        gone.sort();
+        assert_eq!(gone, ["/docs/sub/b.txt"]);
+    }
+
+    #[test]
+    fn store_ids_cover_every_chunk() {
+        assert_eq!(
+            entry(Some("doc"), 2, 0, 0, "").store_ids(),
+            ["doc:0", "doc:1"]
+        );
+        // Images are stored whole
+        assert_eq!(entry(Some("img"), 0, 0, 0, "").store_ids(), ["img"]);
+        assert!(entry(None, 0, 0, 0, "").store_ids().is_empty());
+    }
+
+    #[test]
+    fn find_resolves_chunk_ids_to_their_file() {
+        let manifest = manifest(&["/docs/a.txt", "/docs/b.txt"]);
+
+        assert_eq!(
+            manifest.find("doc1:1").map(|(p, _)| p.as_str()),
+            Some("/docs/b.txt")
+        );
+        assert_eq!(
+            manifest.find("doc0").map(|(p, _)| p.as_str()),
+            Some("/docs/a.txt")
+        );
+        assert!(manifest.find("doc7:0").is_none());
+    }
+}
@@ -0,0 +1,38 @@
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::io;
+use std::path::Path;
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct FileMetadata {
+    pub filepath: String,
+    pub filename: String,
+    pub extension: String,
+    pub size: u64,
+}
+
+impl FileMetadata {
+    pub fn from_path(path: &Path) -> io::Result<FileMetadata> {
+        let size = std::fs::metadata(path)?.len();
+
+        Ok(FileMetadata {
+            filepath: path.display().to_string(),
+            filename: path
+                .file_name()
+                .map(|n| n.to_string_lossy().to_string())
+                .unwrap_or_default(),
+            extension: path
+                .extension()
+                .map(|e| e.to_string_lossy().to_lowercase())
+                .unwrap_or_default(),
+            size,
+        })
+    }
+
+    pub fn to_map(&self) -> Map<String, Value> {
+        match serde_json::to_value(self) {
+            Ok(Value::Object(map)) => map,
+            _ => Map::new(),
+        }
+    }
+}
@@ -0,0 +1,12 @@
+pub mod chunk;
+pub mod extract;
+pub mod indexer;
+pub mod manifest;
+pub mod metadata;
+pub mod rules;
+pub mod watcher;
+
+pub use indexer::{index_job, Indexer};
+pub use manifest::Manifest;
+pub use metadata::FileMetadata;
+pub use watcher::{reload_watcher, spawn_watcher};
@@ -0,0 +1,193 @@
+use ignore::gitignore::GitignoreBuilder;
+use ignore::overrides::{Override, OverrideBuilder};
+use ignore::{Match, WalkBuilder};
+use std::error::Error;
+use std::path::{Path, PathBuf};
+
+use crate::util::IndexingConfig;
+
+/// Per-directory ignore file, same syntax as `.gitignore`
+pub const SIFTIGNORE: &str = ".siftignore";
+
+struct Root {
+    path: PathBuf,
+    overrides: Override,
+}
+
+/// The compiled form of `IndexingConfig`, shared by the full walk and the
+/// watcher so both agree on what belongs in the index.
+pub struct IndexRules {
+    roots: Vec<Root>,
+    max_file_size: u64,
+    index_hidden: bool,
+    respect_gitignore: bool,
+}
+
+fn expand_root(root: &str) -> Option<PathBuf> {
+    let path = match root.strip_prefix('~') {
+        Some(rest) => dirs::home_dir()?.join(rest.trim_start_matches(['/', '\\'])),
+        None => PathBuf::from(root),
+    };
+
+    if !path.is_absolute() {
+        eprintln!("FILE:WARN: Ignoring relative index root {}", root);
+        return None;
+    }
+
+    Some(path)
+}
+
+fn build_overrides(root: &Path, config: &IndexingConfig) -> Result<Override, Box<dyn Error>> {
+    let mut builder = OverrideBuilder::new(root);
+    builder.case_insensitive(true)?;
+
+    // Later globs win, so excludes go last to take precedence over includes
+    for glob in &config.include {
+        builder.add(glob)?;
+    }
+    for glob in &config.exclude {
+        builder.add(&format!("!{}", glob))?;
+    }
+
+    Ok(builder.build()?)
+}
+
+fn is_hidden(path: &Path) -> bool {
+    path.file_name()
+        .map(|name| name.to_string_lossy().starts_with('.'))
+        .unwrap_or(false)
+}
+
+impl IndexRules {
+    /// Fails on globs that don't parse. Roots that don't exist are dropped.
+    pub fn from_config(config: &IndexingConfig) -> Result<IndexRules, Box<dyn Error>> {
+        let mut roots = Vec::new();
+
+        for root in &config.roots {
+            let path = match expand_root(root) {
+                Some(path) if path.is_dir() => path,
+                Some(path) => {
+                    eprintln!("FILE:WARN: Index root {} does not exist", path.display());
+                    continue;
+                }
+                None => continue,
+            };
+
+            let overrides = build_overrides(&path, config)?;
+            roots.push(Root { path, overrides });
+        }
+
+        Ok(IndexRules {
+            roots,
+            max_file_size: config.max_file_size,
+            index_hidden: config.index_hidden,
+            respect_gitignore: config.respect_gitignore,
+        })
+    }
+
+    pub fn roots(&self) -> Vec<PathBuf> {
+        self.roots.iter().map(|root| root.path.clone()).collect()
+    }
+
+    fn root_of(&self, path: &Path) -> Option<&Root> {
+        self.roots
+            .iter()
+            .filter(|root| path.starts_with(&root.path))
+            .max_by_key(|root| root.path.components().count())
+    }
+
+    /// A walker over `root` that applies every rule
+    pub fn walker(&self, root: &Path) -> WalkBuilder {
+        let mut builder = WalkBuilder::new(root);
+        builder
+            .hidden(!self.index_hidden)
+            .git_ignore(self.respect_gitignore)
+            .git_exclude(self.respect_gitignore)
+            .git_global(self.respect_gitignore)
+            .require_git(false)
+            .add_custom_ignore_filename(SIFTIGNORE)
+            .max_filesize(Some(self.max_file_size));
+
+        if let Some(root) = self.root_of(root) {
+            builder.overrides(root.overrides.clone());
+        }
+
+        builder
+    }
+
+    /// Mirrors the walker for paths that arrive one at a time, such as watcher
+    /// events. Paths outside every root are excluded.
+    pub fn is_excluded(&self, path: &Path) -> bool {
+        let root = match self.root_of(path) {
+            Some(root) => root,
+            None => return true,
+        };
+        let relative = match path.strip_prefix(&root.path) {
+            Ok(relative) => relative,
+            Err(_) => return true,
+        };
+
+        // Every directory on the way down has to survive the same filters
+        // the walker would have pruned it with
+        let path_is_dir = path.is_dir();
+        let mut current = root.path.clone();
+        let mut prefix = PathBuf::new();
+        let components: Vec<_> = relative.components().collect();
+        for (i, component) in components.iter().enumerate() {
+            let is_dir = i + 1 < components.len() || path_is_dir;
+
+            if self.is_ignored_by_files(&current, path, path_is_dir) {
+                return true;
+            }
+
+            current.push(component);
+            prefix.push(component);
+
+            if !self.index_hidden && is_hidden(&prefix) {
+                return true;
+            }
+            if root.overrides.matched(&prefix, is_dir).is_ignore() {
+                return true;
+            }
+        }
+
+        match path.metadata() {
+            Ok(metadata) => metadata.is_file() && metadata.len() > self.max_file_size,
+            Err(_) => false,
+        }
+    }
+
+    // Checks the ignore files that live directly in `dir` against `path`
+    fn is_ignored_by_files(&self, dir: &Path, path: &Path, is_dir: bool) -> bool {
+        let mut names = vec![SIFTIGNORE];
+        if self.respect_gitignore {
+            names.push(".gitignore");
+        }
+
+        let mut builder = GitignoreBuilder::new(dir);
+        let mut found = false;
+        for name in names {
+            let file = dir.join(name);
+            if file.is_file() && builder.add(&file).is_none() {
+                found = true;
+            }
+        }
+        if !found {
+            return false;
+        }
+
+        match builder.build() {
+            Ok(gitignore) => matches!(
+                gitignore.matched_path_or_any_parents(path, is_dir),
+                Match::Ignore(_)
+            ),
+            Err(_) => false,
+        }
+    }
+}
+
+impl Default for IndexRules {
+    fn default() -> Self {
+        IndexRules::from_config(&IndexingConfig::default()).expect("default index rules are valid")
+    }
+}
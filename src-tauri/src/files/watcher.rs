@@ -0,0 +1,133 @@
+use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use std::collections::HashSet;
+use std::path::PathBuf;
+use std::sync::mpsc::{channel, RecvTimeoutError, Sender};
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::files::indexer::{Indexer, SyncSummary};
+use crate::files::rules::IndexRules;
+
+// Editors and sync clients fire bursts of events per save, wait for quiet
+const DEBOUNCE: Duration = Duration::from_secs(2);
+
+// Upper bound on how long a constantly changing tree can hold back a batch
+const MAX_BATCH_DELAY: Duration = Duration::from_secs(30);
+
+fn relevant_paths(event: Event, rules: &IndexRules) -> Vec<PathBuf> {
+    match event.kind {
+        EventKind::Create(_) | EventKind::Modify(_) | EventKind::Remove(_) => {}
+        _ => return Vec::new(),
+    }
+
+    // Renames arrive as a modify event carrying both the old and new path
+    event
+        .paths
+        .into_iter()
+        .filter(|path| !rules.is_excluded(path))
+        .collect()
+}
+
+enum Message {
+    Event(notify::Result<Event>),
+    Reload(Indexer),
+}
+
+// Set once the watcher runs, `reload_watcher` talks to it through this
+static RELOAD: Mutex<Option<Sender<Message>>> = Mutex::new(None);
+
+fn watch_roots(watcher: &mut RecommendedWatcher, indexer: &Indexer) -> notify::Result<()> {
+    for root in indexer.rules.roots() {
+        watcher.watch(&root, RecursiveMode::Recursive)?;
+        println!("FILE:INFO: Watching {}", root.display());
+    }
+    Ok(())
+}
+
+/// Watches the indexer's roots on a background thread and applies debounced
+/// batches of changes to the index. `on_batch` is called after every batch
+/// that indexed or removed at least one file.
+pub fn spawn_watcher<F>(indexer: Indexer, mut on_batch: F) -> notify::Result<()>
+where
+    F: FnMut(&SyncSummary) + Send + 'static,
+{
+    let (tx, rx) = channel();
+    let events = tx.clone();
+    let mut watcher = RecommendedWatcher::new(
+        move |event| {
+            let _ = events.send(Message::Event(event));
+        },
+        notify::Config::default(),
+    )?;
+    watch_roots(&mut watcher, &indexer)?;
+    *RELOAD.lock().unwrap_or_else(|e| e.into_inner()) = Some(tx);
+
+    thread::spawn(move || {
+        let mut indexer = indexer;
+        let mut pending: HashSet<PathBuf> = HashSet::new();
+        let mut first_pending: Option<Instant> = None;
+
+        loop {
+            match rx.recv_timeout(DEBOUNCE) {
+                Ok(Message::Event(Ok(event))) => {
+                    let paths = relevant_paths(event, &indexer.rules);
+                    if !paths.is_empty() && first_pending.is_none() {
+                        first_pending = Some(Instant::now());
+                    }
+                    pending.extend(paths);
+
+                    let overdue = first_pending
+                        .map(|t| t.elapsed() >= MAX_BATCH_DELAY)
+                        .unwrap_or(false);
+                    if !overdue {
+                        continue;
+                    }
+                }
+                Ok(Message::Event(Err(e))) => {
+                    eprintln!("FILE:WARN: Watch error: {}", e);
+                    continue;
+                }
+                Ok(Message::Reload(reloaded)) => {
+                    for root in indexer.rules.roots() {
+                        let _ = watcher.unwatch(&root);
+                    }
+                    if let Err(e) = watch_roots(&mut watcher, &reloaded) {
+                        eprintln!("FILE:WARN: Failed to watch the new roots: {}", e);
+                    }
+                    indexer = reloaded;
+                    continue;
+                }
+                Err(RecvTimeoutError::Timeout) => {}
+                Err(RecvTimeoutError::Disconnected) => break,
+            }
+
+            if pending.is_empty() {
+                continue;
+            }
+
+            let paths: Vec<PathBuf> = pending.drain().collect();
+            first_pending = None;
+
+            let summary = indexer.sync_paths(&paths);
+            if !summary.indexed.is_empty() || !summary.removed.is_empty() {
+                println!(
+                    "FILE:INFO: Watcher indexed {} and removed {} file(s)",
+                    summary.indexed.len(),
+                    summary.removed.len()
+                );
+                on_batch(&summary);
+            }
+        }
+    });
+
+    Ok(())
+}
+
+/// Swaps the rules of the running watcher, e.g. after the indexing config
+/// changed. Does nothing when no watcher runs.
+pub fn reload_watcher(indexer: Indexer) {
+    if let Some(tx) = RELOAD.lock().unwrap_or_else(|e| e.into_inner()).as_ref() {
+        let _ = tx.send(Message::Reload(indexer));
+    }
+}
@@ -99,15 +99,16 @@ pub async fn github_oauth() -> Result<String, String> {
     let (shutdown_tx, shutdown_rx) = tokio::sync::oneshot::channel();
 
     let server = warp::serve(redirect_route);
-    let (_addr, server) = server.bind_with_graceful_shutdown(([127, 0, 0, 1], 35435), async {
+    let port = util::PortRegistry::shared().port(util::Service::GithubOauth);
+    let (_addr, server) = server.bind_with_graceful_shutdown(([127, 0, 0, 1], port), async {
         shutdown_rx.await.ok();
     });
 
     tokio::spawn(server);
 
     let auth_url = format!(
-        "https://github.com/login/oauth/authorize?client_id={}&scope=repo,user&redirect_uri=http://localhost:35435/gh_auth_callback",
-        github_client_id
+        "https://github.com/login/oauth/authorize?client_id={}&scope=repo,user&redirect_uri=http://localhost:{}/gh_auth_callback",
+        github_client_id, port
     );
 
     // println!("Open this URL in your browser: {}", auth_url);
@@ -248,7 +249,12 @@ pub async fn slack_oauth() -> Result<String, String> {
         .tls()
         .cert_path("./cert.pem")
         .key_path("./key.pem");
-    let (_addr, server_fut) = server.bind_with_graceful_shutdown(([127, 0, 0, 1], 35439), async {
+    let redirect_uri = format!(
+        "{}/slk_auth_callback",
+        util::PortRegistry::shared().url(util::Service::SlackOauth)
+    );
+    let port = util::PortRegistry::shared().port(util::Service::SlackOauth);
+    let (_addr, server_fut) = server.bind_with_graceful_shutdown(([127, 0, 0, 1], port), async {
         shutdown_rx.await.ok();
     });
 
@@ -260,8 +266,8 @@ pub async fn slack_oauth() -> Result<String, String> {
 
     // Slack authorization URL with redirect_uri pointing to the Warp server
     let auth_url = format!(
-        "https://slack.com/oauth/v2/authorize?client_id={}&scope={}&redirect_uri=https://localhost:35439/slk_auth_callback",
-        slack_client_id, scopes
+        "https://slack.com/oauth/v2/authorize?client_id={}&scope={}&redirect_uri={}",
+        slack_client_id, scopes, redirect_uri
     );
 
     // Open the authorization URL in the browser
@@ -291,7 +297,7 @@ pub async fn slack_oauth() -> Result<String, String> {
         ("client_id", slack_client_id),
         ("client_secret", slack_client_secret),
         ("code", slack_authorization_code.as_str()),
-        ("redirect_uri", "https://localhost:35439/slk_auth_callback"),
+        ("redirect_uri", redirect_uri.as_str()),
     ];
 
     let response = client
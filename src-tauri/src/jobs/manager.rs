@@ -0,0 +1,744 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::error::Error;
+use std::ffi::OsStr;
+use std::fs::File;
+use std::io::{BufReader, BufWriter};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+
+use crate::files;
+use crate::sync;
+use crate::sync::SyncTrigger;
+use crate::util::{db_path, SyncSource};
+
+// Progress events of a job are at least this far apart, state changes are
+// sent right away
+const EVENT_INTERVAL: Duration = Duration::from_millis(250);
+// Errors kept per job, `error_count` has the full number
+const MAX_ERRORS: usize = 50;
+// Finished jobs listed before the oldest are dropped
+const MAX_FINISHED: usize = 50;
+// How long `shutdown` waits for running jobs to save a checkpoint
+const SHUTDOWN_GRACE: Duration = Duration::from_secs(3);
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum JobKind {
+    /// A full walk of the indexing roots
+    LocalIndex,
+    /// A connector script, started by the scheduler or by hand
+    Sync {
+        source: SyncSource,
+        trigger: SyncTrigger,
+    },
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum JobState {
+    Running,
+    /// Asked to pause, stops at the next checkpoint
+    Pausing,
+    Paused,
+    /// Asked to cancel, stops at the next checkpoint
+    Cancelling,
+    Completed,
+    Failed,
+    Cancelled,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct JobProgress {
+    pub done: u64,
+    /// Unknown until the job has looked at everything
+    pub total: Option<u64>,
+}
+
+/// What the UI sees of a job, sent as `jobs://progress`
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Job {
+    pub id: String,
+    pub kind: JobKind,
+    pub label: String,
+    pub state: JobState,
+    pub progress: JobProgress,
+    pub current: Option<String>,
+    pub errors: Vec<String>,
+    pub error_count: usize,
+    /// Whether a resume continues from a checkpoint rather than starting over
+    pub resumable: bool,
+    /// Seconds since the epoch
+    pub created_at: i64,
+    pub updated_at: i64,
+    pub finished_at: Option<i64>,
+}
+
+/// Why a job is asked to stop
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Stop {
+    Pause,
+    Cancel,
+    /// The app is quitting, the job comes back paused
+    Shutdown,
+}
+
+/// Called with a job after every change
+pub type JobListener = Arc<dyn Fn(&Job) + Send + Sync>;
+
+// `jobs/{id}.json` in the datastore
+#[derive(Serialize, Deserialize)]
+struct SavedJob {
+    job: Job,
+    checkpoint: Option<Value>,
+}
+
+struct Entry {
+    job: Mutex<Job>,
+    checkpoint: Mutex<Option<Value>>,
+    stop: Mutex<Option<Stop>>,
+    // Whether the job saw `stop`, one that finished first just completed
+    stop_seen: AtomicBool,
+    last_event: Mutex<Option<Instant>>,
+}
+
+/// Runs long jobs on background threads and keeps track of them. Jobs stop
+/// cooperatively: pausing or cancelling sets a flag the job checks between
+/// items, and a paused job is started again from its last checkpoint.
+/// Checkpoints are saved to disk, so jobs cut off by a crash come back
+/// paused on the next launch.
+pub struct JobManager {
+    entries: Mutex<Vec<Arc<Entry>>>,
+    listener: OnceLock<JobListener>,
+    dir: PathBuf,
+    shutting_down: AtomicBool,
+}
+
+/// Handed to a running job to report on itself
+#[derive(Clone)]
+pub struct JobControl {
+    entry: Arc<Entry>,
+    manager: &'static JobManager,
+}
+
+fn now_secs() -> i64 {
+    SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or_default()
+}
+
+fn new_id() -> String {
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+    let millis = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map(|d| d.as_millis())
+        .unwrap_or_default();
+    format!("{:x}-{}", millis, COUNTER.fetch_add(1, Ordering::SeqCst))
+}
+
+impl JobKind {
+    pub fn label(&self) -> String {
+        match self {
+            JobKind::LocalIndex => "Indexing local files".to_string(),
+            JobKind::Sync { source, .. } => format!("Syncing {}", source.name()),
+        }
+    }
+
+    // Two jobs doing the same work never run at once
+    fn same_work(&self, other: &JobKind) -> bool {
+        match (self, other) {
+            (JobKind::LocalIndex, JobKind::LocalIndex) => true,
+            (JobKind::Sync { source: a, .. }, JobKind::Sync { source: b, .. }) => a == b,
+            _ => false,
+        }
+    }
+
+    fn run(&self, control: &JobControl, checkpoint: Option<Value>) -> Result<(), String> {
+        match self {
+            JobKind::LocalIndex => files::index_job(control, checkpoint),
+            JobKind::Sync { source, trigger } => {
+                sync::sync_job(*source, *trigger, control, checkpoint)
+            }
+        }
+    }
+}
+
+impl JobState {
+    fn name(self) -> String {
+        format!("{:?}", self).to_lowercase()
+    }
+
+    pub fn is_finished(self) -> bool {
+        matches!(
+            self,
+            JobState::Completed | JobState::Failed | JobState::Cancelled
+        )
+    }
+
+    pub fn is_running(self) -> bool {
+        matches!(
+            self,
+            JobState::Running | JobState::Pausing | JobState::Cancelling
+        )
+    }
+}
+
+impl Entry {
+    fn job(&self) -> Job {
+        self.job.lock().unwrap().clone()
+    }
+
+    fn id(&self) -> String {
+        self.job.lock().unwrap().id.clone()
+    }
+}
+
+impl JobManager {
+    /// Picks up the jobs saved in `dir`, the ones that were running come
+    /// back paused
+    pub fn new(dir: PathBuf) -> JobManager {
+        let mut entries = Vec::new();
+
+        if let Ok(files) = std::fs::read_dir(&dir) {
+            for path in files.filter_map(|f| f.ok()).map(|f| f.path()) {
+                if path.extension() != Some(OsStr::new("json")) {
+                    continue;
+                }
+                let saved: SavedJob =
+                    match File::open(&path).map_err(|e| e.to_string()).and_then(|f| {
+                        serde_json::from_reader(BufReader::new(f)).map_err(|e| e.to_string())
+                    }) {
+                        Ok(saved) => saved,
+                        Err(e) => {
+                            eprintln!("JOBS:WARN: Skipping {}: {}", path.display(), e);
+                            continue;
+                        }
+                    };
+
+                let mut job = saved.job;
+                if job.state.is_running() {
+                    job.state = JobState::Paused;
+                    push_error(
+                        &mut job,
+                        "Interrupted when the app quit, resume to continue".to_string(),
+                    );
+                }
+                job.resumable = saved.checkpoint.is_some();
+                entries.push(Arc::new(Entry {
+                    job: Mutex::new(job),
+                    checkpoint: Mutex::new(saved.checkpoint),
+                    stop: Mutex::new(None),
+                    stop_seen: AtomicBool::new(false),
+                    last_event: Mutex::new(None),
+                }));
+            }
+        }
+        entries.sort_by_key(|e| e.job.lock().unwrap().created_at);
+
+        JobManager {
+            entries: Mutex::new(entries),
+            listener: OnceLock::new(),
+            dir,
+            shutting_down: AtomicBool::new(false),
+        }
+    }
+
+    pub fn shared() -> &'static JobManager {
+        static MANAGER: OnceLock<JobManager> = OnceLock::new();
+        MANAGER.get_or_init(|| JobManager::new(db_path().join("jobs")))
+    }
+
+    /// Sets where job updates go, only the first call counts
+    pub fn on_change<F>(&self, listener: F)
+    where
+        F: Fn(&Job) + Send + Sync + 'static,
+    {
+        let _ = self.listener.set(Arc::new(listener));
+    }
+
+    /// Every job, newest first
+    pub fn list(&self) -> Vec<Job> {
+        let entries = self.entries.lock().unwrap();
+        entries.iter().rev().map(|e| e.job()).collect()
+    }
+
+    /// A running or paused job doing the same work as `kind`
+    pub fn active(&self, kind: &JobKind) -> Option<Job> {
+        let entries = self.entries.lock().unwrap();
+        entries
+            .iter()
+            .map(|e| e.job())
+            .find(|job| !job.state.is_finished() && job.kind.same_work(kind))
+    }
+
+    /// Starts a job, unless one doing the same work is running or paused
+    pub fn start(&'static self, kind: JobKind) -> Result<Job, String> {
+        let entry = {
+            let mut entries = self.entries.lock().unwrap();
+            if self.shutting_down.load(Ordering::SeqCst) {
+                return Err("The app is shutting down".to_string());
+            }
+            if let Some(job) = entries
+                .iter()
+                .map(|e| e.job())
+                .find(|job| !job.state.is_finished() && job.kind.same_work(&kind))
+            {
+                return Err(format!("{} is already {}", job.label, job.state.name()));
+            }
+
+            let now = now_secs();
+            let entry = Arc::new(Entry {
+                job: Mutex::new(Job {
+                    id: new_id(),
+                    label: kind.label(),
+                    kind,
+                    state: JobState::Running,
+                    progress: JobProgress::default(),
+                    current: None,
+                    errors: Vec::new(),
+                    error_count: 0,
+                    resumable: false,
+                    created_at: now,
+                    updated_at: now,
+                    finished_at: None,
+                }),
+                checkpoint: Mutex::new(None),
+                stop: Mutex::new(None),
+                stop_seen: AtomicBool::new(false),
+                last_event: Mutex::new(None),
+            });
+            entries.push(entry.clone());
+            entry
+        };
+
+        self.run(entry.clone());
+        Ok(entry.job())
+    }
+
+    fn find(&self, id: &str) -> Result<Arc<Entry>, String> {
+        self.entries
+            .lock()
+            .unwrap()
+            .iter()
+            .find(|e| e.id() == id)
+            .cloned()
+            .ok_or_else(|| format!("No job with id {}", id))
+    }
+
+    fn run(&'static self, entry: Arc<Entry>) {
+        self.persist(&entry);
+        self.notify(&entry, true);
+
+        thread::spawn(move || {
+            let control = JobControl {
+                entry: entry.clone(),
+                manager: self,
+            };
+            let kind = entry.job().kind;
+            let checkpoint = entry.checkpoint.lock().unwrap().clone();
+            let result = kind.run(&control, checkpoint);
+
+            let stop = entry
+                .stop
+                .lock()
+                .unwrap()
+                .take()
+                .filter(|_| entry.stop_seen.swap(false, Ordering::SeqCst));
+            {
+                let mut job = entry.job.lock().unwrap();
+                job.state = match (stop, result) {
+                    (Some(Stop::Cancel), _) => JobState::Cancelled,
+                    (Some(Stop::Pause | Stop::Shutdown), _) => JobState::Paused,
+                    (None, Ok(())) => JobState::Completed,
+                    (None, Err(reason)) => {
+                        eprintln!("JOBS:WARN: {} failed: {}", job.label, reason);
+                        push_error(&mut job, reason);
+                        JobState::Failed
+                    }
+                };
+                job.updated_at = now_secs();
+                if job.state.is_finished() {
+                    job.finished_at = Some(job.updated_at);
+                    job.current = None;
+                }
+                println!("JOBS:INFO: {} is {:?}", job.label, job.state);
+            }
+
+            self.persist(&entry);
+            self.notify(&entry, true);
+            self.prune();
+        });
+    }
+
+    /// Asks a running job to pause, it keeps its place
+    pub fn pause(&self, id: &str) -> Result<Job, String> {
+        let entry = self.find(id)?;
+        {
+            let mut job = entry.job.lock().unwrap();
+            if job.state != JobState::Running {
+                return Err(format!("Can't pause a job that is {}", job.state.name()));
+            }
+            *entry.stop.lock().unwrap() = Some(Stop::Pause);
+            job.state = JobState::Pausing;
+        }
+        self.notify(&entry, true);
+        Ok(entry.job())
+    }
+
+    /// Starts a paused or failed job again from its last checkpoint
+    pub fn resume(&'static self, id: &str) -> Result<Job, String> {
+        let entry = self.find(id)?;
+        {
+            let entries = self.entries.lock().unwrap();
+            let mut job = entry.job.lock().unwrap();
+            if !matches!(job.state, JobState::Paused | JobState::Failed) {
+                return Err(format!("Can't resume a job that is {}", job.state.name()));
+            }
+            if self.shutting_down.load(Ordering::SeqCst) {
+                return Err("The app is shutting down".to_string());
+            }
+            // A failed job may have been started again in the meantime
+            let busy = entries.iter().any(|other| {
+                !Arc::ptr_eq(other, &entry) && {
+                    let other = other.job.lock().unwrap();
+                    !other.state.is_finished() && other.kind.same_work(&job.kind)
+                }
+            });
+            if busy {
+                return Err(format!("{} is already running", job.label));
+            }
+            job.state = JobState::Running;
+            job.finished_at = None;
+            job.updated_at = now_secs();
+        }
+
+        self.run(entry.clone());
+        Ok(entry.job())
+    }
+
+    /// Stops a job for good, dropping its checkpoint
+    pub fn cancel(&self, id: &str) -> Result<Job, String> {
+        let entry = self.find(id)?;
+        let stopped_now = {
+            let mut job = entry.job.lock().unwrap();
+            match job.state {
+                JobState::Running | JobState::Pausing => {
+                    *entry.stop.lock().unwrap() = Some(Stop::Cancel);
+                    job.state = JobState::Cancelling;
+                    false
+                }
+                JobState::Paused | JobState::Failed => {
+                    job.state = JobState::Cancelled;
+                    job.updated_at = now_secs();
+                    job.finished_at = Some(job.updated_at);
+                    true
+                }
+                state => return Err(format!("Can't cancel a job that is {}", state.name())),
+            }
+        };
+
+        if stopped_now {
+            self.persist(&entry);
+        }
+        self.notify(&entry, true);
+        Ok(entry.job())
+    }
+
+    /// Asks running jobs to stop where they are and waits a little for them
+    /// to save a checkpoint, they're picked up paused on the next launch
+    pub fn shutdown(&self) {
+        if self.shutting_down.swap(true, Ordering::SeqCst) {
+            return;
+        }
+
+        let running: Vec<Arc<Entry>> = self
+            .entries
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|e| e.job.lock().unwrap().state.is_running())
+            .cloned()
+            .collect();
+        for entry in &running {
+            let mut stop = entry.stop.lock().unwrap();
+            // A cancel that's under way still cancels
+            if *stop != Some(Stop::Cancel) {
+                *stop = Some(Stop::Shutdown);
+            }
+        }
+
+        let deadline = Instant::now() + SHUTDOWN_GRACE;
+        while Instant::now() < deadline
+            && running
+                .iter()
+                .any(|e| e.job.lock().unwrap().state.is_running())
+        {
+            thread::sleep(Duration::from_millis(50));
+        }
+    }
+
+    fn notify(&self, entry: &Entry, force: bool) {
+        {
+            let mut last = entry.last_event.lock().unwrap();
+            if !force && last.is_some_and(|at| at.elapsed() < EVENT_INTERVAL) {
+                return;
+            }
+            *last = Some(Instant::now());
+        }
+        if let Some(listener) = self.listener.get() {
+            listener(&entry.job());
+        }
+    }
+
+    // Finished jobs have nothing to resume, only the others are kept on disk
+    fn persist(&self, entry: &Entry) {
+        let job = entry.job();
+        let path = self.dir.join(format!("{}.json", job.id));
+
+        let result = if job.state.is_finished() && job.state != JobState::Failed {
+            match std::fs::remove_file(&path) {
+                Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+                _ => Ok(()),
+            }
+        } else {
+            let saved = SavedJob {
+                job,
+                checkpoint: entry.checkpoint.lock().unwrap().clone(),
+            };
+            save_json(&path, &saved)
+        };
+
+        if let Err(e) = result {
+            eprintln!("JOBS:WARN: Failed to save job {}: {}", path.display(), e);
+        }
+    }
+
+    fn prune(&self) {
+        let mut entries = self.entries.lock().unwrap();
+        let finished = entries
+            .iter()
+            .filter(|e| e.job.lock().unwrap().state.is_finished())
+            .count();
+        let mut excess = finished.saturating_sub(MAX_FINISHED);
+
+        entries.retain(|e| {
+            let job = e.job.lock().unwrap();
+            if excess > 0 && job.state.is_finished() {
+                excess -= 1;
+                let _ = std::fs::remove_file(self.dir.join(format!("{}.json", job.id)));
+                false
+            } else {
+                true
+            }
+        });
+    }
+}
+
+fn push_error(job: &mut Job, message: String) {
+    if job.errors.len() >= MAX_ERRORS {
+        job.errors.remove(0);
+    }
+    job.errors.push(message);
+    job.error_count += 1;
+}
+
+fn save_json<T: Serialize>(path: &PathBuf, value: &T) -> Result<(), Box<dyn Error>> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let tmp_path = path.with_extension("json.tmp");
+    let mut out = BufWriter::new(File::create(&tmp_path)?);
+    serde_json::to_writer(&mut out, value)?;
+    out.into_inner().map_err(|e| e.into_error())?.sync_all()?;
+    std::fs::rename(tmp_path, path)?;
+    Ok(())
+}
+
+impl JobControl {
+    /// Set when the job should wrap up: save a checkpoint and return
+    pub fn stopping(&self) -> Option<Stop> {
+        let stop = *self.entry.stop.lock().unwrap();
+        if stop.is_some() {
+            self.entry.stop_seen.store(true, Ordering::SeqCst);
+        }
+        stop
+    }
+
+    pub fn progress(&self, done: u64, total: Option<u64>) {
+        self.update(|job| job.progress = JobProgress { done, total });
+    }
+
+    /// The item being worked on, e.g. a file or a repository
+    pub fn current(&self, item: &str) {
+        self.update(|job| job.current = Some(item.to_string()));
+    }
+
+    /// Records an error that didn't stop the job
+    pub fn error(&self, message: String) {
+        self.update(|job| push_error(job, message));
+    }
+
+    /// Saves where to pick up again, handed back to the job when it resumes
+    pub fn checkpoint<T: Serialize>(&self, value: &T) {
+        match serde_json::to_value(value) {
+            Ok(value) => {
+                *self.entry.checkpoint.lock().unwrap() = Some(value);
+                self.entry.job.lock().unwrap().resumable = true;
+                self.manager.persist(&self.entry);
+            }
+            Err(e) => eprintln!("JOBS:WARN: Can't save a checkpoint: {}", e),
+        }
+    }
+
+    fn update(&self, change: impl FnOnce(&mut Job)) {
+        {
+            let mut job = self.entry.job.lock().unwrap();
+            change(&mut job);
+            job.updated_at = now_secs();
+        }
+        self.manager.notify(&self.entry, false);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn job(kind: JobKind, state: JobState) -> Job {
+        Job {
+            id: new_id(),
+            label: kind.label(),
+            kind,
+            state,
+            progress: JobProgress::default(),
+            current: None,
+            errors: Vec::new(),
+            error_count: 0,
+            resumable: false,
+            created_at: now_secs(),
+            updated_at: now_secs(),
+            finished_at: None,
+        }
+    }
+
+    fn github(trigger: SyncTrigger) -> JobKind {
+        JobKind::Sync {
+            source: SyncSource::Github,
+            trigger,
+        }
+    }
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("sift-jobs-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        dir
+    }
+
+    // Jobs that aren't actually running, so nothing picks up their stop flag
+    fn manager(name: &str, jobs: Vec<Job>) -> &'static JobManager {
+        let manager = JobManager::new(temp_dir(name));
+        for job in jobs {
+            manager.entries.lock().unwrap().push(Arc::new(Entry {
+                job: Mutex::new(job),
+                checkpoint: Mutex::new(None),
+                stop: Mutex::new(None),
+                stop_seen: AtomicBool::new(false),
+                last_event: Mutex::new(None),
+            }));
+        }
+        Box::leak(Box::new(manager))
+    }
+
+    fn stop(manager: &JobManager, id: &str) -> Option<Stop> {
+        *manager.find(id).unwrap().stop.lock().unwrap()
+    }
+
+    #[test]
+    fn running_jobs_are_asked_to_pause_then_cancel() {
+        let running = job(JobKind::LocalIndex, JobState::Running);
+        let id = running.id.clone();
+        let manager = manager("running", vec![running]);
+
+        assert_eq!(manager.pause(&id).unwrap().state, JobState::Pausing);
+        assert_eq!(stop(manager, &id), Some(Stop::Pause));
+        assert!(manager.pause(&id).is_err());
+        assert!(manager.resume(&id).is_err());
+
+        assert_eq!(manager.cancel(&id).unwrap().state, JobState::Cancelling);
+        assert_eq!(stop(manager, &id), Some(Stop::Cancel));
+        assert!(manager.cancel(&id).is_err());
+    }
+
+    #[test]
+    fn paused_jobs_are_cancelled_right_away() {
+        let paused = job(JobKind::LocalIndex, JobState::Paused);
+        let id = paused.id.clone();
+        let manager = manager("paused", vec![paused]);
+
+        let cancelled = manager.cancel(&id).unwrap();
+        assert_eq!(cancelled.state, JobState::Cancelled);
+        assert!(cancelled.finished_at.is_some());
+        assert!(manager.resume(&id).is_err());
+        assert!(manager.pause(&id).is_err());
+    }
+
+    #[test]
+    fn finished_jobs_stay_finished() {
+        let completed = job(JobKind::LocalIndex, JobState::Completed);
+        let id = completed.id.clone();
+        let manager = manager("finished", vec![completed]);
+
+        assert!(manager.pause(&id).is_err());
+        assert!(manager.resume(&id).is_err());
+        assert!(manager.cancel(&id).is_err());
+    }
+
+    #[test]
+    fn same_work_is_started_once() {
+        let paused = job(github(SyncTrigger::Scheduled), JobState::Paused);
+        let manager = manager("same-work", vec![paused]);
+
+        // The trigger doesn't make it different work
+        let active = manager.active(&github(SyncTrigger::Manual)).unwrap();
+        assert_eq!(active.state, JobState::Paused);
+        assert!(manager.active(&JobKind::LocalIndex).is_none());
+        assert_eq!(
+            manager.start(github(SyncTrigger::Manual)).unwrap_err(),
+            "Syncing github is already paused"
+        );
+    }
+
+    #[test]
+    fn interrupted_jobs_come_back_paused() {
+        let dir = temp_dir("reload");
+        let interrupted = job(JobKind::LocalIndex, JobState::Pausing);
+        let failed = job(github(SyncTrigger::Scheduled), JobState::Failed);
+        let saved = [
+            (&interrupted, Some(serde_json::json!({ "roots_done": [] }))),
+            (&failed, None),
+        ];
+        for (job, checkpoint) in saved {
+            let saved = SavedJob {
+                job: job.clone(),
+                checkpoint,
+            };
+            save_json(&dir.join(format!("{}.json", job.id)), &saved).unwrap();
+        }
+
+        let manager = JobManager::new(dir.clone());
+        let jobs = manager.list();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let interrupted = jobs.iter().find(|j| j.id == interrupted.id).unwrap();
+        assert_eq!(interrupted.state, JobState::Paused);
+        assert!(interrupted.resumable);
+        assert_eq!(interrupted.error_count, 1);
+        let failed = jobs.iter().find(|j| j.id == failed.id).unwrap();
+        assert_eq!(failed.state, JobState::Failed);
+        assert!(!failed.resumable);
+    }
+}
@@ -0,0 +1,4 @@
+pub mod manager;
+pub mod process;
+
+pub use manager::{Job, JobControl, JobKind, JobManager, JobState, Stop};
@@ -0,0 +1,134 @@
+use serde::Deserialize;
+use serde_json::Value;
+use std::env;
+use std::process::{Command, Stdio};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::jobs::manager::JobControl;
+use crate::sidecar::logs::{capture, capture_lines, LogFile};
+use crate::util;
+
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+/// Where a resumed script finds its last checkpoint, as JSON
+pub const CHECKPOINT_ENV: &str = "SIFT_JOB_CHECKPOINT";
+
+// `JOB:PROGRESS {"done": 3, "total": 12, "current": "repo"}`
+#[derive(Deserialize)]
+struct ProgressLine {
+    done: u64,
+    total: Option<u64>,
+    current: Option<String>,
+}
+
+fn python() -> &'static str {
+    if env::consts::OS == "windows" {
+        "python"
+    } else {
+        "python3"
+    }
+}
+
+// Scripts report on themselves with `JOB:` lines on stdout, see
+// `pybindings/jobs.py`
+fn handle_line(control: &JobControl, line: &str) {
+    let (kind, rest) = match line.strip_prefix("JOB:").and_then(|l| l.split_once(' ')) {
+        Some(parts) => parts,
+        None => return,
+    };
+
+    match kind {
+        "PROGRESS" => match serde_json::from_str::<ProgressLine>(rest) {
+            Ok(progress) => {
+                control.progress(progress.done, progress.total);
+                if let Some(current) = progress.current {
+                    control.current(&current);
+                }
+            }
+            Err(e) => eprintln!("JOBS:WARN: Bad progress line '{}': {}", rest, e),
+        },
+        "CHECKPOINT" => match serde_json::from_str::<Value>(rest) {
+            Ok(checkpoint) => control.checkpoint(&checkpoint),
+            Err(e) => eprintln!("JOBS:WARN: Bad checkpoint line '{}': {}", rest, e),
+        },
+        "ERROR" => control.error(rest.to_string()),
+        _ => {}
+    }
+}
+
+/// Runs `pybindings/{script} {args}` as the work of a job, with its output in
+/// `logs/{log_name}.log`. The script is killed when the job is asked to
+/// stop, and gets `checkpoint` back through `SIFT_JOB_CHECKPOINT` when it
+/// is started again.
+pub fn run_script(
+    control: &JobControl,
+    script: &str,
+    args: &[String],
+    log_name: &str,
+    timeout: Duration,
+    checkpoint: Option<Value>,
+) -> Result<(), String> {
+    let log = LogFile::open(log_name).map_err(|e| e.to_string())?;
+    let log = Arc::new(Mutex::new(log));
+
+    let mut command = Command::new(python());
+    command
+        .arg(util::pybinding(script))
+        .args(args)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+    if let Some(checkpoint) = checkpoint {
+        command.env(CHECKPOINT_ENV, checkpoint.to_string());
+    }
+    let mut child = command
+        .spawn()
+        .map_err(|e| format!("can't run {}: {}", python(), e))?;
+
+    let stdout = child.stdout.take().map(|stdout| {
+        let control = control.clone();
+        capture_lines(log.clone(), "stdout", stdout, move |line| {
+            handle_line(&control, line)
+        })
+    });
+    if let Some(stderr) = child.stderr.take() {
+        capture(log, "stderr", stderr);
+    }
+
+    let deadline = Instant::now() + timeout;
+    let result = loop {
+        thread::sleep(POLL_INTERVAL);
+
+        match child.try_wait() {
+            Ok(Some(status)) if status.success() => break Ok(()),
+            Ok(Some(status)) => {
+                break Err(format!(
+                    "{} exited with {}, see logs/{}.log",
+                    script, status, log_name
+                ))
+            }
+            Ok(None) => {}
+            Err(e) => break Err(e.to_string()),
+        }
+
+        let stop = control.stopping().is_some();
+        if stop || Instant::now() >= deadline {
+            let _ = child.kill();
+            let _ = child.wait();
+            if stop {
+                break Ok(());
+            }
+            break Err(format!(
+                "timed out after {} minutes",
+                timeout.as_secs() / 60
+            ));
+        }
+    };
+
+    // The last lines may still hold a checkpoint
+    if let Some(stdout) = stdout {
+        let _ = stdout.join();
+    }
+    result
+}
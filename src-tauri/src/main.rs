@@ -3,100 +3,25 @@
     windows_subsystem = "windows"
 )]
 
-use std::process::{Command, Stdio};
-use std::sync::{Arc, Mutex};
-use std::thread;
-use tauri::{CustomMenuItem, Menu, Submenu};
+use tauri::{CustomMenuItem, Manager, Menu, Submenu};
 use tokio::signal;
-use util::{config_path, db_formatted_path};
 use std::fs::File;
 use std::io::Read;
 
 
 mod apis;
 mod chroma;
+mod embed;
+mod files;
 mod invokes;
+mod jobs;
+mod search;
+mod sidecar;
+mod startup;
+mod store;
+mod sync;
 mod util;
 
-fn start_chroma_server(command: &str) -> Arc<Mutex<std::process::Child>> {
-    let chrdb = if cfg!(target_os = "windows") {
-        Arc::new(Mutex::new(
-            Command::new("cmd")
-                .args(&["/C", command]) // Use "/C" for cmd.exe to run a command
-                .stdout(Stdio::inherit()) // Inherit standard output for logging
-                .stderr(Stdio::inherit()) // Inherit standard error for logging
-                .spawn()
-                .expect("Failed to start Chroma server on Windows"),
-        ))
-    } else {
-        Arc::new(Mutex::new(
-            Command::new("sh")
-                .arg("-c")
-                .arg(command)
-                .stdout(Stdio::inherit()) // Inherit standard output for logging
-                .stderr(Stdio::inherit()) // Inherit standard error for logging
-                .spawn()
-                .expect("Failed to start Chroma server on Unix-like system"),
-        ))
-    };
-
-    let _ = Arc::clone(&chrdb);
-    chrdb
-}
-
-fn start_chroma_db() {
-    chroma::run_python_sdk(
-        &db_formatted_path().as_str(),
-        &chroma::Action::GetOrCreate {
-            collection_name: "siftfiles".to_string(),
-        },
-        false,
-    )
-    .expect("Failed to create Chroma database collection");
-}
-
-fn start_chroma_query_agent() -> Arc<Mutex<std::process::Child>> {
-    let chrqr = Arc::new(Mutex::new(
-        Command::new("uvicorn")
-            .arg("src.queryapi:app")
-            .arg("--host")
-            .arg("127.0.0.1")
-            .arg("--port")
-            .arg("35443")
-            .stdout(Stdio::inherit()) // Inherit standard output for logging
-            .stderr(Stdio::inherit()) // Inherit standard error for logging
-            .spawn()
-            .expect("Failed to start Chroma server on Windows"),
-    ));
-
-    let _ = Arc::clone(&chrqr);
-    chrqr
-}
-
-fn init_local_files() -> Arc<Mutex<std::process::Child>> {
-    let proc = Arc::new(Mutex::new(
-        Command::new("python3")
-            .arg("./pybindings/init_local.py")
-            .spawn()
-            .expect("Failed to startup local files"),
-    ));
-
-    let _ = Arc::clone(&proc);
-    proc
-}
-
-fn init_gh_files() -> Arc<Mutex<std::process::Child>> {
-    let proc = Arc::new(Mutex::new(
-        Command::new("python3")
-            .arg("./pybindings/init_gh.py")
-            .spawn()
-            .expect("Failed to startup local files"),
-    ));
-
-    let _ = Arc::clone(&proc);
-    proc
-}
-
 #[tauri::command]
 fn run_subprocess(command: String) -> Result<String, String> {
     invokes::run_cmd(command)
@@ -115,6 +40,253 @@ fn read_pdf_file(file_path: String) -> Result<Vec<u8>, String> {
     Ok(buffer)
 }
 
+/// Starts a full index of the configured roots as a job, progress comes as
+/// `jobs://progress`
+#[tauri::command]
+fn index_local() -> Result<jobs::Job, String> {
+    jobs::JobManager::shared().start(jobs::JobKind::LocalIndex)
+}
+
+/// Resolves a document or chunk id from a search result back to its file
+#[tauri::command]
+fn locate_document(id: String) -> Result<String, String> {
+    let manifest = files::Manifest::load().map_err(|e| e.to_string())?;
+
+    match manifest.find(&id) {
+        Some((path, _)) if std::path::Path::new(path).exists() => Ok(path.clone()),
+        Some((path, _)) => Err(format!("{} no longer exists", path)),
+        None => Err(format!("No indexed document with id {}", id)),
+    }
+}
+
+#[tauri::command]
+fn get_index_config() -> Result<util::IndexingConfig, String> {
+    util::load_config()
+        .map(|cfg| cfg.indexing)
+        .map_err(|e| e.to_string())
+}
+
+/// The watcher switches to the new rules right away, files already indexed
+/// are brought in line on the next `index_local` run
+#[tauri::command]
+fn set_index_config(indexing: util::IndexingConfig) -> Result<util::IndexingConfig, String> {
+    // Reject globs that don't compile before they reach the config file
+    let indexer = files::Indexer::from_config(&indexing).map_err(|e| e.to_string())?;
+
+    let mut cfg = util::load_config().map_err(|e| e.to_string())?;
+    cfg.indexing = indexing;
+    util::write_config(cfg.clone()).map_err(|e| e.to_string())?;
+    files::reload_watcher(indexer);
+
+    Ok(cfg.indexing)
+}
+
+// The SDK runs as a Python subprocess, keep it off the async runtime
+async fn run_blocking<T, F>(task: F) -> Result<T, String>
+where
+    T: Send + 'static,
+    F: FnOnce() -> Result<T, Box<dyn std::error::Error>> + Send + 'static,
+{
+    tauri::async_runtime::spawn_blocking(move || task().map_err(|e| e.to_string()))
+        .await
+        .map_err(|e| e.to_string())?
+}
+
+// Texts are embedded natively unless the config still uses OpenCLIP
+#[tauri::command]
+async fn chroma_query(request: chroma::QueryRequest) -> Result<chroma::QueryResult, String> {
+    run_blocking(move || match embed::shared()? {
+        Some(embedder) => {
+            let cache = search::SearchCache::shared();
+            let embeddings = request
+                .query_texts
+                .iter()
+                .map(|text| cache.query_embedding(embedder.as_ref(), text))
+                .collect::<Result<Vec<_>, _>>()?;
+            store::shared().query(
+                &request.collection_name,
+                &embeddings,
+                request.n_results,
+                &request.filter,
+            )
+        }
+        None => chroma::query(request),
+    })
+    .await
+}
+
+#[tauri::command]
+async fn chroma_upsert(request: chroma::UpsertRequest) -> Result<(), String> {
+    run_blocking(move || {
+        let keywords = request.documents.clone().map(|d| (request.ids.clone(), d));
+        match embed::shared()? {
+            Some(embedder) => {
+                let documents = request.documents.ok_or("Upserts need documents to embed")?;
+                let store = store::shared();
+                embed::check_collection(store, &request.collection_name, embedder.as_ref())?;
+                let records = chroma::chrust::EmbeddedRecords {
+                    embeddings: embedder.embed(&documents)?,
+                    ids: request.ids,
+                    documents: Some(documents),
+                    metadatas: request.metadatas,
+                };
+                store.upsert(&request.collection_name, &records)?;
+            }
+            None => chroma::upsert(request.clone())?,
+        }
+
+        if let Some((ids, documents)) = keywords {
+            search::KeywordStore::shared().upsert(&request.collection_name, &ids, &documents)?;
+        }
+        search::SearchCache::shared().bump_generation();
+        Ok(())
+    })
+    .await
+}
+
+#[tauri::command]
+async fn search(request: search::SearchRequest) -> Result<Vec<search::SearchHit>, String> {
+    run_blocking(move || search::search(&request)).await
+}
+
+#[tauri::command]
+async fn search_documents(
+    request: search::SearchRequest,
+) -> Result<Vec<search::DocumentHit>, String> {
+    run_blocking(move || search::search_documents(&request)).await
+}
+
+#[tauri::command]
+async fn search_page(request: search::SearchRequest) -> Result<search::Page, String> {
+    run_blocking(move || search::first_page(&request)).await
+}
+
+#[tauri::command]
+async fn search_next_page(cursor: String, size: usize) -> Result<search::Page, String> {
+    run_blocking(move || Ok(search::next_page(&cursor, size)?)).await
+}
+
+#[tauri::command]
+async fn store_get(request: chroma::GetRequest) -> Result<chroma::GetResult, String> {
+    run_blocking(move || store::shared().get(&request)).await
+}
+
+// The keyword index only knows ids, so filters are resolved against the store first
+#[tauri::command]
+async fn store_delete(request: chroma::DeleteRequest) -> Result<(), String> {
+    run_blocking(move || {
+        // A get with neither would match, and then delete, every record
+        let filter = store::filter::CompiledFilter::compile(&request.filter)?;
+        if request.ids.is_none() && filter.is_empty() {
+            return Err(Box::from("Delete needs ids or a filter"));
+        }
+
+        let store = store::shared();
+        let ids = store
+            .get(&chroma::GetRequest {
+                collection_name: request.collection_name.clone(),
+                ids: request.ids.clone(),
+                filter: request.filter.clone(),
+                limit: None,
+                offset: None,
+                include: Some(Vec::new()),
+            })?
+            .ids;
+
+        store.delete(&request)?;
+        search::KeywordStore::shared().delete(&request.collection_name, &ids)?;
+        search::SearchCache::shared().bump_generation();
+        Ok(())
+    })
+    .await
+}
+
+#[tauri::command]
+async fn store_count(collection_name: String) -> Result<usize, String> {
+    run_blocking(move || store::shared().count(&collection_name)).await
+}
+
+#[tauri::command]
+async fn store_peek(collection_name: String, limit: usize) -> Result<chroma::GetResult, String> {
+    run_blocking(move || store::shared().peek(&collection_name, limit)).await
+}
+
+#[tauri::command]
+async fn store_list_collections() -> Result<Vec<chroma::CollectionInfo>, String> {
+    run_blocking(|| store::shared().list_collections()).await
+}
+
+#[tauri::command]
+async fn store_delete_collection(collection_name: String) -> Result<(), String> {
+    run_blocking(move || {
+        store::shared().delete_collection(&collection_name)?;
+        search::KeywordStore::shared().delete_collection(&collection_name)?;
+        search::SearchCache::shared().bump_generation();
+        Ok(())
+    })
+    .await
+}
+
+#[tauri::command]
+fn service_ports() -> Vec<util::ports::ServiceEndpoint> {
+    util::PortRegistry::shared().endpoints()
+}
+
+#[tauri::command]
+fn sync_status() -> Result<Vec<sync::SourceStatus>, String> {
+    sync::Scheduler::shared()
+        .statuses()
+        .map_err(|e| e.to_string())
+}
+
+/// Starts a sync of `source` outside its schedule
+#[tauri::command]
+fn sync_now(source: util::SyncSource) -> Result<jobs::Job, String> {
+    sync::Scheduler::shared().sync_now(source)
+}
+
+/// Running jobs and the last finished ones, newest first
+#[tauri::command]
+fn list_jobs() -> Vec<jobs::Job> {
+    jobs::JobManager::shared().list()
+}
+
+#[tauri::command]
+fn cancel_job(id: String) -> Result<jobs::Job, String> {
+    jobs::JobManager::shared().cancel(&id)
+}
+
+#[tauri::command]
+fn pause_job(id: String) -> Result<jobs::Job, String> {
+    jobs::JobManager::shared().pause(&id)
+}
+
+/// Continues a paused or failed job from its last checkpoint
+#[tauri::command]
+fn resume_job(id: String) -> Result<jobs::Job, String> {
+    jobs::JobManager::shared().resume(&id)
+}
+
+fn run_startup(app_handle: tauri::AppHandle) -> bool {
+    startup::Startup::shared().run(move |report| {
+        let _ = app_handle.emit_all("startup://progress", report.clone());
+    })
+}
+
+#[tauri::command]
+fn startup_status() -> startup::StartupReport {
+    startup::Startup::shared().report()
+}
+
+/// Runs the stages that failed again, progress comes as `startup://progress`
+#[tauri::command]
+fn retry_startup(app_handle: tauri::AppHandle) -> startup::StartupReport {
+    if !run_startup(app_handle) {
+        println!("STARTUP:INFO: Startup is already running");
+    }
+    startup::Startup::shared().report()
+}
+
 #[tauri::command]
 async fn gh_oauth() -> Result<String, String> {
     invokes::github_oauth().await
@@ -145,63 +317,63 @@ async fn ggl_oauth() -> Result<String, String> {
     invokes::google_oauth().await
 }
 
+// Stops the child processes, they'd outlive the app otherwise
+fn shutdown() {
+    sync::Scheduler::shared().shutdown();
+    jobs::JobManager::shared().shutdown();
+    sidecar::Supervisor::shared().shutdown();
+}
+
 fn main() {
     let quit = CustomMenuItem::new("quit".to_string(), "Quit");
     let submenu = Submenu::new("File", Menu::new().add_item(quit));
 
-    let app_cfg = util::load_config().unwrap();
-    println!("\nBeginning Sift.AI Startup...\n");
-
-    let command = "chroma run --path /Users/ashwa/Desktop/sift_datastore --port 35436".to_string();
-    let _ = start_chroma_server(&command);
-
-    println!("Chroma server is running in the background on http://localhost:35436.\n");
-    println!("AppConfig: {:?}", app_cfg);
-
-    start_chroma_db();
-    println!("Chroma database is configured.\n");
-
-    println!("Instantiating File Cache\n");
+    // Pick every port up front so the log shows where everything ended up
+    for endpoint in util::PortRegistry::shared().endpoints() {
+        println!("{:?} on {}", endpoint.service, endpoint.url);
+    }
 
-    // let local_handle = thread::spawn(|| {
-    //     init_local_files();
-    //     println!("Local files are initialized.\n");
-    // });
-
-    // let gh_handle = thread::spawn(|| {
-    //     init_gh_files();
-    //     println!("GitHub files are initialized.\n");
-    // });
+    tauri::Builder::default()
+        .setup(|app| {
+            let app_handle = app.handle();
 
-    // // Join the threads to ensure both complete
-    // local_handle.join().expect("Failed to initialize local files");
-    // gh_handle.join().expect("Failed to initialize GitHub files");
+            // The Python scripts are bundled as resources, find them once
+            // rather than relying on the working directory
+            match app.path_resolver().resolve_resource("pybindings") {
+                Some(dir) => util::set_pybindings_dir(dir),
+                None => eprintln!("STARTUP:WARN: Couldn't resolve the pybindings resource dir"),
+            }
 
-    println!("Both tasks are completed.");
+            // Keep the index in sync with edits made after startup
+            let watch_handle = app.handle();
+            if let Err(e) = files::spawn_watcher(files::Indexer::configured(), move |summary| {
+                let _ = watch_handle.emit_all("index://updated", summary.clone());
+            }) {
+                eprintln!("Failed to start file watcher: {}", e);
+            }
 
-    let _ = start_chroma_query_agent();
-    println!("Running Chroma Query Agent on http://localhost:35443...\n");
+            // Indexing and syncs report through the job manager
+            let jobs_handle = app.handle();
+            jobs::JobManager::shared().on_change(move |job| {
+                let _ = jobs_handle.emit_all("jobs://progress", job.clone());
+            });
 
-    println!("\nCompleted Startup Configurations\n");
+            // Config, Chroma and the servers come up in the background, the
+            // window opens right away and follows along
+            run_startup(app.handle());
 
-    tauri::Builder::default()
-        .setup(|app| {
-            let app_handle = app.handle();
+            // Connector syncs on their schedules, see `sync` in the config
+            let sync_handle = app.handle();
+            sync::Scheduler::shared().spawn(move |record| {
+                let _ = sync_handle.emit_all("sync://finished", record.clone());
+            });
 
-            // Spawn the background server and handle Ctrl+C signal
+            // Handle Ctrl+C for graceful shutdown
             tauri::async_runtime::spawn(async move {
-                let server_handle = tokio::spawn(apis::fileserv::serve());
-
-                // Handle Ctrl+C for graceful shutdown
-                tokio::spawn(async move {
-                    if signal::ctrl_c().await.is_ok() {
-                        println!("Ctrl+C detected, shutting down...");
-                        app_handle.exit(0);
-                    }
-                });
-
-                if let Err(e) = server_handle.await {
-                    eprintln!("Server error: {}", e);
+                if signal::ctrl_c().await.is_ok() {
+                    println!("Ctrl+C detected, shutting down...");
+                    shutdown();
+                    app_handle.exit(0);
                 }
             });
 
@@ -216,20 +388,47 @@ fn main() {
             disc_oauth,
             ggl_oauth,
             end_app,
-            read_pdf_file
+            read_pdf_file,
+            index_local,
+            locate_document,
+            get_index_config,
+            set_index_config,
+            chroma_query,
+            chroma_upsert,
+            search,
+            search_documents,
+            search_page,
+            search_next_page,
+            store_get,
+            store_delete,
+            store_count,
+            store_peek,
+            store_list_collections,
+            store_delete_collection,
+            service_ports,
+            startup_status,
+            retry_startup,
+            sync_status,
+            sync_now,
+            list_jobs,
+            cancel_job,
+            pause_job,
+            resume_job
         ])
         .menu(Menu::new().add_submenu(submenu))
         .on_window_event(move |event| {
             if let tauri::WindowEvent::CloseRequested { .. } = event.event() {
                 println!("Closing Chroma server...");
-                // if let Ok(mut chroma) = chroma_clone.lock() {
-                //     if let Err(e) = chroma.kill() {
-                //         eprintln!("Failed to kill Chroma server: {}", e);
-                //     }
-                // }
+                shutdown();
                 println!("Goodbye from Sift.AI");
             }
         })
-        .run(tauri::generate_context!())
-        .expect("error while running tauri application");
+        .build(tauri::generate_context!())
+        .expect("error while running tauri application")
+        .run(|_, event| {
+            // Quitting from the menu or `app.exit` skips the window events
+            if let tauri::RunEvent::Exit = event {
+                shutdown();
+            }
+        });
 }
@@ -0,0 +1,321 @@
+use lru_cache::LruCache;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::error::Error;
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+
+use crate::embed::Embedder;
+use crate::search::hybrid::SearchHit;
+use crate::util::{db_path, load_config, SearchCacheConfig};
+
+// The log is rewritten once it holds this many more lines than entries
+const COMPACT_SLACK: usize = 1_000;
+
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+enum LogEntry {
+    Embedding {
+        key: String,
+        vector: Vec<f32>,
+    },
+    Results {
+        key: String,
+        generation: u64,
+        hits: Vec<SearchHit>,
+        saturated: bool,
+    },
+    Generation {
+        value: u64,
+    },
+}
+
+/// Ranked hits of a query, before snippets are added
+#[derive(Debug, Clone)]
+pub struct CachedResults {
+    pub hits: Vec<SearchHit>,
+    pub saturated: bool,
+}
+
+struct Log {
+    path: PathBuf,
+    lines: usize,
+}
+
+/// Query embeddings and ranked results, least recently used out first.
+/// Result keys include the index generation, which every write bumps, so
+/// results computed before a write are never served after it.
+pub struct SearchCache {
+    embeddings: Mutex<LruCache<String, Arc<Vec<f32>>>>,
+    results: Mutex<LruCache<String, Arc<CachedResults>>>,
+    generation: AtomicU64,
+    log: Option<Mutex<Log>>,
+}
+
+/// Trims and collapses runs of whitespace. Case is kept, embedders are case
+/// sensitive.
+pub fn normalize_query(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// JSON with object keys sorted, so equal filters make equal keys whatever
+/// order they were written in
+pub fn canonical_json(value: &Value) -> String {
+    match value {
+        Value::Object(map) => {
+            let mut keys: Vec<&String> = map.keys().collect();
+            keys.sort();
+            let fields: Vec<String> = keys
+                .into_iter()
+                .map(|k| format!("{}:{}", Value::from(k.as_str()), canonical_json(&map[k])))
+                .collect();
+            format!("{{{}}}", fields.join(","))
+        }
+        Value::Array(items) => {
+            let items: Vec<String> = items.iter().map(canonical_json).collect();
+            format!("[{}]", items.join(","))
+        }
+        _ => value.to_string(),
+    }
+}
+
+/// Hashes the parts of a cache key into a fixed size key
+pub fn cache_key(parts: &[&str]) -> String {
+    let mut hasher = Sha256::new();
+    for part in parts {
+        hasher.update(part.as_bytes());
+        hasher.update(b"\0");
+    }
+    format!("{:x}", hasher.finalize())
+}
+
+impl SearchCache {
+    /// `path` is where the cache is persisted, `None` keeps it in memory
+    pub fn new(capacity: usize, path: Option<PathBuf>) -> SearchCache {
+        let cache = SearchCache {
+            embeddings: Mutex::new(LruCache::new(capacity)),
+            results: Mutex::new(LruCache::new(capacity)),
+            generation: AtomicU64::new(0),
+            log: None,
+        };
+
+        match path {
+            Some(path) if capacity > 0 => cache.with_log(path),
+            _ => cache,
+        }
+    }
+
+    /// Process-wide cache, sized by the `search_cache` config
+    pub fn shared() -> &'static SearchCache {
+        static CACHE: OnceLock<SearchCache> = OnceLock::new();
+        CACHE.get_or_init(|| {
+            let config = load_config()
+                .map(|cfg| cfg.search_cache)
+                .unwrap_or_default();
+            SearchCache::from_config(&config)
+        })
+    }
+
+    pub fn from_config(config: &SearchCacheConfig) -> SearchCache {
+        let path = config
+            .persist
+            .then(|| db_path().join("cache").join("search.jsonl"));
+        SearchCache::new(config.capacity, path)
+    }
+
+    // Replays the log, results from an older generation are left out
+    fn with_log(mut self, path: PathBuf) -> SearchCache {
+        let mut lines = 0;
+        let mut results = Vec::new();
+
+        if path.exists() {
+            let file = match File::open(&path) {
+                Ok(file) => file,
+                Err(e) => {
+                    eprintln!("CACHE:WARN: Can't read {}: {}", path.display(), e);
+                    return self;
+                }
+            };
+            for line in BufReader::new(file).lines().map_while(Result::ok) {
+                lines += 1;
+                match serde_json::from_str(&line) {
+                    Ok(LogEntry::Embedding { key, vector }) => {
+                        self.embeddings
+                            .get_mut()
+                            .unwrap()
+                            .insert(key, Arc::new(vector));
+                    }
+                    Ok(LogEntry::Generation { value }) => {
+                        *self.generation.get_mut() = value;
+                    }
+                    Ok(LogEntry::Results {
+                        key,
+                        generation,
+                        hits,
+                        saturated,
+                    }) => results.push((key, generation, CachedResults { hits, saturated })),
+                    Err(e) => {
+                        eprintln!("CACHE:WARN: Skipping bad line in {}: {}", path.display(), e)
+                    }
+                }
+            }
+        }
+
+        let generation = *self.generation.get_mut();
+        for (key, _, cached) in results.into_iter().filter(|(_, g, _)| *g == generation) {
+            self.results
+                .get_mut()
+                .unwrap()
+                .insert(key, Arc::new(cached));
+        }
+
+        self.log = Some(Mutex::new(Log { path, lines }));
+        if let Err(e) = self.compact_if_needed() {
+            eprintln!("CACHE:WARN: Failed to compact the search cache: {}", e);
+        }
+        self
+    }
+
+    pub fn generation(&self) -> u64 {
+        self.generation.load(Ordering::SeqCst)
+    }
+
+    /// Called after every write to a collection, cached results are stale
+    /// from then on
+    pub fn bump_generation(&self) {
+        let value = self.generation.fetch_add(1, Ordering::SeqCst) + 1;
+        self.results.lock().unwrap().clear();
+        self.append(&LogEntry::Generation { value });
+    }
+
+    /// The embedding of `text`, computed on a miss
+    pub fn query_embedding(
+        &self,
+        embedder: &dyn Embedder,
+        text: &str,
+    ) -> Result<Vec<f32>, Box<dyn Error>> {
+        let text = normalize_query(text);
+        let key = cache_key(&[embedder.model(), &text]);
+
+        if let Some(vector) = self.embeddings.lock().unwrap().get_mut(&key) {
+            return Ok(vector.to_vec());
+        }
+
+        let vector = embedder
+            .embed(&[text])?
+            .into_iter()
+            .next()
+            .ok_or("The embedder returned no vector")?;
+        self.embeddings
+            .lock()
+            .unwrap()
+            .insert(key.clone(), Arc::new(vector.clone()));
+        self.append(&LogEntry::Embedding {
+            key,
+            vector: vector.clone(),
+        });
+        Ok(vector)
+    }
+
+    pub fn results(&self, key: &str) -> Option<Arc<CachedResults>> {
+        self.results.lock().unwrap().get_mut(key).cloned()
+    }
+
+    /// Caches results ranked at `generation`, ignored when the index has
+    /// moved on since
+    pub fn store_results(&self, key: String, generation: u64, cached: CachedResults) {
+        if generation != self.generation() {
+            return;
+        }
+
+        let entry = LogEntry::Results {
+            key: key.clone(),
+            generation,
+            hits: cached.hits.clone(),
+            saturated: cached.saturated,
+        };
+        self.results.lock().unwrap().insert(key, Arc::new(cached));
+        self.append(&entry);
+    }
+
+    fn append(&self, entry: &LogEntry) {
+        let log = match &self.log {
+            Some(log) => log,
+            None => return,
+        };
+
+        let written = (|| -> Result<(), Box<dyn Error>> {
+            let mut log = log.lock().unwrap();
+            if let Some(parent) = log.path.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            let file = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(&log.path)?;
+            let mut out = BufWriter::new(file);
+            serde_json::to_writer(&mut out, entry)?;
+            out.write_all(b"\n")?;
+            out.flush()?;
+            log.lines += 1;
+            Ok(())
+        })();
+
+        if let Err(e) = written.and_then(|_| self.compact_if_needed()) {
+            eprintln!("CACHE:WARN: Failed to persist the search cache: {}", e);
+        }
+    }
+
+    // Rewrites the log with the live entries once it has grown well past them
+    fn compact_if_needed(&self) -> Result<(), Box<dyn Error>> {
+        let log = match &self.log {
+            Some(log) => log,
+            None => return Ok(()),
+        };
+        let mut log = log.lock().unwrap();
+
+        let embeddings = self.embeddings.lock().unwrap();
+        let results = self.results.lock().unwrap();
+        if log.lines <= embeddings.len() + results.len() + COMPACT_SLACK {
+            return Ok(());
+        }
+
+        let generation = self.generation();
+        let tmp_path = log.path.with_extension("jsonl.tmp");
+        let mut out = BufWriter::new(File::create(&tmp_path)?);
+        let mut lines = 1;
+
+        serde_json::to_writer(&mut out, &LogEntry::Generation { value: generation })?;
+        out.write_all(b"\n")?;
+        // Least recently used first, so replaying keeps the order
+        for (key, vector) in embeddings.iter() {
+            let entry = LogEntry::Embedding {
+                key: key.clone(),
+                vector: vector.to_vec(),
+            };
+            serde_json::to_writer(&mut out, &entry)?;
+            out.write_all(b"\n")?;
+            lines += 1;
+        }
+        for (key, cached) in results.iter() {
+            let entry = LogEntry::Results {
+                key: key.clone(),
+                generation,
+                hits: cached.hits.clone(),
+                saturated: cached.saturated,
+            };
+            serde_json::to_writer(&mut out, &entry)?;
+            out.write_all(b"\n")?;
+            lines += 1;
+        }
+
+        out.into_inner().map_err(|e| e.into_error())?.sync_all()?;
+        std::fs::rename(tmp_path, &log.path)?;
+        log.lines = lines;
+        Ok(())
+    }
+}
@@ -0,0 +1,90 @@
+use serde::Serialize;
+use std::collections::HashMap;
+
+use crate::chroma::Metadata;
+use crate::search::hybrid::SearchHit;
+use crate::util::ids::parse_chunk_id;
+
+// Metadata that describes a single chunk rather than the whole file
+const CHUNK_KEYS: &[&str] = &[
+    "chunk_index",
+    "start_byte",
+    "end_byte",
+    "start_line",
+    "end_line",
+    "section_kind",
+    "section_label",
+    "section_title",
+    "symbol",
+    "symbol_kind",
+];
+
+/// A file along with its best matching chunks
+#[derive(Serialize, Debug, Clone)]
+pub struct DocumentHit {
+    /// Document id, the chunk ids without their `:{index}` suffix
+    pub id: String,
+    /// The chunk scores with each further chunk counting half as much as the
+    /// one before it, so the best chunk dominates and repeated matches only
+    /// break ties
+    pub score: f32,
+    /// File level metadata, taken from the best chunk
+    pub metadata: Option<Metadata>,
+    /// How many of the document's chunks matched
+    pub matched_chunks: usize,
+    /// The best chunks, best first
+    pub passages: Vec<SearchHit>,
+}
+
+fn document_metadata(metadata: Option<&Metadata>) -> Option<Metadata> {
+    let mut metadata = metadata?.clone();
+    for key in CHUNK_KEYS {
+        metadata.remove(*key);
+    }
+    Some(metadata)
+}
+
+/// Collapses ranked chunk hits into their documents, best first, keeping at
+/// most `passages` chunks per document. Records that aren't chunks (images)
+/// make up a document of their own.
+pub fn group_hits(hits: Vec<SearchHit>, passages: usize) -> Vec<DocumentHit> {
+    let mut order: Vec<String> = Vec::new();
+    let mut groups: HashMap<String, Vec<SearchHit>> = HashMap::new();
+
+    // `hits` is sorted, so each group is too
+    for hit in hits {
+        let id = parse_chunk_id(&hit.id).0.to_string();
+        groups
+            .entry(id.clone())
+            .or_insert_with(|| {
+                order.push(id);
+                Vec::new()
+            })
+            .push(hit);
+    }
+
+    let mut documents: Vec<DocumentHit> = order
+        .into_iter()
+        .filter_map(|id| {
+            let mut chunks = groups.remove(&id)?;
+            let score = chunks
+                .iter()
+                .enumerate()
+                .map(|(i, hit)| hit.score / 2f32.powi(i as i32))
+                .sum();
+            let matched_chunks = chunks.len();
+            chunks.truncate(passages.max(1));
+
+            Some(DocumentHit {
+                metadata: document_metadata(chunks[0].metadata.as_ref()),
+                id,
+                score,
+                matched_chunks,
+                passages: chunks,
+            })
+        })
+        .collect();
+
+    documents.sort_by(|a, b| b.score.total_cmp(&a.score).then(a.id.cmp(&b.id)));
+    documents
+}
@@ -0,0 +1,485 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::error::Error;
+
+use crate::chroma::{self, Filter, GetRequest, Metadata, QueryRequest};
+use crate::embed;
+use crate::search::cache::{
+    cache_key, canonical_json, normalize_query, CachedResults, SearchCache,
+};
+use crate::search::group::{group_hits, DocumentHit};
+use crate::search::keyword::KeywordStore;
+use crate::search::query::{merge_filters, parse_query};
+use crate::search::snippet::{snippet, Snippet};
+use crate::store;
+
+// Damping constant from the original reciprocal-rank fusion paper
+const RRF_K: f32 = 60.0;
+
+// Each ranking contributes this many candidates per requested result, so
+// hits that only one signal ranks highly still get fused
+const CANDIDATE_FACTOR: usize = 4;
+const MAX_CANDIDATES: usize = 200;
+
+// Document searches fetch this many more chunks per wanted result
+const CHUNKS_PER_DOCUMENT: usize = 2;
+const MAX_DOCUMENT_CANDIDATES: usize = 500;
+
+fn default_n_results() -> usize {
+    10
+}
+
+fn default_passages() -> usize {
+    3
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum SearchMode {
+    /// Embedding similarity only
+    Semantic,
+    /// BM25 over the keyword index only
+    Keyword,
+    /// Both rankings combined with reciprocal-rank fusion
+    #[default]
+    Hybrid,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SearchRequest {
+    pub collection_name: String,
+    /// Free text with optional field filters, see `parse_query`
+    pub query: String,
+    #[serde(default = "default_n_results")]
+    pub n_results: usize,
+    /// Results to skip, for paging
+    #[serde(default)]
+    pub offset: usize,
+    /// Chunks kept per document by `search_documents`
+    #[serde(default = "default_passages")]
+    pub passages: usize,
+    #[serde(flatten)]
+    pub filter: Filter,
+    #[serde(default)]
+    pub mode: SearchMode,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SemanticSignal {
+    /// 1-based position in the semantic ranking
+    pub rank: usize,
+    pub distance: f32,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct KeywordSignal {
+    /// 1-based position in the keyword ranking
+    pub rank: usize,
+    /// BM25 score
+    pub score: f32,
+    pub matched: Vec<String>,
+}
+
+/// A ranked record along with the signals that put it there, `None` for
+/// a signal that didn't rank it
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SearchHit {
+    pub id: String,
+    /// Sum of `1 / (60 + rank)` over the signals, higher is better
+    pub score: f32,
+    pub document: Option<String>,
+    pub metadata: Option<Metadata>,
+    pub semantic: Option<SemanticSignal>,
+    pub keyword: Option<KeywordSignal>,
+    /// Best matching passage of `document`
+    pub snippet: Option<Snippet>,
+}
+
+impl SearchHit {
+    fn new(id: String, document: Option<String>, metadata: Option<Metadata>) -> SearchHit {
+        SearchHit {
+            id,
+            score: 0.0,
+            document,
+            metadata,
+            semantic: None,
+            keyword: None,
+            snippet: None,
+        }
+    }
+}
+
+fn rrf(rank: usize) -> f32 {
+    1.0 / (RRF_K + rank as f32)
+}
+
+struct Ranked {
+    text: String,
+    hits: Vec<SearchHit>,
+    saturated: bool,
+}
+
+pub struct RankedDocuments {
+    /// Free text of the parsed query, for snippets
+    pub text: String,
+    pub documents: Vec<DocumentHit>,
+    pub saturated: bool,
+}
+
+// What a single ranking needs to know, with the query already parsed
+struct Ranking<'a> {
+    collection: &'a str,
+    text: &'a str,
+    filter: &'a Filter,
+    depth: usize,
+}
+
+fn semantic_hits(ranking: &Ranking) -> Result<Vec<SearchHit>, Box<dyn Error>> {
+    let result = match embed::shared()? {
+        Some(embedder) => store::shared().query(
+            ranking.collection,
+            &[SearchCache::shared().query_embedding(embedder.as_ref(), ranking.text)?],
+            ranking.depth,
+            ranking.filter,
+        )?,
+        None => chroma::query(QueryRequest {
+            collection_name: ranking.collection.to_string(),
+            query_texts: vec![ranking.text.to_string()],
+            n_results: ranking.depth,
+            filter: ranking.filter.clone(),
+            include: None,
+        })?,
+    };
+
+    let ids = result.ids.into_iter().next().unwrap_or_default();
+    let mut documents = result.documents.and_then(|d| d.into_iter().next());
+    let mut metadatas = result.metadatas.and_then(|m| m.into_iter().next());
+    let distances = result.distances.and_then(|d| d.into_iter().next());
+
+    Ok(ids
+        .into_iter()
+        .enumerate()
+        .map(|(i, id)| {
+            let document = documents.as_mut().and_then(|d| d.get_mut(i)?.take());
+            let metadata = metadatas.as_mut().and_then(|m| m.get_mut(i)?.take());
+            let mut hit = SearchHit::new(id, document, metadata);
+            hit.semantic = Some(SemanticSignal {
+                rank: i + 1,
+                distance: distances
+                    .as_ref()
+                    .and_then(|d| d.get(i).copied())
+                    .unwrap_or(f32::NAN),
+            });
+            hit
+        })
+        .collect())
+}
+
+// Matches are looked up in the vector store, which applies the filter and
+// drops ids that were deleted since they were indexed
+fn keyword_hits(ranking: &Ranking) -> Result<Vec<SearchHit>, Box<dyn Error>> {
+    let matches = KeywordStore::shared().search(ranking.collection, ranking.text, ranking.depth)?;
+    if matches.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let stored = store::shared().get(&GetRequest {
+        collection_name: ranking.collection.to_string(),
+        ids: Some(matches.iter().map(|m| m.id.clone()).collect()),
+        filter: ranking.filter.clone(),
+        limit: None,
+        offset: None,
+        include: None,
+    })?;
+
+    let mut records: HashMap<String, (Option<String>, Option<Metadata>)> = HashMap::new();
+    for (i, id) in stored.ids.into_iter().enumerate() {
+        let document = stored.documents.as_ref().and_then(|d| d.get(i).cloned()?);
+        let metadata = stored.metadatas.as_ref().and_then(|m| m.get(i).cloned()?);
+        records.insert(id, (document, metadata));
+    }
+
+    Ok(matches
+        .into_iter()
+        .filter_map(|m| {
+            let (document, metadata) = records.remove(&m.id)?;
+            Some((m, document, metadata))
+        })
+        .enumerate()
+        .map(|(i, (m, document, metadata))| {
+            let mut hit = SearchHit::new(m.id, document, metadata);
+            hit.keyword = Some(KeywordSignal {
+                rank: i + 1,
+                score: m.score,
+                matched: m.matched,
+            });
+            hit
+        })
+        .collect())
+}
+
+// A query made only of filters has nothing to rank by, so it lists matches
+fn filtered_hits(ranking: &Ranking) -> Result<Vec<SearchHit>, Box<dyn Error>> {
+    let stored = store::shared().get(&GetRequest {
+        collection_name: ranking.collection.to_string(),
+        ids: None,
+        filter: ranking.filter.clone(),
+        limit: Some(ranking.depth),
+        offset: None,
+        include: None,
+    })?;
+
+    let mut documents = stored.documents.unwrap_or_default().into_iter();
+    let mut metadatas = stored.metadatas.unwrap_or_default().into_iter();
+    Ok(stored
+        .ids
+        .into_iter()
+        .map(|id| SearchHit::new(id, documents.next().flatten(), metadatas.next().flatten()))
+        .collect())
+}
+
+// Parses `request.query`, runs the rankings `request.mode` asks for and
+// fuses them, best first. `depth` candidates come from each ranking, fewer
+// make it out when `path:` rules drop some.
+fn ranked_hits(
+    request: &SearchRequest,
+    depth: impl Fn(bool) -> usize,
+) -> Result<Ranked, Box<dyn Error>> {
+    let parsed = parse_query(&request.query)?;
+    let filter = merge_filters(&request.filter, &parsed.filter);
+    if parsed.text.is_empty()
+        && filter.where_metadata.is_none()
+        && filter.where_document.is_none()
+        && parsed.paths.is_empty()
+    {
+        return Err(Box::from("Empty search query"));
+    }
+
+    let ranking = Ranking {
+        collection: &request.collection_name,
+        text: &parsed.text,
+        filter: &filter,
+        depth: depth(!parsed.paths.is_empty()),
+    };
+
+    // Read before ranking, a write landing meanwhile makes the results stale
+    let cache = SearchCache::shared();
+    let generation = cache.generation();
+    let model = embed::shared()
+        .ok()
+        .flatten()
+        .map(|e| e.model().to_string())
+        .unwrap_or_else(|| "clip".to_string());
+    let key = cache_key(&[
+        ranking.collection,
+        &format!("{:?}", request.mode),
+        &model,
+        &normalize_query(ranking.text),
+        &canonical_json(&serde_json::to_value(ranking.filter)?),
+        &serde_json::to_string(&parsed.paths)?,
+        &ranking.depth.to_string(),
+        &generation.to_string(),
+    ]);
+    if let Some(cached) = cache.results(&key) {
+        return Ok(Ranked {
+            text: parsed.text,
+            hits: cached.hits.clone(),
+            saturated: cached.saturated,
+        });
+    }
+
+    let (mut hits, saturated) = if parsed.text.is_empty() {
+        let hits = filtered_hits(&ranking)?;
+        let saturated = hits.len() >= ranking.depth;
+        (hits, saturated)
+    } else {
+        let semantic = match request.mode {
+            SearchMode::Keyword => Vec::new(),
+            _ => semantic_hits(&ranking)?,
+        };
+        let keyword = match request.mode {
+            SearchMode::Semantic => Vec::new(),
+            _ => keyword_hits(&ranking)?,
+        };
+        let saturated = semantic.len() >= ranking.depth || keyword.len() >= ranking.depth;
+        (fuse(semantic, keyword), saturated)
+    };
+
+    hits.retain(|hit| parsed.matches_path(hit.metadata.as_ref()));
+    cache.store_results(
+        key,
+        generation,
+        CachedResults {
+            hits: hits.clone(),
+            saturated,
+        },
+    );
+    Ok(Ranked {
+        text: parsed.text,
+        hits,
+        saturated,
+    })
+}
+
+/// Fills in `snippet` for hits that have a document, `text` being the free
+/// text of the parsed query
+pub fn add_snippets(hits: &mut [SearchHit], text: &str) {
+    for hit in hits {
+        hit.snippet = hit
+            .document
+            .as_deref()
+            .and_then(|document| snippet(document, text, hit.metadata.as_ref()));
+    }
+}
+
+/// Chunk level search, best first. Blocking, keep it off the async runtime.
+pub fn search(request: &SearchRequest) -> Result<Vec<SearchHit>, Box<dyn Error>> {
+    let wanted = request.offset + request.n_results;
+    // `path:` is checked after the fact, so fetch as much as allowed
+    let ranked = ranked_hits(request, |post_filtered| {
+        if post_filtered {
+            MAX_CANDIDATES.max(wanted)
+        } else {
+            (wanted * CANDIDATE_FACTOR).clamp(wanted, MAX_CANDIDATES)
+        }
+    })?;
+
+    let mut hits: Vec<SearchHit> = ranked
+        .hits
+        .into_iter()
+        .skip(request.offset)
+        .take(request.n_results)
+        .collect();
+    add_snippets(&mut hits, &ranked.text);
+    Ok(hits)
+}
+
+/// Ranks enough chunks to fill `wanted` documents and groups them, without
+/// snippets. `saturated` tells whether a ranking ran out of depth, in which
+/// case more documents match than were returned.
+pub fn rank_documents(
+    request: &SearchRequest,
+    wanted: usize,
+) -> Result<RankedDocuments, Box<dyn Error>> {
+    // Several chunks of a file tend to rank together, dig deeper so enough
+    // distinct files come out
+    let ranked = ranked_hits(request, |post_filtered| {
+        if post_filtered {
+            MAX_DOCUMENT_CANDIDATES.max(wanted)
+        } else {
+            (wanted * CANDIDATE_FACTOR * CHUNKS_PER_DOCUMENT).clamp(wanted, MAX_DOCUMENT_CANDIDATES)
+        }
+    })?;
+
+    let mut documents = group_hits(ranked.hits, request.passages);
+    let saturated = ranked.saturated || documents.len() > wanted;
+    documents.truncate(wanted);
+    Ok(RankedDocuments {
+        text: ranked.text,
+        documents,
+        saturated,
+    })
+}
+
+/// Document level search: chunks are collapsed into their files, so
+/// `n_results` and `offset` count distinct documents. Blocking as well.
+pub fn search_documents(request: &SearchRequest) -> Result<Vec<DocumentHit>, Box<dyn Error>> {
+    let ranked = rank_documents(request, request.offset + request.n_results)?;
+
+    let mut documents: Vec<DocumentHit> =
+        ranked.documents.into_iter().skip(request.offset).collect();
+    for document in &mut documents {
+        add_snippets(&mut document.passages, &ranked.text);
+    }
+    Ok(documents)
+}
+
+fn fuse(semantic: Vec<SearchHit>, keyword: Vec<SearchHit>) -> Vec<SearchHit> {
+    let mut fused: HashMap<String, SearchHit> = HashMap::new();
+    for hit in semantic {
+        let signal = hit.semantic.clone();
+        let fused_hit = fused.entry(hit.id.clone()).or_insert(hit);
+        fused_hit.score += signal.as_ref().map(|s| rrf(s.rank)).unwrap_or(0.0);
+    }
+    for hit in keyword {
+        let signal = hit.keyword.clone();
+        let fused_hit = fused.entry(hit.id.clone()).or_insert(hit);
+        fused_hit.score += signal.as_ref().map(|s| rrf(s.rank)).unwrap_or(0.0);
+        fused_hit.keyword = signal;
+    }
+
+    let mut hits: Vec<SearchHit> = fused.into_values().collect();
+    hits.sort_by(|a, b| b.score.total_cmp(&a.score).then(a.id.cmp(&b.id)));
+    hits
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn semantic(ids: &[&str]) -> Vec<SearchHit> {
+        ids.iter()
+            .enumerate()
+            .map(|(i, id)| {
+                let mut hit = SearchHit::new(id.to_string(), None, None);
+                hit.semantic = Some(SemanticSignal {
+                    rank: i + 1,
+                    distance: i as f32,
+                });
+                hit
+            })
+            .collect()
+    }
+
+    fn keyword(ids: &[&str]) -> Vec<SearchHit> {
+        ids.iter()
+            .enumerate()
+            .map(|(i, id)| {
+                let mut hit = SearchHit::new(id.to_string(), None, None);
+                hit.keyword = Some(KeywordSignal {
+                    rank: i + 1,
+                    score: 10.0 - i as f32,
+                    matched: vec!["term".to_string()],
+                });
+                hit
+            })
+            .collect()
+    }
+
+    fn ids(hits: &[SearchHit]) -> Vec<&str> {
+        hits.iter().map(|h| h.id.as_str()).collect()
+    }
+
+    #[test]
+    fn hits_ranked_by_both_signals_come_first() {
+        let fused = fuse(semantic(&["a", "b", "c"]), keyword(&["c", "d"]));
+
+        assert_eq!(ids(&fused), ["c", "a", "b", "d"]);
+        assert_eq!(fused[0].score, rrf(3) + rrf(1));
+        assert_eq!(fused[1].score, rrf(1));
+    }
+
+    #[test]
+    fn fused_hits_keep_both_signals() {
+        let fused = fuse(semantic(&["a", "b"]), keyword(&["b"]));
+        let b = fused.iter().find(|h| h.id == "b").unwrap();
+
+        assert_eq!(b.semantic.as_ref().map(|s| s.rank), Some(2));
+        assert_eq!(b.keyword.as_ref().map(|k| k.rank), Some(1));
+        let a = fused.iter().find(|h| h.id == "a").unwrap();
+        assert!(a.keyword.is_none());
+    }
+
+    #[test]
+    fn equal_scores_are_ordered_by_id() {
+        // Same rank in one signal each
+        let fused = fuse(semantic(&["z"]), keyword(&["m"]));
+        assert_eq!(ids(&fused), ["m", "z"]);
+    }
+
+    #[test]
+    fn a_single_signal_keeps_its_order() {
+        assert_eq!(ids(&fuse(Vec::new(), keyword(&["x", "y"]))), ["x", "y"]);
+        assert_eq!(ids(&fuse(semantic(&["y", "x"]), Vec::new())), ["y", "x"]);
+    }
+}
@@ -0,0 +1,325 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::error::Error;
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, OnceLock, RwLock};
+
+use crate::search::tokenize::terms;
+use crate::store::embedded::validate_name;
+use crate::util::db_path;
+
+// Standard BM25 parameters
+const K1: f32 = 1.2;
+const B: f32 = 0.75;
+
+// The log is rewritten once it holds this many more ops than live documents
+const COMPACT_SLACK: usize = 1_000;
+
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "lowercase")]
+enum LogOp {
+    Upsert {
+        id: String,
+        terms: HashMap<String, u32>,
+    },
+    Delete {
+        id: String,
+    },
+}
+
+struct Doc {
+    terms: HashMap<String, u32>,
+    len: u32,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct KeywordHit {
+    pub id: String,
+    pub score: f32,
+    /// Query terms found in the document
+    pub matched: Vec<String>,
+}
+
+/// BM25 inverted index over one collection's documents. Writes are appended
+/// to a JSON lines log which is replayed on open.
+struct KeywordIndex {
+    path: PathBuf,
+    docs: HashMap<String, Doc>,
+    postings: HashMap<String, HashSet<String>>,
+    total_len: u64,
+    logged: usize,
+}
+
+fn term_counts(text: &str) -> HashMap<String, u32> {
+    let mut counts = HashMap::new();
+    for term in terms(text) {
+        *counts.entry(term).or_insert(0) += 1;
+    }
+    counts
+}
+
+impl KeywordIndex {
+    fn open(path: PathBuf) -> Result<KeywordIndex, Box<dyn Error>> {
+        let mut index = KeywordIndex {
+            path,
+            docs: HashMap::new(),
+            postings: HashMap::new(),
+            total_len: 0,
+            logged: 0,
+        };
+
+        if index.path.exists() {
+            for line in BufReader::new(File::open(&index.path)?).lines() {
+                let line = line?;
+                // A crash mid-append leaves at most one partial last line
+                match serde_json::from_str(&line) {
+                    Ok(op) => index.apply(op),
+                    Err(e) => eprintln!(
+                        "SEARCH:WARN: Skipping bad line in {}: {}",
+                        index.path.display(),
+                        e
+                    ),
+                }
+                index.logged += 1;
+            }
+        }
+
+        Ok(index)
+    }
+
+    fn remove(&mut self, id: &str) {
+        if let Some(doc) = self.docs.remove(id) {
+            self.total_len -= doc.len as u64;
+            for term in doc.terms.keys() {
+                if let Some(ids) = self.postings.get_mut(term) {
+                    ids.remove(id);
+                    if ids.is_empty() {
+                        self.postings.remove(term);
+                    }
+                }
+            }
+        }
+    }
+
+    fn apply(&mut self, op: LogOp) {
+        match op {
+            LogOp::Upsert { id, terms } => {
+                self.remove(&id);
+                let len = terms.values().sum();
+                for term in terms.keys() {
+                    self.postings
+                        .entry(term.clone())
+                        .or_default()
+                        .insert(id.clone());
+                }
+                self.total_len += len as u64;
+                self.docs.insert(id, Doc { terms, len });
+            }
+            LogOp::Delete { id } => self.remove(&id),
+        }
+    }
+
+    fn write(&mut self, ops: Vec<LogOp>) -> Result<(), Box<dyn Error>> {
+        if ops.is_empty() {
+            return Ok(());
+        }
+
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        let mut out = BufWriter::new(file);
+        for op in &ops {
+            serde_json::to_writer(&mut out, op)?;
+            out.write_all(b"\n")?;
+        }
+        out.flush()?;
+
+        self.logged += ops.len();
+        for op in ops {
+            self.apply(op);
+        }
+
+        if self.logged > self.docs.len() * 2 + COMPACT_SLACK {
+            self.compact()?;
+        }
+        Ok(())
+    }
+
+    // Rewrites the log with one upsert per live document
+    fn compact(&mut self) -> Result<(), Box<dyn Error>> {
+        let tmp_path = self.path.with_extension("jsonl.tmp");
+        let mut out = BufWriter::new(File::create(&tmp_path)?);
+
+        for (id, doc) in &self.docs {
+            let op = LogOp::Upsert {
+                id: id.clone(),
+                terms: doc.terms.clone(),
+            };
+            serde_json::to_writer(&mut out, &op)?;
+            out.write_all(b"\n")?;
+        }
+
+        out.into_inner().map_err(|e| e.into_error())?.sync_all()?;
+        std::fs::rename(tmp_path, &self.path)?;
+        self.logged = self.docs.len();
+        Ok(())
+    }
+
+    fn search(&self, query: &str, limit: usize) -> Vec<KeywordHit> {
+        if self.docs.is_empty() {
+            return Vec::new();
+        }
+
+        let count = self.docs.len() as f32;
+        let average_len = self.total_len as f32 / count;
+        let mut hits: HashMap<&str, KeywordHit> = HashMap::new();
+
+        let unique: HashSet<String> = terms(query).into_iter().collect();
+        for term in unique {
+            let ids = match self.postings.get(&term) {
+                Some(ids) => ids,
+                None => continue,
+            };
+
+            let frequency = ids.len() as f32;
+            let idf = (1.0 + (count - frequency + 0.5) / (frequency + 0.5)).ln();
+
+            for id in ids {
+                let doc = &self.docs[id];
+                let tf = doc.terms[&term] as f32;
+                let norm = K1 * (1.0 - B + B * doc.len as f32 / average_len);
+
+                let hit = hits.entry(id).or_insert_with(|| KeywordHit {
+                    id: id.clone(),
+                    score: 0.0,
+                    matched: Vec::new(),
+                });
+                hit.score += idf * tf * (K1 + 1.0) / (tf + norm);
+                hit.matched.push(term.clone());
+            }
+        }
+
+        let mut hits: Vec<KeywordHit> = hits.into_values().collect();
+        hits.sort_by(|a, b| b.score.total_cmp(&a.score).then(a.id.cmp(&b.id)));
+        hits.truncate(limit);
+        for hit in &mut hits {
+            hit.matched.sort();
+        }
+        hits
+    }
+}
+
+/// Keyword indexes of all collections, kept next to the vector store and
+/// fed by the same writes
+pub struct KeywordStore {
+    root: PathBuf,
+    indexes: Mutex<HashMap<String, Arc<RwLock<KeywordIndex>>>>,
+}
+
+impl KeywordStore {
+    pub fn new(root: PathBuf) -> KeywordStore {
+        KeywordStore {
+            root,
+            indexes: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Process-wide keyword store under the datastore directory
+    pub fn shared() -> &'static KeywordStore {
+        static STORE: OnceLock<KeywordStore> = OnceLock::new();
+        STORE.get_or_init(|| KeywordStore::new(db_path().join("keyword")))
+    }
+
+    fn path(&self, collection: &str) -> PathBuf {
+        self.root.join(format!("{}.jsonl", collection))
+    }
+
+    fn index(&self, collection: &str) -> Result<Arc<RwLock<KeywordIndex>>, Box<dyn Error>> {
+        validate_name(collection)?;
+        let mut indexes = self.indexes.lock().unwrap_or_else(|e| e.into_inner());
+
+        if let Some(index) = indexes.get(collection) {
+            return Ok(index.clone());
+        }
+
+        std::fs::create_dir_all(&self.root)?;
+        let index = Arc::new(RwLock::new(KeywordIndex::open(self.path(collection))?));
+        indexes.insert(collection.to_string(), index.clone());
+        Ok(index)
+    }
+
+    /// Whether anything was ever written for `collection`
+    pub fn exists(&self, collection: &str) -> bool {
+        Path::new(&self.path(collection)).exists()
+    }
+
+    pub fn upsert(
+        &self,
+        collection: &str,
+        ids: &[String],
+        documents: &[String],
+    ) -> Result<(), Box<dyn Error>> {
+        if ids.len() != documents.len() {
+            return Err(Box::from("ids and documents differ in length"));
+        }
+
+        let ops = ids
+            .iter()
+            .zip(documents)
+            .map(|(id, document)| LogOp::Upsert {
+                id: id.clone(),
+                terms: term_counts(document),
+            })
+            .collect();
+
+        let index = self.index(collection)?;
+        let mut index = index.write().unwrap_or_else(|e| e.into_inner());
+        index.write(ops)
+    }
+
+    pub fn delete(&self, collection: &str, ids: &[String]) -> Result<(), Box<dyn Error>> {
+        if ids.is_empty() || !self.exists(collection) {
+            return Ok(());
+        }
+
+        let index = self.index(collection)?;
+        let mut index = index.write().unwrap_or_else(|e| e.into_inner());
+        let ops = ids
+            .iter()
+            .filter(|id| index.docs.contains_key(id.as_str()))
+            .map(|id| LogOp::Delete { id: id.clone() })
+            .collect();
+        index.write(ops)
+    }
+
+    pub fn delete_collection(&self, collection: &str) -> Result<(), Box<dyn Error>> {
+        validate_name(collection)?;
+        let mut indexes = self.indexes.lock().unwrap_or_else(|e| e.into_inner());
+        indexes.remove(collection);
+
+        let path = self.path(collection);
+        if path.exists() {
+            std::fs::remove_file(path)?;
+        }
+        Ok(())
+    }
+
+    /// The `limit` best BM25 matches for `query`, best first
+    pub fn search(
+        &self,
+        collection: &str,
+        query: &str,
+        limit: usize,
+    ) -> Result<Vec<KeywordHit>, Box<dyn Error>> {
+        if !self.exists(collection) {
+            return Ok(Vec::new());
+        }
+
+        let index = self.index(collection)?;
+        let index = index.read().unwrap_or_else(|e| e.into_inner());
+        Ok(index.search(query, limit))
+    }
+}
@@ -0,0 +1,60 @@
+pub mod cache;
+pub mod group;
+pub mod hybrid;
+pub mod keyword;
+pub mod page;
+pub mod query;
+pub mod snippet;
+pub mod tokenize;
+
+use std::error::Error;
+
+use crate::chroma::{Filter, GetRequest};
+use crate::store::VectorStore;
+
+pub use cache::SearchCache;
+pub use group::DocumentHit;
+pub use hybrid::{search, search_documents, SearchHit, SearchMode, SearchRequest};
+pub use keyword::KeywordStore;
+pub use page::{first_page, next_page, Page};
+pub use query::ParseError;
+
+const BACKFILL_PAGE: usize = 500;
+
+/// Feeds the keyword index with the documents in `collection` that match
+/// `filter`, for documents written without going through the indexer: ones
+/// from before keyword search existed and ones the connector scripts write
+pub fn backfill(
+    store: &dyn VectorStore,
+    collection: &str,
+    filter: &Filter,
+) -> Result<usize, Box<dyn Error>> {
+    let keywords = KeywordStore::shared();
+    let mut offset = 0;
+
+    loop {
+        let page = store.get(&GetRequest {
+            collection_name: collection.to_string(),
+            ids: None,
+            filter: filter.clone(),
+            limit: Some(BACKFILL_PAGE),
+            offset: Some(offset),
+            include: Some(vec!["documents".to_string()]),
+        })?;
+
+        let fetched = page.ids.len();
+        let documents = page.documents.unwrap_or_default();
+        let (ids, documents): (Vec<String>, Vec<String>) = page
+            .ids
+            .into_iter()
+            .zip(documents)
+            .filter_map(|(id, document)| Some((id, document?)))
+            .unzip();
+        keywords.upsert(collection, &ids, &documents)?;
+
+        offset += fetched;
+        if fetched < BACKFILL_PAGE {
+            return Ok(offset);
+        }
+    }
+}
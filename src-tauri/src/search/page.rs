@@ -0,0 +1,151 @@
+use serde::Serialize;
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashMap};
+use std::error::Error;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Duration, Instant, SystemTime};
+use thiserror::Error;
+
+use crate::search::group::DocumentHit;
+use crate::search::hybrid::{add_snippets, rank_documents, SearchRequest};
+
+/// Most documents a single query can page through
+pub const MAX_RESULTS: usize = 250;
+
+// A cursor is only good while its result set is kept around
+const CURSOR_TTL: Duration = Duration::from_secs(10 * 60);
+const MAX_RESULT_SETS: usize = 32;
+
+#[derive(Error, Debug)]
+pub enum CursorError {
+    #[error("Invalid cursor")]
+    Invalid,
+    #[error("Cursor expired, run the search again")]
+    Expired,
+}
+
+// Every ranked document of a query, so later pages come out of memory in
+// the same order instead of re-running the rankings
+struct ResultSet {
+    created: Instant,
+    text: String,
+    documents: Vec<DocumentHit>,
+    total_is_estimate: bool,
+    sources: BTreeMap<String, usize>,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct Page {
+    pub results: Vec<DocumentHit>,
+    /// Pass back for the following page, `None` on the last one
+    pub next_cursor: Option<String>,
+    /// Matching documents, counted up to `MAX_RESULTS`
+    pub total: usize,
+    /// Whether more documents may match than `total` says
+    pub total_is_estimate: bool,
+    /// `total` split by the `location` of the documents
+    pub sources: BTreeMap<String, usize>,
+}
+
+fn result_sets() -> &'static Mutex<HashMap<String, Arc<ResultSet>>> {
+    static SETS: OnceLock<Mutex<HashMap<String, Arc<ResultSet>>>> = OnceLock::new();
+    SETS.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+fn result_set_id(request: &SearchRequest) -> String {
+    let nanos = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map(|d| d.as_nanos())
+        .unwrap_or_default();
+    let mut hasher = Sha256::new();
+    hasher.update(serde_json::to_vec(request).unwrap_or_default());
+    hasher.update(nanos.to_le_bytes());
+    format!("{:x}", hasher.finalize())[..16].to_string()
+}
+
+fn count_sources(documents: &[DocumentHit]) -> BTreeMap<String, usize> {
+    let mut sources = BTreeMap::new();
+    for document in documents {
+        let source = document
+            .metadata
+            .as_ref()
+            .and_then(|m| m.get("location"))
+            .and_then(Value::as_str)
+            .unwrap_or("unknown");
+        *sources.entry(source.to_string()).or_insert(0) += 1;
+    }
+    sources
+}
+
+fn page_of(id: &str, set: &ResultSet, offset: usize, size: usize) -> Page {
+    let mut results: Vec<DocumentHit> = set
+        .documents
+        .iter()
+        .skip(offset)
+        .take(size)
+        .cloned()
+        .collect();
+    for document in &mut results {
+        add_snippets(&mut document.passages, &set.text);
+    }
+
+    let next = offset + results.len();
+    Page {
+        next_cursor: (next < set.documents.len()).then(|| format!("{}:{}", id, next)),
+        results,
+        total: set.documents.len(),
+        total_is_estimate: set.total_is_estimate,
+        sources: set.sources.clone(),
+    }
+}
+
+/// Runs `request` once for up to `MAX_RESULTS` documents and returns the
+/// page of `n_results` starting at `offset`, the rest is served by
+/// `next_page`. Blocking.
+pub fn first_page(request: &SearchRequest) -> Result<Page, Box<dyn Error>> {
+    let ranked = rank_documents(request, MAX_RESULTS)?;
+    let set = ResultSet {
+        created: Instant::now(),
+        sources: count_sources(&ranked.documents),
+        total_is_estimate: ranked.saturated,
+        text: ranked.text,
+        documents: ranked.documents,
+    };
+
+    let id = result_set_id(request);
+    let page = page_of(&id, &set, request.offset, request.n_results);
+
+    if page.next_cursor.is_some() {
+        let mut sets = result_sets().lock().unwrap();
+        sets.retain(|_, set| set.created.elapsed() < CURSOR_TTL);
+        if sets.len() >= MAX_RESULT_SETS {
+            let oldest = sets
+                .iter()
+                .min_by_key(|(_, set)| set.created)
+                .map(|(id, _)| id.clone());
+            if let Some(oldest) = oldest {
+                sets.remove(&oldest);
+            }
+        }
+        sets.insert(id, Arc::new(set));
+    }
+
+    Ok(page)
+}
+
+/// The `size` documents following `cursor`
+pub fn next_page(cursor: &str, size: usize) -> Result<Page, CursorError> {
+    let (id, offset) = cursor.rsplit_once(':').ok_or(CursorError::Invalid)?;
+    let offset: usize = offset.parse().map_err(|_| CursorError::Invalid)?;
+
+    let set = result_sets()
+        .lock()
+        .unwrap()
+        .get(id)
+        .filter(|set| set.created.elapsed() < CURSOR_TTL)
+        .cloned()
+        .ok_or(CursorError::Expired)?;
+
+    Ok(page_of(id, &set, offset, size))
+}
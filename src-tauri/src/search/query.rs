@@ -0,0 +1,447 @@
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+use thiserror::Error;
+
+use crate::chroma::{Filter, Metadata};
+
+/// A query that couldn't be parsed. `position` counts characters from the
+/// start of the query.
+#[derive(Serialize, Debug, Clone, Error)]
+#[error("{message} at position {position}")]
+pub struct ParseError {
+    pub position: usize,
+    pub message: String,
+}
+
+fn error<T>(position: usize, message: impl Into<String>) -> Result<T, ParseError> {
+    Err(ParseError {
+        position,
+        message: message.into(),
+    })
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Field {
+    Source,
+    Ext,
+    Path,
+    Modified,
+    Author,
+}
+
+impl Field {
+    fn parse(name: &str) -> Option<Field> {
+        match name.to_lowercase().as_str() {
+            "source" => Some(Field::Source),
+            "ext" => Some(Field::Ext),
+            "path" => Some(Field::Path),
+            "modified" => Some(Field::Modified),
+            "author" => Some(Field::Author),
+            _ => None,
+        }
+    }
+
+    /// Metadata key the field filters on
+    fn key(&self) -> &'static str {
+        match self {
+            Field::Source => "location",
+            Field::Ext => "extension",
+            Field::Path => "filepath",
+            Field::Modified => "mtime",
+            Field::Author => "author",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Comparison {
+    Eq,
+    Gt,
+    Gte,
+    Lt,
+    Lte,
+}
+
+#[derive(Debug, Clone)]
+enum Term {
+    Word(String),
+    Phrase(String),
+    Field {
+        field: Field,
+        comparison: Comparison,
+        value: String,
+        // Where the value starts, for errors about it
+        position: usize,
+    },
+}
+
+/// A `path:` rule. Chroma can't match string prefixes, so these are checked
+/// against the hits' `filepath` after the store query.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct PathRule {
+    pub prefix: String,
+    pub negated: bool,
+}
+
+/// A query split into the free text to rank by and the filters narrowing
+/// what may match
+#[derive(Serialize, Debug, Clone, Default)]
+pub struct ParsedQuery {
+    /// What's left for the semantic and keyword rankings, may be empty
+    pub text: String,
+    pub filter: Filter,
+    pub paths: Vec<PathRule>,
+}
+
+impl ParsedQuery {
+    pub fn matches_path(&self, metadata: Option<&Metadata>) -> bool {
+        if self.paths.is_empty() {
+            return true;
+        }
+
+        let path = metadata
+            .and_then(|m| m.get(Field::Path.key()))
+            .and_then(Value::as_str)
+            .unwrap_or("");
+        self.paths
+            .iter()
+            .all(|rule| path.starts_with(&rule.prefix) != rule.negated)
+    }
+}
+
+struct Lexer {
+    chars: Vec<char>,
+    position: usize,
+}
+
+impl Lexer {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.position).copied()
+    }
+
+    fn peek_at(&self, offset: usize) -> Option<char> {
+        self.chars.get(self.position + offset).copied()
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.peek().map(char::is_whitespace).unwrap_or(false) {
+            self.position += 1;
+        }
+    }
+
+    fn bare(&mut self) -> String {
+        let start = self.position;
+        while self.peek().map(|c| !c.is_whitespace()).unwrap_or(false) {
+            self.position += 1;
+        }
+        self.chars[start..self.position].iter().collect()
+    }
+
+    // Expects to be on the opening quote
+    fn quoted(&mut self) -> Result<String, ParseError> {
+        let open = self.position;
+        self.position += 1;
+        let start = self.position;
+
+        while let Some(c) = self.peek() {
+            if c == '"' {
+                let text = self.chars[start..self.position].iter().collect();
+                self.position += 1;
+                return Ok(text);
+            }
+            self.position += 1;
+        }
+
+        error(open, "Unterminated quote")
+    }
+
+    // `name:` for a known field name, consuming it only if it matches
+    fn field_name(&mut self) -> Option<Field> {
+        let mut end = self.position;
+        while self
+            .chars
+            .get(end)
+            .map(|c| c.is_ascii_alphabetic())
+            .unwrap_or(false)
+        {
+            end += 1;
+        }
+
+        if self.chars.get(end) != Some(&':') {
+            return None;
+        }
+
+        let name: String = self.chars[self.position..end].iter().collect();
+        let field = Field::parse(&name)?;
+        self.position = end + 1;
+        Some(field)
+    }
+
+    fn comparison(&mut self) -> Comparison {
+        let (comparison, length) = match (self.peek(), self.peek_at(1)) {
+            (Some('>'), Some('=')) => (Comparison::Gte, 2),
+            (Some('<'), Some('=')) => (Comparison::Lte, 2),
+            (Some('>'), _) => (Comparison::Gt, 1),
+            (Some('<'), _) => (Comparison::Lt, 1),
+            _ => (Comparison::Eq, 0),
+        };
+        self.position += length;
+        comparison
+    }
+
+    /// The next term and whether it was negated with a leading `-`
+    fn next(&mut self) -> Result<Option<(Term, bool)>, ParseError> {
+        self.skip_whitespace();
+        let start = self.position;
+        let c = match self.peek() {
+            Some(c) => c,
+            None => return Ok(None),
+        };
+
+        let negated = c == '-' && self.peek_at(1).map(|n| !n.is_whitespace()).unwrap_or(false);
+        if negated {
+            self.position += 1;
+        }
+
+        if self.peek() == Some('"') {
+            return Ok(Some((Term::Phrase(self.quoted()?), negated)));
+        }
+
+        if let Some(field) = self.field_name() {
+            let operator = self.position;
+            let comparison = self.comparison();
+            if comparison != Comparison::Eq && field != Field::Modified {
+                return error(operator, "Only modified: can be compared with < or >");
+            }
+
+            let position = self.position;
+            let value = if self.peek() == Some('"') {
+                self.quoted()?
+            } else {
+                self.bare()
+            };
+            if value.is_empty() {
+                return error(position, "Missing value after the field");
+            }
+
+            return Ok(Some((
+                Term::Field {
+                    field,
+                    comparison,
+                    value,
+                    position,
+                },
+                negated,
+            )));
+        }
+
+        // A lone `-` or an unknown `name:` is just text
+        self.position = start;
+        let word = self.bare();
+        Ok(Some(match word.strip_prefix('-') {
+            Some(rest) if negated => (Term::Word(rest.to_string()), true),
+            _ => (Term::Word(word), false),
+        }))
+    }
+}
+
+// Days since 1970-01-01 for a proleptic Gregorian date
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let month_index = (month + 9) % 12;
+    let day_of_year = (153 * month_index + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
+
+/// Unix seconds at the start of a `YYYY-MM-DD` day, in UTC
+fn parse_date(value: &str, position: usize) -> Result<i64, ParseError> {
+    let invalid = || ParseError {
+        position,
+        message: format!("Expected a date like 2024-06-01, got {}", value),
+    };
+
+    let parts: Vec<&str> = value.split('-').collect();
+    let [year, month, day] = parts.as_slice() else {
+        return Err(invalid());
+    };
+    if year.len() != 4 || month.len() != 2 || day.len() != 2 {
+        return Err(invalid());
+    }
+
+    let (year, month, day): (i64, i64, i64) = match (year.parse(), month.parse(), day.parse()) {
+        (Ok(year), Ok(month), Ok(day)) => (year, month, day),
+        _ => return Err(invalid()),
+    };
+
+    let leap = year % 4 == 0 && (year % 100 != 0 || year % 400 == 0);
+    let days_in_month = match month {
+        2 if leap => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        1..=12 => 31,
+        _ => return Err(invalid()),
+    };
+    if !(1..=days_in_month).contains(&day) {
+        return Err(invalid());
+    }
+
+    Ok(days_from_civil(year, month, day) * 86_400)
+}
+
+fn modified_condition(
+    comparison: Comparison,
+    value: &str,
+    position: usize,
+    negated: bool,
+) -> Result<Value, ParseError> {
+    let start = parse_date(value, position)?;
+    let end = start + 86_400;
+    let key = Field::Modified.key();
+
+    // `>` means after the whole day, `<=` up to its end
+    let (comparison, negated) = match (comparison, negated) {
+        (Comparison::Eq, _) => (Comparison::Eq, negated),
+        (Comparison::Gt, true) => (Comparison::Lte, false),
+        (Comparison::Gte, true) => (Comparison::Lt, false),
+        (Comparison::Lt, true) => (Comparison::Gte, false),
+        (Comparison::Lte, true) => (Comparison::Gt, false),
+        (comparison, false) => (comparison, false),
+    };
+
+    Ok(match (comparison, negated) {
+        (Comparison::Eq, false) => json!({ "$and": [
+            { key: { "$gte": start } },
+            { key: { "$lt": end } },
+        ] }),
+        (Comparison::Eq, true) => json!({ "$or": [
+            { key: { "$lt": start } },
+            { key: { "$gte": end } },
+        ] }),
+        (Comparison::Gt, _) => json!({ key: { "$gte": end } }),
+        (Comparison::Gte, _) => json!({ key: { "$gte": start } }),
+        (Comparison::Lt, _) => json!({ key: { "$lt": start } }),
+        (Comparison::Lte, _) => json!({ key: { "$lt": end } }),
+    })
+}
+
+fn field_value(field: Field, value: &str) -> String {
+    match field {
+        Field::Ext => value.trim_start_matches('.').to_lowercase(),
+        Field::Source => value.to_lowercase(),
+        _ => value.to_string(),
+    }
+}
+
+fn expand_home(path: &str) -> String {
+    match (path.strip_prefix('~'), dirs::home_dir()) {
+        (Some(rest), Some(home)) if rest.is_empty() || rest.starts_with('/') => {
+            format!("{}{}", home.display(), rest)
+        }
+        _ => path.to_string(),
+    }
+}
+
+/// Chroma rejects `$and` / `$or` with fewer than two conditions
+pub fn all_of(mut conditions: Vec<Value>) -> Option<Value> {
+    match conditions.len() {
+        0 => None,
+        1 => conditions.pop(),
+        _ => Some(json!({ "$and": conditions })),
+    }
+}
+
+/// Both filters at once
+pub fn merge_filters(a: &Filter, b: &Filter) -> Filter {
+    let both =
+        |a: &Option<Value>, b: &Option<Value>| all_of(a.iter().chain(b.iter()).cloned().collect());
+    Filter {
+        where_metadata: both(&a.where_metadata, &b.where_metadata),
+        where_document: both(&a.where_document, &b.where_document),
+    }
+}
+
+/// Parses a search box query such as
+/// `budget "q3 forecast" source:notion ext:pdf -draft modified:>2024-06-01`.
+///
+/// - `source:`, `ext:` and `author:` match the `location`, `extension` and
+///   `author` metadata, repeating a field matches any of its values
+/// - `path:` keeps hits whose path starts with the value, `~` is expanded
+/// - `modified:` takes a `YYYY-MM-DD` day (UTC), optionally after `>`,
+///   `>=`, `<` or `<=`
+/// - quoted phrases must appear in the document as written
+/// - a leading `-` negates a word, phrase or field
+///
+/// Unknown `name:` prefixes are left in the text, so URLs and error codes
+/// still search as typed.
+pub fn parse_query(query: &str) -> Result<ParsedQuery, ParseError> {
+    let mut lexer = Lexer {
+        chars: query.chars().collect(),
+        position: 0,
+    };
+
+    let mut text = Vec::new();
+    let mut document = Vec::new();
+    let mut metadata = Vec::new();
+    let mut paths = Vec::new();
+    // (key, negated) -> values, so repeated fields become `$in` / `$nin`
+    let mut values: BTreeMap<(&'static str, bool), Vec<String>> = BTreeMap::new();
+
+    while let Some((term, negated)) = lexer.next()? {
+        match term {
+            Term::Word(word) if negated => document.push(json!({ "$not_contains": word })),
+            Term::Word(word) => text.push(word),
+            Term::Phrase(phrase) if phrase.trim().is_empty() => {}
+            Term::Phrase(phrase) if negated => document.push(json!({ "$not_contains": phrase })),
+            Term::Phrase(phrase) => {
+                document.push(json!({ "$contains": phrase }));
+                text.push(phrase);
+            }
+            Term::Field {
+                field: Field::Path,
+                value,
+                ..
+            } => paths.push(PathRule {
+                prefix: expand_home(&value),
+                negated,
+            }),
+            Term::Field {
+                field: Field::Modified,
+                comparison,
+                value,
+                position,
+            } => metadata.push(modified_condition(comparison, &value, position, negated)?),
+            Term::Field { field, value, .. } => values
+                .entry((field.key(), negated))
+                .or_default()
+                .push(field_value(field, &value)),
+        }
+    }
+
+    for ((key, negated), mut values) in values {
+        values.dedup();
+        let operator = match (values.len(), negated) {
+            (1, false) => "$eq",
+            (1, true) => "$ne",
+            (_, false) => "$in",
+            (_, true) => "$nin",
+        };
+        let value = match values.len() {
+            1 => json!(values[0]),
+            _ => json!(values),
+        };
+        metadata.push(json!({ key: { operator: value } }));
+    }
+
+    Ok(ParsedQuery {
+        text: text.join(" "),
+        filter: Filter {
+            where_metadata: all_of(metadata),
+            where_document: all_of(document),
+        },
+        paths,
+    })
+}
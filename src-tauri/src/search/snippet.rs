@@ -0,0 +1,197 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashSet;
+use std::ops::Range;
+
+use crate::chroma::Metadata;
+use crate::search::tokenize;
+
+// Upper bound for the passage, a few lines of a preview pane
+const MAX_SNIPPET_BYTES: usize = 320;
+
+/// The passage of a hit that best matches the query
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Snippet {
+    pub text: String,
+    /// `[start, end)` pairs of character (not byte) offsets into `text`
+    pub highlights: Vec<(usize, usize)>,
+    /// 1-based lines of the source file the passage spans, when the chunk
+    /// recorded where it came from
+    pub start_line: Option<usize>,
+    pub end_line: Option<usize>,
+    /// Page or slide number of the passage for PDFs and office documents
+    pub page: Option<usize>,
+    /// Whether the passage was cut from a longer document
+    pub truncated: bool,
+}
+
+struct Match {
+    range: Range<usize>,
+    term: String,
+}
+
+// Whole words matching a query term, or just the matching parts of an
+// identifier (`index` in `index_file`)
+fn find_matches(document: &str, terms: &HashSet<String>) -> Vec<Match> {
+    let mut matches = Vec::new();
+
+    for (range, word) in tokenize::words(document) {
+        let lower = word.to_lowercase();
+        if terms.contains(&lower) {
+            matches.push(Match { range, term: lower });
+            continue;
+        }
+        for part in tokenize::parts(word) {
+            let lower = word[part.clone()].to_lowercase();
+            if terms.contains(&lower) {
+                let start = range.start + part.start;
+                matches.push(Match {
+                    range: start..range.start + part.end,
+                    term: lower,
+                });
+            }
+        }
+    }
+
+    matches
+}
+
+// The span of consecutive matches fitting in the budget that covers the most
+// distinct terms, then the most matches. Earliest wins ties.
+fn best_span(matches: &[Match]) -> Option<Range<usize>> {
+    let mut best: Option<((usize, usize), Range<usize>)> = None;
+
+    for (i, first) in matches.iter().enumerate() {
+        let mut distinct = HashSet::new();
+        let mut end = first.range.end;
+        let mut count = 0;
+
+        for m in &matches[i..] {
+            if m.range.end - first.range.start > MAX_SNIPPET_BYTES {
+                break;
+            }
+            distinct.insert(m.term.as_str());
+            end = m.range.end;
+            count += 1;
+        }
+
+        let score = (distinct.len(), count);
+        if best.as_ref().map(|(s, _)| score > *s).unwrap_or(true) {
+            best = Some((score, first.range.start..end));
+        }
+    }
+
+    best.map(|(_, span)| span)
+}
+
+fn floor_char_boundary(text: &str, mut index: usize) -> usize {
+    index = index.min(text.len());
+    while !text.is_char_boundary(index) {
+        index -= 1;
+    }
+    index
+}
+
+// Byte offsets just past each sentence or line end in `text`. A period
+// only counts when whitespace follows, so `config.rs` and `3.14` don't.
+fn sentence_ends(text: &str) -> impl Iterator<Item = usize> + '_ {
+    let mut chars = text.char_indices().peekable();
+    std::iter::from_fn(move || {
+        while let Some((i, c)) = chars.next() {
+            let next_is_space = chars.peek().map(|(_, n)| n.is_whitespace()).unwrap_or(true);
+            if c == '\n' || (matches!(c, '.' | '!' | '?') && next_is_space) {
+                return Some(i + c.len_utf8());
+            }
+        }
+        None
+    })
+}
+
+// Grows `span` to the budget, cutting at sentence or line ends when there
+// are some in reach and otherwise on a word boundary
+fn expand(document: &str, span: Range<usize>) -> Range<usize> {
+    let slack = MAX_SNIPPET_BYTES.saturating_sub(span.len());
+
+    let lower = floor_char_boundary(document, span.start.saturating_sub(slack / 2));
+    let before = &document[lower..span.start];
+    let start = match sentence_ends(before).next() {
+        _ if lower == 0 => 0,
+        Some(i) => lower + i,
+        None => before
+            .find(char::is_whitespace)
+            .map(|i| lower + i)
+            .unwrap_or(span.start),
+    };
+
+    let upper = floor_char_boundary(document, start + MAX_SNIPPET_BYTES).max(span.end);
+    let after = &document[span.end..upper];
+    let end = match sentence_ends(after).last() {
+        _ if upper == document.len() => upper,
+        Some(i) => span.end + i,
+        None => after
+            .rfind(char::is_whitespace)
+            .map(|i| span.end + i)
+            .unwrap_or(span.end),
+    };
+
+    start..end
+}
+
+fn metadata_number(metadata: Option<&Metadata>, key: &str) -> Option<usize> {
+    metadata?.get(key)?.as_u64().map(|n| n as usize)
+}
+
+fn page_of(metadata: Option<&Metadata>) -> Option<usize> {
+    let metadata = metadata?;
+    match metadata.get("section_kind").and_then(Value::as_str)? {
+        "page" | "slide" => metadata.get("section_label")?.as_str()?.parse().ok(),
+        _ => None,
+    }
+}
+
+/// Picks the passage of `document` (a chunk's text) that best matches
+/// `query_text` and locates it in the source file using the chunk
+/// metadata. Falls back to the start of the document when nothing matches,
+/// as happens with purely semantic hits. `None` for images, whose document
+/// is the encoded image rather than text.
+pub fn snippet(document: &str, query_text: &str, metadata: Option<&Metadata>) -> Option<Snippet> {
+    // Only text gets chunked
+    if !metadata
+        .map(|m| m.contains_key("chunk_index"))
+        .unwrap_or(false)
+    {
+        return None;
+    }
+
+    let terms: HashSet<String> = tokenize::terms(query_text).into_iter().collect();
+    let matches = find_matches(document, &terms);
+
+    let span = best_span(&matches).unwrap_or(0..0);
+    let span = expand(document, span);
+
+    // Leading and trailing whitespace don't make it into the passage
+    let raw = &document[span.clone()];
+    let text = raw.trim();
+    let start = span.start + raw.len() - raw.trim_start().len();
+    let end = start + text.len();
+
+    let char_offset = |byte: usize| document[start..byte].chars().count();
+    let highlights = matches
+        .iter()
+        .filter(|m| m.range.start >= start && m.range.end <= end)
+        .map(|m| (char_offset(m.range.start), char_offset(m.range.end)))
+        .collect();
+
+    let start_line = metadata_number(metadata, "start_line")
+        .map(|line| line + document[..start].matches('\n').count());
+    let end_line = start_line.map(|line| line + text.matches('\n').count());
+
+    Some(Snippet {
+        text: text.to_string(),
+        highlights,
+        start_line,
+        end_line,
+        page: page_of(metadata),
+        truncated: !document[..start].trim().is_empty() || !document[end..].trim().is_empty(),
+    })
+}
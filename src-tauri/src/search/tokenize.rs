@@ -0,0 +1,91 @@
+use std::ops::Range;
+
+// Longer runs are hashes, base64 blobs and the like, not something people type
+const MAX_TERM_CHARS: usize = 64;
+
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || matches!(c, '_' | '.' | '-')
+}
+
+// `parseHTTPHeader2` -> `parse`, `HTTP`, `Header2`, as byte ranges of `word`
+fn split_parts(word: &str) -> Vec<Range<usize>> {
+    let mut parts = Vec::new();
+    let mut start = None;
+    let chars: Vec<(usize, char)> = word.char_indices().collect();
+
+    for (i, &(offset, c)) in chars.iter().enumerate() {
+        if !c.is_alphanumeric() {
+            if let Some(start) = start.take() {
+                parts.push(start..offset);
+            }
+            continue;
+        }
+
+        let boundary = i > 0 && c.is_uppercase() && {
+            let previous = chars[i - 1].1;
+            let next_is_lower = chars
+                .get(i + 1)
+                .map(|(_, n)| n.is_lowercase())
+                .unwrap_or(false);
+            previous.is_lowercase() || (previous.is_uppercase() && next_is_lower)
+        };
+        if boundary {
+            if let Some(start) = start.take() {
+                parts.push(start..offset);
+            }
+        }
+        start.get_or_insert(offset);
+    }
+    if let Some(start) = start {
+        parts.push(start..word.len());
+    }
+
+    parts
+}
+
+/// Words of `text` that terms are made of, with their byte ranges
+pub fn words(text: &str) -> impl Iterator<Item = (Range<usize>, &str)> {
+    let mut offset = 0;
+    text.split(|c: char| !is_word_char(c))
+        .filter_map(move |word| {
+            let start = offset;
+            offset += word.len()
+                + text[start + word.len()..]
+                    .chars()
+                    .next()
+                    .map_or(0, char::len_utf8);
+
+            let trimmed = word.trim_start_matches(|c: char| !c.is_alphanumeric());
+            let start = start + word.len() - trimmed.len();
+            let trimmed = trimmed.trim_end_matches(|c: char| !c.is_alphanumeric());
+            if trimmed.is_empty() || trimmed.chars().count() > MAX_TERM_CHARS {
+                return None;
+            }
+            Some((start..start + trimmed.len(), trimmed))
+        })
+}
+
+/// Byte ranges of the parts of an identifier, empty when `word` is a
+/// single part
+pub fn parts(word: &str) -> Vec<Range<usize>> {
+    let parts = split_parts(word);
+    if parts.len() > 1 {
+        parts
+    } else {
+        Vec::new()
+    }
+}
+
+/// Lowercased search terms of `text`. Identifiers like `index_file`,
+/// `config.rs` or `ERR_CONN_RESET` are kept whole and also split into their
+/// parts, so both the exact identifier and its pieces match.
+pub fn terms(text: &str) -> Vec<String> {
+    let mut terms = Vec::new();
+
+    for (_, word) in words(text) {
+        terms.push(word.to_lowercase());
+        terms.extend(parts(word).into_iter().map(|p| word[p].to_lowercase()));
+    }
+
+    terms
+}
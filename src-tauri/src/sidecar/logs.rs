@@ -0,0 +1,96 @@
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Read, Write};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::SystemTime;
+
+use crate::util::db_path;
+
+// Past this the log is moved to `{name}.log.1`, replacing the previous one
+const MAX_LOG_BYTES: u64 = 5 * 1024 * 1024;
+
+pub fn log_dir() -> PathBuf {
+    db_path().join("logs")
+}
+
+/// Append-only log of a sidecar's output, shared by its stdout and stderr
+pub struct LogFile {
+    path: PathBuf,
+    file: File,
+    size: u64,
+}
+
+impl LogFile {
+    pub fn open(name: &str) -> std::io::Result<LogFile> {
+        let dir = log_dir();
+        std::fs::create_dir_all(&dir)?;
+        let path = dir.join(format!("{}.log", name));
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        let size = file.metadata()?.len();
+        Ok(LogFile { path, file, size })
+    }
+
+    pub fn path(&self) -> &PathBuf {
+        &self.path
+    }
+
+    pub fn write_line(&mut self, stream: &str, line: &str) -> std::io::Result<()> {
+        if self.size > MAX_LOG_BYTES {
+            self.rotate()?;
+        }
+
+        let seconds = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or_default();
+        let entry = format!("{} [{}] {}\n", seconds, stream, line);
+        self.file.write_all(entry.as_bytes())?;
+        self.size += entry.len() as u64;
+        Ok(())
+    }
+
+    fn rotate(&mut self) -> std::io::Result<()> {
+        std::fs::rename(&self.path, self.path.with_extension("log.1"))?;
+        self.file = File::create(&self.path)?;
+        self.size = 0;
+        Ok(())
+    }
+}
+
+/// Copies `output` into `log` line by line until the stream closes, which
+/// happens when the process exits
+pub fn capture(
+    log: Arc<Mutex<LogFile>>,
+    stream: &'static str,
+    output: impl Read + Send + 'static,
+) -> JoinHandle<()> {
+    capture_lines(log, stream, output, |_| {})
+}
+
+/// Like `capture`, also handing every line to `on_line`
+pub fn capture_lines(
+    log: Arc<Mutex<LogFile>>,
+    stream: &'static str,
+    output: impl Read + Send + 'static,
+    mut on_line: impl FnMut(&str) + Send + 'static,
+) -> JoinHandle<()> {
+    thread::spawn(move || {
+        for line in BufReader::new(output).lines() {
+            let line = match line {
+                Ok(line) => line,
+                Err(_) => break,
+            };
+            on_line(&line);
+            let mut log = log.lock().unwrap_or_else(|e| e.into_inner());
+            if let Err(e) = log.write_line(stream, &line) {
+                eprintln!(
+                    "SIDECAR:WARN: Can't write to {}: {}",
+                    log.path().display(),
+                    e
+                );
+                break;
+            }
+        }
+    })
+}
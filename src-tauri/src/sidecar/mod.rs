@@ -0,0 +1,4 @@
+pub mod logs;
+pub mod supervisor;
+
+pub use supervisor::{SidecarSpec, SidecarStatus, Supervisor};
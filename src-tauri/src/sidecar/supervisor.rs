@@ -0,0 +1,284 @@
+use serde::Serialize;
+use std::process::{Child, Command, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::sidecar::logs::{capture, LogFile};
+
+const HEALTH_INTERVAL: Duration = Duration::from_secs(2);
+// Health checks don't count until the first success or this long after a
+// start, Chroma takes a while to come up the first time
+const STARTUP_GRACE: Duration = Duration::from_secs(60);
+// Failed checks in a row before a running process is considered hung
+const UNHEALTHY_LIMIT: u32 = 3;
+const MIN_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+// A process that stayed up this long starts over from the shortest backoff
+const STABLE_AFTER: Duration = Duration::from_secs(120);
+
+/// Returns `Err` with the reason when the sidecar isn't serving
+pub type HealthCheck = Arc<dyn Fn() -> Result<(), String> + Send + Sync>;
+
+/// A child process the app depends on
+pub struct SidecarSpec {
+    /// Also names the log file, `logs/{name}.log` in the datastore
+    pub name: String,
+    /// Run directly rather than through a shell, so killing it kills the
+    /// actual server
+    pub program: String,
+    pub args: Vec<String>,
+    pub health: Option<HealthCheck>,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+#[serde(tag = "state", rename_all = "lowercase")]
+pub enum SidecarStatus {
+    Starting,
+    Healthy,
+    /// Running but failing health checks
+    Unhealthy {
+        reason: String,
+    },
+    /// Exited, hung or couldn't be spawned (e.g. the program isn't
+    /// installed), starting again in `delay_ms`
+    Restarting {
+        attempt: u32,
+        delay_ms: u64,
+        reason: String,
+    },
+    Stopped,
+}
+
+struct Sidecar {
+    spec: SidecarSpec,
+    child: Mutex<Option<Child>>,
+    status: Mutex<SidecarStatus>,
+}
+
+/// Owns the sidecar processes: restarts them with exponential backoff when
+/// they exit or stop answering health checks, writes their output to log
+/// files, and kills them all on `shutdown`.
+pub struct Supervisor {
+    sidecars: Mutex<Vec<Arc<Sidecar>>>,
+    stopping: AtomicBool,
+}
+
+fn backoff(attempt: u32) -> Duration {
+    let factor = 2u32.saturating_pow(attempt.saturating_sub(1));
+    MIN_BACKOFF.saturating_mul(factor).min(MAX_BACKOFF)
+}
+
+impl Sidecar {
+    fn set_status(&self, status: SidecarStatus) {
+        let mut current = self.status.lock().unwrap_or_else(|e| e.into_inner());
+        if *current != status {
+            println!("SIDECAR:INFO: {} is {:?}", self.spec.name, status);
+            *current = status;
+        }
+    }
+
+    fn start(&self, stopping: &AtomicBool) -> Result<(), String> {
+        let mut slot = self.child.lock().unwrap_or_else(|e| e.into_inner());
+        // Checked under the lock so `shutdown` can't miss a fresh child
+        if stopping.load(Ordering::SeqCst) {
+            return Err("shutting down".to_string());
+        }
+
+        let log = LogFile::open(&self.spec.name).map_err(|e| e.to_string())?;
+        let log = Arc::new(Mutex::new(log));
+
+        let mut child = Command::new(&self.spec.program)
+            .args(&self.spec.args)
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|e| format!("can't run {}: {}", self.spec.program, e))?;
+
+        if let Some(stdout) = child.stdout.take() {
+            capture(log.clone(), "stdout", stdout);
+        }
+        if let Some(stderr) = child.stderr.take() {
+            capture(log, "stderr", stderr);
+        }
+
+        println!(
+            "SIDECAR:INFO: Started {} (pid {})",
+            self.spec.name,
+            child.id()
+        );
+        *slot = Some(child);
+        Ok(())
+    }
+
+    fn kill(&self) {
+        let mut slot = self.child.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(mut child) = slot.take() {
+            if let Err(e) = child.kill() {
+                eprintln!("SIDECAR:WARN: Failed to kill {}: {}", self.spec.name, e);
+            }
+            let _ = child.wait();
+        }
+    }
+
+    // Ok(Some) once the process exited on its own
+    fn try_wait(&self) -> std::io::Result<Option<String>> {
+        let mut slot = self.child.lock().unwrap_or_else(|e| e.into_inner());
+        match slot.as_mut().map(Child::try_wait).transpose()? {
+            Some(Some(status)) => {
+                slot.take();
+                Ok(Some(format!("exited with {}", status)))
+            }
+            Some(None) => Ok(None),
+            None => Ok(Some("not running".to_string())),
+        }
+    }
+
+    // Watches a started process until it has to be restarted, returning why,
+    // or `None` when the supervisor is shutting down
+    fn watch(&self, stopping: &AtomicBool) -> Option<String> {
+        let started = Instant::now();
+        let mut failures = 0;
+        let mut came_up = false;
+
+        loop {
+            if !sleep_unless_stopping(HEALTH_INTERVAL, stopping) {
+                return None;
+            }
+
+            match self.try_wait() {
+                Ok(None) => {}
+                Ok(Some(reason)) => return Some(reason),
+                Err(e) => return Some(e.to_string()),
+            }
+
+            let health = match &self.spec.health {
+                Some(health) => health(),
+                None => Ok(()),
+            };
+            match health {
+                Ok(()) => {
+                    failures = 0;
+                    came_up = true;
+                    self.set_status(SidecarStatus::Healthy);
+                }
+                Err(_) if !came_up && started.elapsed() < STARTUP_GRACE => {}
+                Err(reason) => {
+                    failures += 1;
+                    self.set_status(SidecarStatus::Unhealthy {
+                        reason: reason.clone(),
+                    });
+                    if failures >= UNHEALTHY_LIMIT {
+                        self.kill();
+                        return Some(format!("unresponsive: {}", reason));
+                    }
+                }
+            }
+        }
+    }
+
+    fn supervise(&self, stopping: &AtomicBool) {
+        let mut attempt = 0;
+
+        while !stopping.load(Ordering::SeqCst) {
+            self.set_status(SidecarStatus::Starting);
+            let started = Instant::now();
+
+            let reason = match self.start(stopping) {
+                Ok(()) => match self.watch(stopping) {
+                    Some(reason) => reason,
+                    None => break,
+                },
+                Err(reason) => reason,
+            };
+            eprintln!("SIDECAR:WARN: {} {}", self.spec.name, reason);
+
+            if started.elapsed() >= STABLE_AFTER {
+                attempt = 0;
+            }
+            attempt += 1;
+            let delay = backoff(attempt);
+            self.set_status(SidecarStatus::Restarting {
+                attempt,
+                delay_ms: delay.as_millis() as u64,
+                reason,
+            });
+            if !sleep_unless_stopping(delay, stopping) {
+                break;
+            }
+        }
+
+        self.kill();
+        self.set_status(SidecarStatus::Stopped);
+    }
+}
+
+// False when woken up by a shutdown
+fn sleep_unless_stopping(duration: Duration, stopping: &AtomicBool) -> bool {
+    let deadline = Instant::now() + duration;
+    while Instant::now() < deadline {
+        if stopping.load(Ordering::SeqCst) {
+            return false;
+        }
+        thread::sleep(
+            Duration::from_millis(100).min(deadline.saturating_duration_since(Instant::now())),
+        );
+    }
+    !stopping.load(Ordering::SeqCst)
+}
+
+impl Supervisor {
+    pub fn new() -> Supervisor {
+        Supervisor {
+            sidecars: Mutex::new(Vec::new()),
+            stopping: AtomicBool::new(false),
+        }
+    }
+
+    /// Process-wide supervisor, shut down when the app exits
+    pub fn shared() -> &'static Supervisor {
+        static SUPERVISOR: OnceLock<Supervisor> = OnceLock::new();
+        SUPERVISOR.get_or_init(Supervisor::new)
+    }
+
+    /// Starts `spec` and keeps it running on a background thread
+    pub fn spawn(&'static self, spec: SidecarSpec) {
+        let sidecar = Arc::new(Sidecar {
+            spec,
+            child: Mutex::new(None),
+            status: Mutex::new(SidecarStatus::Starting),
+        });
+        self.sidecars.lock().unwrap().push(sidecar.clone());
+
+        thread::spawn(move || sidecar.supervise(&self.stopping));
+    }
+
+    pub fn status(&self, name: &str) -> Option<SidecarStatus> {
+        self.sidecars
+            .lock()
+            .unwrap()
+            .iter()
+            .find(|s| s.spec.name == name)
+            .map(|s| s.status.lock().unwrap_or_else(|e| e.into_inner()).clone())
+    }
+
+    /// Kills every sidecar and stops restarting them. Safe to call more
+    /// than once.
+    pub fn shutdown(&self) {
+        if self.stopping.swap(true, Ordering::SeqCst) {
+            return;
+        }
+        for sidecar in self.sidecars.lock().unwrap().iter() {
+            println!("SIDECAR:INFO: Stopping {}", sidecar.spec.name);
+            sidecar.kill();
+        }
+    }
+}
+
+impl Default for Supervisor {
+    fn default() -> Self {
+        Supervisor::new()
+    }
+}
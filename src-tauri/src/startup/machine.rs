@@ -0,0 +1,237 @@
+use serde::Serialize;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{channel, RecvTimeoutError};
+use std::sync::{Mutex, OnceLock};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::startup::stages::Stage;
+
+const TIMEOUT_SLACK: Duration = Duration::from_secs(1);
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+#[serde(tag = "state", rename_all = "lowercase")]
+pub enum StageStatus {
+    Pending,
+    Running,
+    Done,
+    Failed {
+        reason: String,
+    },
+    /// Not run because a stage it requires didn't finish
+    Skipped {
+        reason: String,
+    },
+    /// Nothing to do with the current settings, doesn't make startup degraded
+    Unneeded {
+        reason: String,
+    },
+}
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Phase {
+    Starting,
+    Ready,
+    /// Startup finished with failed stages, the app runs without them
+    Degraded,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct StageReport {
+    pub stage: Stage,
+    pub label: &'static str,
+    #[serde(flatten)]
+    pub status: StageStatus,
+    pub elapsed_ms: Option<u64>,
+}
+
+/// Sent as `startup://progress` after every change
+#[derive(Serialize, Debug, Clone)]
+pub struct StartupReport {
+    pub phase: Phase,
+    pub stages: Vec<StageReport>,
+}
+
+/// Brings the app up one stage at a time. A stage that fails or runs past
+/// its timeout doesn't stop the others, only the stages requiring it are
+/// skipped, and a retry runs whatever isn't done yet.
+pub struct Startup {
+    report: Mutex<StartupReport>,
+    running: AtomicBool,
+}
+
+impl StartupReport {
+    fn status(&self, stage: Stage) -> &StageStatus {
+        &self
+            .stages
+            .iter()
+            .find(|s| s.stage == stage)
+            .expect("every stage is reported")
+            .status
+    }
+}
+
+impl Startup {
+    pub fn new() -> Startup {
+        let stages = Stage::ALL
+            .iter()
+            .map(|&stage| StageReport {
+                stage,
+                label: stage.label(),
+                status: StageStatus::Pending,
+                elapsed_ms: None,
+            })
+            .collect();
+
+        Startup {
+            report: Mutex::new(StartupReport {
+                phase: Phase::Starting,
+                stages,
+            }),
+            running: AtomicBool::new(false),
+        }
+    }
+
+    pub fn shared() -> &'static Startup {
+        static STARTUP: OnceLock<Startup> = OnceLock::new();
+        STARTUP.get_or_init(Startup::new)
+    }
+
+    pub fn report(&self) -> StartupReport {
+        self.report
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+
+    /// Runs every stage that isn't done on a background thread, calling
+    /// `on_progress` after each change. False when a run is already going.
+    pub fn run<F>(&'static self, on_progress: F) -> bool
+    where
+        F: Fn(&StartupReport) + Send + 'static,
+    {
+        if self.running.swap(true, Ordering::SeqCst) {
+            return false;
+        }
+
+        thread::spawn(move || {
+            self.run_stages(&on_progress);
+            self.running.store(false, Ordering::SeqCst);
+        });
+        true
+    }
+
+    fn run_stages(&self, on_progress: &dyn Fn(&StartupReport)) {
+        println!("\nBeginning Sift.AI Startup...\n");
+        self.update(on_progress, |report| report.phase = Phase::Starting);
+
+        for stage in Stage::ALL {
+            let report = self.report();
+            if *report.status(stage) == StageStatus::Done {
+                continue;
+            }
+            if let Some(reason) = stage.unneeded() {
+                let reason = reason.to_string();
+                self.set_status(on_progress, stage, StageStatus::Unneeded { reason }, None);
+                continue;
+            }
+
+            let missing: Vec<&str> = stage
+                .requires()
+                .iter()
+                .filter(|&&required| *report.status(required) != StageStatus::Done)
+                .map(|required| required.label())
+                .collect();
+            if !missing.is_empty() {
+                let reason = format!("Needs {}", missing.join(", "));
+                self.set_status(on_progress, stage, StageStatus::Skipped { reason }, None);
+                continue;
+            }
+
+            self.set_status(on_progress, stage, StageStatus::Running, None);
+            let started = Instant::now();
+            let status = match run_with_timeout(stage) {
+                Ok(()) => StageStatus::Done,
+                Err(reason) => {
+                    eprintln!("STARTUP:WARN: {} failed: {}", stage.label(), reason);
+                    StageStatus::Failed { reason }
+                }
+            };
+            let elapsed_ms = started.elapsed().as_millis() as u64;
+            self.set_status(on_progress, stage, status, Some(elapsed_ms));
+        }
+
+        self.update(on_progress, |report| {
+            let all_done = report
+                .stages
+                .iter()
+                .all(|s| matches!(s.status, StageStatus::Done | StageStatus::Unneeded { .. }));
+            report.phase = if all_done {
+                Phase::Ready
+            } else {
+                Phase::Degraded
+            };
+        });
+
+        match self.report().phase {
+            Phase::Ready => println!("\nCompleted Startup Configurations\n"),
+            _ => eprintln!("STARTUP:WARN: Started with some components unavailable"),
+        }
+    }
+
+    fn set_status(
+        &self,
+        on_progress: &dyn Fn(&StartupReport),
+        stage: Stage,
+        status: StageStatus,
+        elapsed_ms: Option<u64>,
+    ) {
+        self.update(on_progress, |report| {
+            if let Some(entry) = report.stages.iter_mut().find(|s| s.stage == stage) {
+                entry.status = status;
+                entry.elapsed_ms = elapsed_ms;
+            }
+        });
+    }
+
+    fn update(
+        &self,
+        on_progress: &dyn Fn(&StartupReport),
+        change: impl FnOnce(&mut StartupReport),
+    ) {
+        let report = {
+            let mut report = self.report.lock().unwrap_or_else(|e| e.into_inner());
+            change(&mut report);
+            report.clone()
+        };
+        on_progress(&report);
+    }
+}
+
+// The stage runs on its own thread so a hung call can't hold up the rest.
+// It gets the deadline too, so stages that poll stop on their own.
+fn run_with_timeout(stage: Stage) -> Result<(), String> {
+    let timeout = stage.timeout();
+    let deadline = Instant::now() + timeout;
+    let (tx, rx) = channel();
+
+    thread::spawn(move || {
+        let _ = tx.send(stage.run(deadline));
+    });
+
+    // A little slack, so a stage that gives up at the deadline explains why
+    match rx.recv_timeout(timeout + TIMEOUT_SLACK) {
+        Ok(result) => result,
+        Err(RecvTimeoutError::Timeout) => {
+            Err(format!("Timed out after {} seconds", timeout.as_secs()))
+        }
+        Err(RecvTimeoutError::Disconnected) => Err("Crashed, see the log".to_string()),
+    }
+}
+
+impl Default for Startup {
+    fn default() -> Self {
+        Startup::new()
+    }
+}
@@ -0,0 +1,4 @@
+pub mod machine;
+pub mod stages;
+
+pub use machine::{Startup, StartupReport};
@@ -0,0 +1,198 @@
+use serde::Serialize;
+use std::future::Future;
+use std::net::{SocketAddr, TcpStream};
+use std::sync::mpsc::{channel, TryRecvError};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::apis;
+use crate::chroma;
+use crate::sidecar::{SidecarSpec, SidecarStatus, Supervisor};
+use crate::store;
+use crate::util::{self, PortRegistry, Service, StoreBackend};
+
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+pub const CHROMA_SIDECAR: &str = "chroma";
+pub const COLLECTION_NAME: &str = "siftfiles";
+
+/// One step of bringing the app up, run in the order of `Stage::ALL`
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Stage {
+    Config,
+    Chroma,
+    Collection,
+    FileServer,
+    QueryServer,
+}
+
+impl Stage {
+    pub const ALL: [Stage; 5] = [
+        Stage::Config,
+        Stage::Chroma,
+        Stage::Collection,
+        Stage::FileServer,
+        Stage::QueryServer,
+    ];
+
+    /// Shown to the user when the stage fails
+    pub fn label(self) -> &'static str {
+        match self {
+            Stage::Config => "Settings",
+            Stage::Chroma => "Chroma server",
+            Stage::Collection => "Search index",
+            Stage::FileServer => "File server",
+            Stage::QueryServer => "Query server",
+        }
+    }
+
+    pub fn timeout(self) -> Duration {
+        match self {
+            Stage::Config => Duration::from_secs(10),
+            // Chroma takes a while to come up the first time
+            Stage::Chroma => Duration::from_secs(60),
+            Stage::Collection => Duration::from_secs(30),
+            Stage::FileServer | Stage::QueryServer => Duration::from_secs(10),
+        }
+    }
+
+    /// Why the stage has nothing to do with the current settings
+    pub fn unneeded(self) -> Option<&'static str> {
+        match self {
+            Stage::Chroma | Stage::Collection
+                if store::configured_backend() != StoreBackend::Chroma =>
+            {
+                Some("Not used with the embedded store")
+            }
+            _ => None,
+        }
+    }
+
+    /// Stages that have to be done before this one can run
+    pub fn requires(self) -> &'static [Stage] {
+        match self {
+            Stage::Collection => &[Stage::Chroma],
+            _ => &[],
+        }
+    }
+
+    /// Does the work of the stage, giving up by `deadline`
+    pub fn run(self, deadline: Instant) -> Result<(), String> {
+        match self {
+            Stage::Config => load_config(),
+            Stage::Chroma => wait_for_chroma(deadline),
+            Stage::Collection => create_collection(),
+            Stage::FileServer => serve(Service::Files, apis::fileserv::serve, deadline),
+            Stage::QueryServer => serve(Service::Query, apis::queryserv::serve, deadline),
+        }
+    }
+}
+
+fn load_config() -> Result<(), String> {
+    let app_cfg = util::load_config()
+        .map_err(|e| format!("Can't read {}: {}", util::config_path().display(), e))?;
+    // Only the settings, the config also holds every connector's token
+    println!(
+        "STARTUP: {:?} store, {:?} embedder, indexing {:?}",
+        app_cfg.store.backend, app_cfg.embedder.provider, app_cfg.indexing.roots
+    );
+    Ok(())
+}
+
+// `chroma run` on the datastore, restarted by the supervisor when it dies
+fn chroma_sidecar() -> SidecarSpec {
+    SidecarSpec {
+        name: CHROMA_SIDECAR.to_string(),
+        program: "chroma".to_string(),
+        args: vec![
+            "run".to_string(),
+            "--path".to_string(),
+            util::db_path().display().to_string(),
+            "--port".to_string(),
+            PortRegistry::shared().port(Service::Chroma).to_string(),
+        ],
+        health: Some(Arc::new(|| {
+            chroma::chrust::ChromaClient::shared()
+                .heartbeat()
+                .map(|_| ())
+                .map_err(|e| e.to_string())
+        })),
+    }
+}
+
+// Starts the sidecar once, then waits until the server answers heartbeats.
+// A retry only waits again, the supervisor keeps restarting it meanwhile.
+fn wait_for_chroma(deadline: Instant) -> Result<(), String> {
+    let supervisor = Supervisor::shared();
+    if supervisor.status(CHROMA_SIDECAR).is_none() {
+        supervisor.spawn(chroma_sidecar());
+        println!(
+            "Chroma server is starting in the background on {}.\n",
+            chroma::chrust::chroma_url()
+        );
+    }
+
+    let mut last_error = String::from("no response yet");
+    while Instant::now() < deadline {
+        match chroma::chrust::ChromaClient::shared().heartbeat() {
+            Ok(_) => return Ok(()),
+            Err(e) => last_error = e.to_string(),
+        }
+        thread::sleep(POLL_INTERVAL);
+    }
+
+    // The supervisor knows better when the process itself is the problem
+    match supervisor.status(CHROMA_SIDECAR) {
+        Some(SidecarStatus::Restarting { reason, .. }) => {
+            Err(format!("The Chroma server keeps failing: {}", reason))
+        }
+        _ => Err(format!(
+            "The Chroma server didn't answer on {}: {}",
+            chroma::chrust::chroma_url(),
+            last_error
+        )),
+    }
+}
+
+fn create_collection() -> Result<(), String> {
+    store::shared()
+        .get_or_create_collection(COLLECTION_NAME, &chroma::Metadata::new())
+        .map(|_| ())
+        .map_err(|e| format!("Failed to create the {} collection: {}", COLLECTION_NAME, e))
+}
+
+// Runs a server on the async runtime and waits until it accepts connections.
+// Binding fails straight away when the port is taken, which ends the wait.
+fn serve<F, Fut>(service: Service, server: F, deadline: Instant) -> Result<(), String>
+where
+    F: FnOnce() -> Fut,
+    Fut: Future<Output = std::io::Result<()>> + Send + 'static,
+{
+    let (tx, rx) = channel();
+    let running = server();
+    tauri::async_runtime::spawn(async move {
+        let result = running.await;
+        if let Err(e) = &result {
+            eprintln!("STARTUP:WARN: {:?} server stopped: {}", service, e);
+        }
+        let _ = tx.send(result);
+    });
+
+    let addr = SocketAddr::from(([127, 0, 0, 1], PortRegistry::shared().port(service)));
+    while Instant::now() < deadline {
+        match rx.try_recv() {
+            Ok(Err(e)) => return Err(format!("Can't serve on {}: {}", addr, e)),
+            Ok(Ok(())) | Err(TryRecvError::Disconnected) => {
+                return Err(format!("The server on {} stopped", addr))
+            }
+            Err(TryRecvError::Empty) => {}
+        }
+        if TcpStream::connect_timeout(&addr, POLL_INTERVAL).is_ok() {
+            return Ok(());
+        }
+        thread::sleep(POLL_INTERVAL);
+    }
+    Err(format!("Nothing is listening on {}", addr))
+}
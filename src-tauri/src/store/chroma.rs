@@ -0,0 +1,75 @@
+use std::error::Error;
+
+use crate::chroma::chrust::EmbeddedRecords;
+use crate::chroma::{
+    ChromaClient, ChromaError, CollectionInfo, DeleteRequest, Filter, GetRequest, GetResult,
+    Metadata, QueryResult,
+};
+use crate::store::VectorStore;
+
+impl VectorStore for ChromaClient {
+    fn name(&self) -> &'static str {
+        "chroma"
+    }
+
+    fn list_collections(&self) -> Result<Vec<CollectionInfo>, Box<dyn Error>> {
+        Ok(ChromaClient::list_collections(self)?)
+    }
+
+    fn get_or_create_collection(
+        &self,
+        collection: &str,
+        metadata: &Metadata,
+    ) -> Result<CollectionInfo, Box<dyn Error>> {
+        // Looked up first, some Chroma versions overwrite the metadata of an
+        // existing collection on `get_or_create`
+        let collection = match self.get_collection(collection) {
+            Err(ChromaError::CollectionNotFound(_)) => {
+                self.create_collection(collection, Some(metadata))?
+            }
+            result => result?,
+        };
+
+        Ok(CollectionInfo {
+            name: collection.name,
+            metadata: collection.metadata,
+        })
+    }
+
+    fn delete_collection(&self, collection: &str) -> Result<(), Box<dyn Error>> {
+        Ok(ChromaClient::delete_collection(self, collection)?)
+    }
+
+    fn count(&self, collection: &str) -> Result<usize, Box<dyn Error>> {
+        Ok(ChromaClient::count(self, collection)?)
+    }
+
+    fn upsert(&self, collection: &str, records: &EmbeddedRecords) -> Result<(), Box<dyn Error>> {
+        ChromaClient::get_or_create_collection(self, collection)?;
+        Ok(ChromaClient::upsert(self, collection, records)?)
+    }
+
+    fn query(
+        &self,
+        collection: &str,
+        embeddings: &[Vec<f32>],
+        n_results: usize,
+        filter: &Filter,
+    ) -> Result<QueryResult, Box<dyn Error>> {
+        Ok(ChromaClient::query(
+            self, collection, embeddings, n_results, filter, None,
+        )?)
+    }
+
+    fn get(&self, request: &GetRequest) -> Result<GetResult, Box<dyn Error>> {
+        Ok(ChromaClient::get(self, request)?)
+    }
+
+    fn delete(&self, request: &DeleteRequest) -> Result<(), Box<dyn Error>> {
+        Ok(ChromaClient::delete(self, request)?)
+    }
+
+    fn peek(&self, collection: &str, limit: usize) -> Result<GetResult, Box<dyn Error>> {
+        Ok(ChromaClient::peek(self, collection, limit)?)
+    }
+}
@@ -0,0 +1,591 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::error::Error;
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, RwLock};
+
+use crate::chroma::chrust::EmbeddedRecords;
+use crate::chroma::{
+    CollectionInfo, DeleteRequest, Filter, GetRequest, GetResult, Metadata, QueryResult,
+};
+use crate::store::filter::CompiledFilter;
+use crate::store::hnsw::{Hnsw, Space};
+use crate::store::segment::{
+    list_segments, read_segment, segment_path, write_segment, SegmentOp, StoredRecord,
+};
+use crate::store::VectorStore;
+
+// Candidate list size for graph searches, higher trades speed for recall
+const EF_SEARCH: usize = 64;
+
+// Filters matching at most this many records are searched exhaustively,
+// which is exact and cheaper than walking the graph past rejected nodes
+const BRUTE_FORCE_LIMIT: usize = 2_000;
+
+// Segments are merged into one once there are more than this many
+const MAX_SEGMENTS: usize = 16;
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct CollectionMeta {
+    name: String,
+    dimension: Option<usize>,
+    #[serde(default)]
+    space: Space,
+    #[serde(default)]
+    metadata: Option<Metadata>,
+}
+
+#[derive(Debug, Clone)]
+struct Entry {
+    id: String,
+    document: Option<String>,
+    metadata: Option<Metadata>,
+}
+
+struct Collection {
+    dir: PathBuf,
+    meta: CollectionMeta,
+    index: Hnsw,
+    // Indexed by graph node, `None` once the record was deleted or replaced
+    entries: Vec<Option<Entry>>,
+    nodes: HashMap<String, usize>,
+    segments: Vec<u64>,
+}
+
+fn meta_path(dir: &Path) -> PathBuf {
+    dir.join("collection.json")
+}
+
+fn not_found(name: &str) -> Box<dyn Error> {
+    Box::from(format!("Collection not found: {}", name))
+}
+
+/// Same rules as Chroma, names double as file and directory names here
+pub fn validate_name(name: &str) -> Result<(), Box<dyn Error>> {
+    let valid_chars = name
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '-'));
+    let valid_ends = name.starts_with(|c: char| c.is_ascii_alphanumeric())
+        && name.ends_with(|c: char| c.is_ascii_alphanumeric());
+
+    if (3..=63).contains(&name.len()) && valid_chars && valid_ends && !name.contains("..") {
+        Ok(())
+    } else {
+        Err(Box::from(format!("Invalid collection name: {}", name)))
+    }
+}
+
+impl Collection {
+    fn create(dir: PathBuf, name: &str) -> Result<Collection, Box<dyn Error>> {
+        std::fs::create_dir_all(&dir)?;
+        let meta = CollectionMeta {
+            name: name.to_string(),
+            dimension: None,
+            space: Space::default(),
+            metadata: None,
+        };
+        let collection = Collection {
+            index: Hnsw::new(meta.space),
+            dir,
+            meta,
+            entries: Vec::new(),
+            nodes: HashMap::new(),
+            segments: Vec::new(),
+        };
+        collection.save_meta()?;
+        Ok(collection)
+    }
+
+    fn open(dir: PathBuf) -> Result<Collection, Box<dyn Error>> {
+        let meta: CollectionMeta = serde_json::from_reader(File::open(meta_path(&dir))?)?;
+        let segments = list_segments(&dir)?;
+        let mut collection = Collection {
+            index: Hnsw::new(meta.space),
+            dir,
+            meta,
+            entries: Vec::new(),
+            nodes: HashMap::new(),
+            segments: Vec::new(),
+        };
+
+        for number in segments {
+            for op in read_segment(&segment_path(&collection.dir, number))? {
+                collection.apply(op);
+            }
+            collection.segments.push(number);
+        }
+
+        Ok(collection)
+    }
+
+    fn save_meta(&self) -> Result<(), Box<dyn Error>> {
+        let path = meta_path(&self.dir);
+        let tmp_path = path.with_extension("json.tmp");
+        std::fs::write(&tmp_path, serde_json::to_string_pretty(&self.meta)?)?;
+        std::fs::rename(tmp_path, path)?;
+        Ok(())
+    }
+
+    fn apply(&mut self, op: SegmentOp) {
+        match op {
+            SegmentOp::Upsert(record) => {
+                if let Some(old) = self.nodes.remove(&record.id) {
+                    self.entries[old] = None;
+                }
+                let node = self.index.insert(record.embedding);
+                self.entries.push(Some(Entry {
+                    id: record.id.clone(),
+                    document: record.document,
+                    metadata: record.metadata,
+                }));
+                self.nodes.insert(record.id, node);
+            }
+            SegmentOp::Delete(id) => {
+                if let Some(node) = self.nodes.remove(&id) {
+                    self.entries[node] = None;
+                }
+            }
+        }
+    }
+
+    fn write(&mut self, ops: Vec<SegmentOp>) -> Result<(), Box<dyn Error>> {
+        if ops.is_empty() {
+            return Ok(());
+        }
+
+        let number = self.segments.last().map(|n| n + 1).unwrap_or(1);
+        write_segment(&segment_path(&self.dir, number), &ops)?;
+        self.segments.push(number);
+        for op in ops {
+            self.apply(op);
+        }
+
+        let dead = self.entries.len() - self.nodes.len();
+        if self.segments.len() > MAX_SEGMENTS || dead > self.nodes.len().max(1_000) {
+            self.compact()?;
+        }
+        Ok(())
+    }
+
+    /// Rewrites the live records into a single segment and rebuilds the graph
+    /// without the nodes left behind by deletes and updates
+    fn compact(&mut self) -> Result<(), Box<dyn Error>> {
+        let records: Vec<SegmentOp> = self
+            .entries
+            .iter()
+            .enumerate()
+            .filter_map(|(node, entry)| {
+                let entry = entry.as_ref()?;
+                Some(SegmentOp::Upsert(StoredRecord {
+                    id: entry.id.clone(),
+                    embedding: self.index.vector(node).to_vec(),
+                    document: entry.document.clone(),
+                    metadata: entry.metadata.clone(),
+                }))
+            })
+            .collect();
+
+        // The merged segment replays to the same state as the ones it
+        // replaces, so a crash before they are removed loses nothing
+        let number = self.segments.last().map(|n| n + 1).unwrap_or(1);
+        write_segment(&segment_path(&self.dir, number), &records)?;
+        for old in std::mem::replace(&mut self.segments, vec![number]) {
+            let _ = std::fs::remove_file(segment_path(&self.dir, old));
+        }
+
+        self.index = Hnsw::new(self.meta.space);
+        self.entries.clear();
+        self.nodes.clear();
+        for op in records {
+            self.apply(op);
+        }
+        Ok(())
+    }
+
+    fn live_nodes(&self) -> impl Iterator<Item = usize> + '_ {
+        (0..self.entries.len()).filter(|&node| self.entries[node].is_some())
+    }
+
+    fn check_dimension(&mut self, dimension: usize) -> Result<(), Box<dyn Error>> {
+        match self.meta.dimension {
+            Some(expected) if expected != dimension => Err(Box::from(format!(
+                "Embedding dimension {} does not match collection dimension {}",
+                dimension, expected
+            ))),
+            Some(_) => Ok(()),
+            None => {
+                self.meta.dimension = Some(dimension);
+                self.save_meta()
+            }
+        }
+    }
+
+    fn matching(&self, filter: &CompiledFilter) -> Vec<usize> {
+        self.live_nodes()
+            .filter(|&node| {
+                let entry = self.entries[node].as_ref();
+                filter.matches(
+                    entry.and_then(|e| e.document.as_deref()),
+                    entry.and_then(|e| e.metadata.as_ref()),
+                )
+            })
+            .collect()
+    }
+
+    fn exact(&self, query: &[f32], nodes: &[usize], k: usize) -> Vec<(usize, f32)> {
+        let mut scored: Vec<(usize, f32)> = nodes
+            .iter()
+            .map(|&node| (node, self.index.distance(query, node)))
+            .collect();
+        scored.sort_by(|a, b| a.1.total_cmp(&b.1));
+        scored.truncate(k);
+        scored
+    }
+
+    fn nearest(&self, query: &[f32], k: usize, filter: &CompiledFilter) -> Vec<(usize, f32)> {
+        if filter.is_empty() {
+            let live = |node: usize| self.entries[node].is_some();
+            return self.index.search(query, k, EF_SEARCH, &live);
+        }
+
+        let allowed = self.matching(filter);
+        if allowed.len() <= BRUTE_FORCE_LIMIT {
+            return self.exact(query, &allowed, k);
+        }
+
+        let mut accepted = vec![false; self.entries.len()];
+        for &node in &allowed {
+            accepted[node] = true;
+        }
+        let accept = |node: usize| accepted[node];
+        let hits = self.index.search(query, k, EF_SEARCH.max(k * 4), &accept);
+
+        // A selective filter can starve the graph walk, fall back to exact
+        if hits.len() < k.min(allowed.len()) {
+            return self.exact(query, &allowed, k);
+        }
+        hits
+    }
+}
+
+/// Vector store that lives entirely in this process. Each collection is a
+/// directory of append-only segments replayed into an in-memory HNSW graph
+/// the first time the collection is used.
+pub struct EmbeddedStore {
+    root: PathBuf,
+    collections: Mutex<HashMap<String, Arc<RwLock<Collection>>>>,
+}
+
+impl EmbeddedStore {
+    pub fn new(root: PathBuf) -> EmbeddedStore {
+        EmbeddedStore {
+            root,
+            collections: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn collection(
+        &self,
+        name: &str,
+        create: bool,
+    ) -> Result<Option<Arc<RwLock<Collection>>>, Box<dyn Error>> {
+        validate_name(name)?;
+        let mut collections = self.collections.lock().unwrap_or_else(|e| e.into_inner());
+
+        if let Some(collection) = collections.get(name) {
+            return Ok(Some(collection.clone()));
+        }
+
+        let dir = self.root.join(name);
+        let collection = if meta_path(&dir).exists() {
+            Collection::open(dir)?
+        } else if create {
+            Collection::create(dir, name)?
+        } else {
+            return Ok(None);
+        };
+
+        let collection = Arc::new(RwLock::new(collection));
+        collections.insert(name.to_string(), collection.clone());
+        Ok(Some(collection))
+    }
+
+    fn existing(&self, name: &str) -> Result<Arc<RwLock<Collection>>, Box<dyn Error>> {
+        self.collection(name, false)?.ok_or_else(|| not_found(name))
+    }
+}
+
+impl VectorStore for EmbeddedStore {
+    fn name(&self) -> &'static str {
+        "embedded"
+    }
+
+    fn list_collections(&self) -> Result<Vec<CollectionInfo>, Box<dyn Error>> {
+        if !self.root.exists() {
+            return Ok(Vec::new());
+        }
+
+        let mut collections = Vec::new();
+        for entry in std::fs::read_dir(&self.root)? {
+            let path = meta_path(&entry?.path());
+            if let Ok(file) = File::open(&path) {
+                let meta: CollectionMeta = serde_json::from_reader(file)?;
+                collections.push(CollectionInfo {
+                    name: meta.name,
+                    metadata: meta.metadata,
+                });
+            }
+        }
+
+        collections.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(collections)
+    }
+
+    fn get_or_create_collection(
+        &self,
+        collection: &str,
+        metadata: &Metadata,
+    ) -> Result<CollectionInfo, Box<dyn Error>> {
+        let collection = self
+            .collection(collection, true)?
+            .ok_or_else(|| not_found(collection))?;
+        let mut collection = collection.write().unwrap_or_else(|e| e.into_inner());
+
+        // Only a collection that was just created has no metadata yet
+        if collection.meta.metadata.is_none() && !metadata.is_empty() {
+            collection.meta.metadata = Some(metadata.clone());
+            collection.save_meta()?;
+        }
+
+        Ok(CollectionInfo {
+            name: collection.meta.name.clone(),
+            metadata: collection.meta.metadata.clone(),
+        })
+    }
+
+    fn delete_collection(&self, collection: &str) -> Result<(), Box<dyn Error>> {
+        validate_name(collection)?;
+        let mut collections = self.collections.lock().unwrap_or_else(|e| e.into_inner());
+        collections.remove(collection);
+
+        let dir = self.root.join(collection);
+        if !dir.exists() {
+            return Err(not_found(collection));
+        }
+        std::fs::remove_dir_all(dir)?;
+        Ok(())
+    }
+
+    fn count(&self, collection: &str) -> Result<usize, Box<dyn Error>> {
+        let collection = self.existing(collection)?;
+        let collection = collection.read().unwrap_or_else(|e| e.into_inner());
+        Ok(collection.nodes.len())
+    }
+
+    fn upsert(&self, collection: &str, records: &EmbeddedRecords) -> Result<(), Box<dyn Error>> {
+        let count = records.ids.len();
+        let lengths_match = records.embeddings.len() == count
+            && records.documents.iter().all(|d| d.len() == count)
+            && records.metadatas.iter().all(|m| m.len() == count);
+        if !lengths_match {
+            return Err(Box::from(
+                "ids, embeddings, documents and metadatas differ in length",
+            ));
+        }
+
+        let collection = self
+            .collection(collection, true)?
+            .ok_or_else(|| not_found(collection))?;
+        let mut collection = collection.write().unwrap_or_else(|e| e.into_inner());
+
+        for embedding in &records.embeddings {
+            collection.check_dimension(embedding.len())?;
+        }
+
+        let ops = (0..count)
+            .map(|i| {
+                SegmentOp::Upsert(StoredRecord {
+                    id: records.ids[i].clone(),
+                    embedding: records.embeddings[i].clone(),
+                    document: records.documents.as_ref().map(|d| d[i].clone()),
+                    metadata: records.metadatas.as_ref().map(|m| m[i].clone()),
+                })
+            })
+            .collect();
+
+        collection.write(ops)
+    }
+
+    fn query(
+        &self,
+        collection: &str,
+        embeddings: &[Vec<f32>],
+        n_results: usize,
+        filter: &Filter,
+    ) -> Result<QueryResult, Box<dyn Error>> {
+        let filter = CompiledFilter::compile(filter)?;
+        let collection = self.existing(collection)?;
+        let collection = collection.read().unwrap_or_else(|e| e.into_inner());
+
+        let mut result = QueryResult {
+            ids: Vec::new(),
+            documents: Some(Vec::new()),
+            metadatas: Some(Vec::new()),
+            distances: Some(Vec::new()),
+        };
+
+        for query in embeddings {
+            if let Some(expected) = collection.meta.dimension {
+                if query.len() != expected {
+                    return Err(Box::from(format!(
+                        "Query dimension {} does not match collection dimension {}",
+                        query.len(),
+                        expected
+                    )));
+                }
+            }
+
+            let hits = collection.nearest(query, n_results, &filter);
+            let entries: Vec<&Entry> = hits
+                .iter()
+                .filter_map(|(node, _)| collection.entries[*node].as_ref())
+                .collect();
+
+            result
+                .ids
+                .push(entries.iter().map(|e| e.id.clone()).collect());
+            if let Some(documents) = result.documents.as_mut() {
+                documents.push(entries.iter().map(|e| e.document.clone()).collect());
+            }
+            if let Some(metadatas) = result.metadatas.as_mut() {
+                metadatas.push(entries.iter().map(|e| e.metadata.clone()).collect());
+            }
+            if let Some(distances) = result.distances.as_mut() {
+                distances.push(hits.iter().map(|(_, distance)| *distance).collect());
+            }
+        }
+
+        Ok(result)
+    }
+
+    fn get(&self, request: &GetRequest) -> Result<GetResult, Box<dyn Error>> {
+        let filter = CompiledFilter::compile(&request.filter)?;
+        let collection = self.existing(&request.collection_name)?;
+        let collection = collection.read().unwrap_or_else(|e| e.into_inner());
+
+        let mut nodes = match &request.ids {
+            Some(ids) => ids
+                .iter()
+                .filter_map(|id| collection.nodes.get(id).copied())
+                .collect(),
+            None => collection.live_nodes().collect::<Vec<_>>(),
+        };
+        nodes.retain(|&node| {
+            let entry = collection.entries[node].as_ref();
+            filter.matches(
+                entry.and_then(|e| e.document.as_deref()),
+                entry.and_then(|e| e.metadata.as_ref()),
+            )
+        });
+
+        let entries: Vec<&Entry> = nodes
+            .into_iter()
+            .skip(request.offset.unwrap_or(0))
+            .take(request.limit.unwrap_or(usize::MAX))
+            .filter_map(|node| collection.entries[node].as_ref())
+            .collect();
+
+        Ok(GetResult {
+            ids: entries.iter().map(|e| e.id.clone()).collect(),
+            documents: Some(entries.iter().map(|e| e.document.clone()).collect()),
+            metadatas: Some(entries.iter().map(|e| e.metadata.clone()).collect()),
+        })
+    }
+
+    fn delete(&self, request: &DeleteRequest) -> Result<(), Box<dyn Error>> {
+        let filter = CompiledFilter::compile(&request.filter)?;
+        if request.ids.is_none() && filter.is_empty() {
+            return Err(Box::from("Delete needs ids or a filter"));
+        }
+
+        let collection = self.existing(&request.collection_name)?;
+        let mut collection = collection.write().unwrap_or_else(|e| e.into_inner());
+
+        let candidates: Vec<usize> = match &request.ids {
+            Some(ids) => ids
+                .iter()
+                .filter_map(|id| collection.nodes.get(id).copied())
+                .collect(),
+            None => collection.live_nodes().collect(),
+        };
+        let ops = candidates
+            .into_iter()
+            .filter_map(|node| collection.entries[node].as_ref())
+            .filter(|e| filter.matches(e.document.as_deref(), e.metadata.as_ref()))
+            .map(|e| SegmentOp::Delete(e.id.clone()))
+            .collect();
+
+        collection.write(ops)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::embed::{Embedder, HashingEmbedder};
+    use serde_json::json;
+
+    fn texts(texts: &[&str]) -> Vec<String> {
+        texts.iter().map(|t| t.to_string()).collect()
+    }
+
+    #[test]
+    fn nearest_text_survives_a_reopen() {
+        let root = std::env::temp_dir().join(format!("sift-embedded-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+        let embedder = HashingEmbedder::new(64);
+        let documents = texts(&[
+            "quarterly budget for the marketing team",
+            "holiday photos from the beach",
+            "notes on the rust borrow checker",
+        ]);
+
+        let store = EmbeddedStore::new(root.clone());
+        store
+            .upsert(
+                "siftfiles",
+                &EmbeddedRecords {
+                    ids: texts(&["budget", "photos", "rust"]),
+                    embeddings: embedder.embed(&documents).unwrap(),
+                    documents: Some(documents),
+                    metadatas: Some(
+                        ["pdf", "jpg", "md"]
+                            .iter()
+                            .map(|ext| json!({ "ext": ext }).as_object().unwrap().clone())
+                            .collect(),
+                    ),
+                },
+            )
+            .unwrap();
+        drop(store);
+
+        let store = EmbeddedStore::new(root.clone());
+        let query = embedder.embed(&texts(&["marketing budget"])).unwrap();
+        let nearest = store
+            .query("siftfiles", &query, 1, &Filter::default())
+            .unwrap();
+        let not_pdf = Filter {
+            where_metadata: Some(json!({ "ext": { "$ne": "pdf" } })),
+            where_document: None,
+        };
+        let filtered = store.query("siftfiles", &query, 3, &not_pdf).unwrap();
+        let count = store.count("siftfiles").unwrap();
+        std::fs::remove_dir_all(&root).unwrap();
+
+        assert_eq!(nearest.ids, [["budget"]]);
+        assert_eq!(filtered.ids[0].len(), 2);
+        assert!(!filtered.ids[0].contains(&"budget".to_string()));
+        assert_eq!(count, 3);
+    }
+}
@@ -0,0 +1,215 @@
+use serde_json::Value;
+use std::cmp::Ordering;
+
+use crate::chroma::{Filter, Metadata};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Op {
+    Eq,
+    Ne,
+    Gt,
+    Gte,
+    Lt,
+    Lte,
+    In,
+    Nin,
+}
+
+#[derive(Debug, Clone)]
+enum Predicate {
+    And(Vec<Predicate>),
+    Or(Vec<Predicate>),
+    Field { key: String, op: Op, value: Value },
+    Contains(String),
+    NotContains(String),
+}
+
+/// A `Filter` checked once up front and then evaluated per record, using the
+/// same `where` / `where_document` syntax Chroma accepts
+#[derive(Debug, Clone, Default)]
+pub struct CompiledFilter {
+    metadata: Option<Predicate>,
+    document: Option<Predicate>,
+}
+
+fn parse_op(op: &str) -> Result<Op, String> {
+    match op {
+        "$eq" => Ok(Op::Eq),
+        "$ne" => Ok(Op::Ne),
+        "$gt" => Ok(Op::Gt),
+        "$gte" => Ok(Op::Gte),
+        "$lt" => Ok(Op::Lt),
+        "$lte" => Ok(Op::Lte),
+        "$in" => Ok(Op::In),
+        "$nin" => Ok(Op::Nin),
+        _ => Err(format!("Unknown operator {}", op)),
+    }
+}
+
+fn parse_list(
+    value: &Value,
+    compile: fn(&Value) -> Result<Predicate, String>,
+) -> Result<Vec<Predicate>, String> {
+    match value {
+        Value::Array(items) if !items.is_empty() => items.iter().map(compile).collect(),
+        _ => Err("$and / $or expect a non-empty list".to_string()),
+    }
+}
+
+fn compile_where(value: &Value) -> Result<Predicate, String> {
+    let map = value
+        .as_object()
+        .ok_or_else(|| format!("Expected an object in where filter, got {}", value))?;
+
+    let mut predicates = Vec::new();
+    for (key, condition) in map {
+        let predicate = match key.as_str() {
+            "$and" => Predicate::And(parse_list(condition, compile_where)?),
+            "$or" => Predicate::Or(parse_list(condition, compile_where)?),
+            _ => match condition {
+                // `{"field": {"$op": value}}`
+                Value::Object(ops) => {
+                    let mut fields = Vec::new();
+                    for (op, value) in ops {
+                        let op = parse_op(op)?;
+                        if matches!(op, Op::In | Op::Nin) && !value.is_array() {
+                            return Err(format!("$in / $nin on {} expect a list", key));
+                        }
+                        fields.push(Predicate::Field {
+                            key: key.clone(),
+                            op,
+                            value: value.clone(),
+                        });
+                    }
+                    Predicate::And(fields)
+                }
+                // `{"field": value}` is shorthand for `$eq`
+                _ => Predicate::Field {
+                    key: key.clone(),
+                    op: Op::Eq,
+                    value: condition.clone(),
+                },
+            },
+        };
+        predicates.push(predicate);
+    }
+
+    Ok(match predicates.len() {
+        1 => predicates.remove(0),
+        _ => Predicate::And(predicates),
+    })
+}
+
+fn compile_where_document(value: &Value) -> Result<Predicate, String> {
+    let map = value
+        .as_object()
+        .ok_or_else(|| format!("Expected an object in where_document, got {}", value))?;
+
+    let mut predicates = Vec::new();
+    for (key, condition) in map {
+        let text = || {
+            condition
+                .as_str()
+                .map(str::to_string)
+                .ok_or_else(|| format!("{} expects a string", key))
+        };
+
+        predicates.push(match key.as_str() {
+            "$contains" => Predicate::Contains(text()?),
+            "$not_contains" => Predicate::NotContains(text()?),
+            "$and" => Predicate::And(parse_list(condition, compile_where_document)?),
+            "$or" => Predicate::Or(parse_list(condition, compile_where_document)?),
+            _ => return Err(format!("Unknown where_document operator {}", key)),
+        });
+    }
+
+    Ok(match predicates.len() {
+        1 => predicates.remove(0),
+        _ => Predicate::And(predicates),
+    })
+}
+
+fn compare(left: &Value, right: &Value) -> Option<Ordering> {
+    match (left, right) {
+        (Value::Number(a), Value::Number(b)) => a.as_f64()?.partial_cmp(&b.as_f64()?),
+        (Value::String(a), Value::String(b)) => Some(a.cmp(b)),
+        (Value::Bool(a), Value::Bool(b)) => Some(a.cmp(b)),
+        _ => None,
+    }
+}
+
+fn equals(left: &Value, right: &Value) -> bool {
+    compare(left, right) == Some(Ordering::Equal) || left == right
+}
+
+fn evaluate(predicate: &Predicate, document: Option<&str>, metadata: Option<&Metadata>) -> bool {
+    match predicate {
+        Predicate::And(all) => all.iter().all(|p| evaluate(p, document, metadata)),
+        Predicate::Or(any) => any.iter().any(|p| evaluate(p, document, metadata)),
+        Predicate::Contains(text) => document.map(|d| d.contains(text.as_str())).unwrap_or(false),
+        Predicate::NotContains(text) => {
+            !document.map(|d| d.contains(text.as_str())).unwrap_or(false)
+        }
+        Predicate::Field { key, op, value } => {
+            let field = match metadata.and_then(|m| m.get(key)) {
+                Some(field) => field,
+                // Like Chroma, a missing field only satisfies the negative operators
+                None => return matches!(op, Op::Ne | Op::Nin),
+            };
+            let list = || value.as_array().map(Vec::as_slice).unwrap_or(&[]);
+
+            match op {
+                Op::Eq => equals(field, value),
+                Op::Ne => !equals(field, value),
+                Op::Gt => compare(field, value) == Some(Ordering::Greater),
+                Op::Gte => matches!(
+                    compare(field, value),
+                    Some(Ordering::Greater | Ordering::Equal)
+                ),
+                Op::Lt => compare(field, value) == Some(Ordering::Less),
+                Op::Lte => matches!(
+                    compare(field, value),
+                    Some(Ordering::Less | Ordering::Equal)
+                ),
+                Op::In => list().iter().any(|v| equals(field, v)),
+                Op::Nin => !list().iter().any(|v| equals(field, v)),
+            }
+        }
+    }
+}
+
+impl CompiledFilter {
+    pub fn compile(filter: &Filter) -> Result<CompiledFilter, String> {
+        // Empty objects mean "no filter", same as leaving them out
+        let present = |value: &Option<Value>| {
+            value
+                .clone()
+                .filter(|v| !v.is_null() && v.as_object().map(|m| !m.is_empty()).unwrap_or(true))
+        };
+
+        Ok(CompiledFilter {
+            metadata: present(&filter.where_metadata)
+                .map(|v| compile_where(&v))
+                .transpose()?,
+            document: present(&filter.where_document)
+                .map(|v| compile_where_document(&v))
+                .transpose()?,
+        })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.metadata.is_none() && self.document.is_none()
+    }
+
+    pub fn matches(&self, document: Option<&str>, metadata: Option<&Metadata>) -> bool {
+        self.metadata
+            .as_ref()
+            .map(|p| evaluate(p, document, metadata))
+            .unwrap_or(true)
+            && self
+                .document
+                .as_ref()
+                .map(|p| evaluate(p, document, metadata))
+                .unwrap_or(true)
+    }
+}
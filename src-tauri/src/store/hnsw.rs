@@ -0,0 +1,289 @@
+use serde::{Deserialize, Serialize};
+use std::cmp::{Ordering, Reverse};
+use std::collections::{BinaryHeap, HashSet};
+
+/// Distance function of a collection, named like Chroma's `hnsw:space`
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum Space {
+    /// Squared euclidean distance
+    #[default]
+    L2,
+    /// `1 - cosine similarity`
+    Cosine,
+    /// `1 - dot product`
+    Ip,
+}
+
+impl Space {
+    pub fn distance(&self, a: &[f32], b: &[f32]) -> f32 {
+        match self {
+            Space::L2 => a.iter().zip(b).map(|(x, y)| (x - y) * (x - y)).sum(),
+            Space::Ip => 1.0 - dot(a, b),
+            Space::Cosine => {
+                let norms = (dot(a, a) * dot(b, b)).sqrt();
+                if norms == 0.0 {
+                    1.0
+                } else {
+                    1.0 - dot(a, b) / norms
+                }
+            }
+        }
+    }
+}
+
+fn dot(a: &[f32], b: &[f32]) -> f32 {
+    a.iter().zip(b).map(|(x, y)| x * y).sum()
+}
+
+// Max-heap ordering on distance, ties broken by node so the order is total
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Scored {
+    distance: f32,
+    node: usize,
+}
+
+impl Eq for Scored {}
+
+impl Ord for Scored {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.distance
+            .total_cmp(&other.distance)
+            .then(self.node.cmp(&other.node))
+    }
+}
+
+impl PartialOrd for Scored {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// Hierarchical navigable small world graph over a growing set of vectors.
+/// Nodes are never removed, callers filter out deleted ones at search time
+/// and rebuild the graph when too many have piled up.
+pub struct Hnsw {
+    space: Space,
+    m: usize,
+    ef_construction: usize,
+    level_mult: f64,
+    vectors: Vec<Vec<f32>>,
+    // links[node][layer] are the node's neighbours on that layer
+    links: Vec<Vec<Vec<usize>>>,
+    entry: Option<usize>,
+    rng: u64,
+}
+
+impl Hnsw {
+    pub fn new(space: Space) -> Hnsw {
+        let m = 16;
+        Hnsw {
+            space,
+            m,
+            ef_construction: 100,
+            level_mult: 1.0 / (m as f64).ln(),
+            vectors: Vec::new(),
+            links: Vec::new(),
+            entry: None,
+            rng: 0x2545_f491_4f6c_dd1d,
+        }
+    }
+
+    pub fn vector(&self, node: usize) -> &[f32] {
+        &self.vectors[node]
+    }
+
+    pub fn distance(&self, query: &[f32], node: usize) -> f32 {
+        self.space.distance(query, &self.vectors[node])
+    }
+
+    fn max_links(&self, layer: usize) -> usize {
+        if layer == 0 {
+            self.m * 2
+        } else {
+            self.m
+        }
+    }
+
+    // Deterministic xorshift, the graph only needs a geometric level spread
+    fn random_level(&mut self) -> usize {
+        self.rng ^= self.rng << 13;
+        self.rng ^= self.rng >> 7;
+        self.rng ^= self.rng << 17;
+        let uniform = (self.rng >> 11) as f64 / (1u64 << 53) as f64;
+        (-(uniform.max(f64::MIN_POSITIVE)).ln() * self.level_mult) as usize
+    }
+
+    fn top_layer(&self) -> usize {
+        self.entry.map(|e| self.links[e].len() - 1).unwrap_or(0)
+    }
+
+    /// Adds a vector and returns its node number, which is its insertion index
+    pub fn insert(&mut self, vector: Vec<f32>) -> usize {
+        let node = self.vectors.len();
+        let level = self.random_level();
+        self.vectors.push(vector);
+        self.links.push(vec![Vec::new(); level + 1]);
+
+        let entry = match self.entry {
+            Some(entry) => entry,
+            None => {
+                self.entry = Some(node);
+                return node;
+            }
+        };
+
+        let query = self.vectors[node].clone();
+        let top = self.top_layer();
+        let mut nearest = entry;
+
+        for layer in (level + 1..=top).rev() {
+            nearest = self.greedy(&query, nearest, layer);
+        }
+
+        let mut entry_points = vec![nearest];
+        for layer in (0..=level.min(top)).rev() {
+            let candidates =
+                self.search_layer(&query, &entry_points, self.ef_construction, layer, &|_| {
+                    true
+                });
+            let neighbours: Vec<usize> = candidates.iter().take(self.m).map(|s| s.node).collect();
+
+            for &neighbour in &neighbours {
+                self.links[neighbour][layer].push(node);
+                self.prune(neighbour, layer);
+            }
+            self.links[node][layer] = neighbours;
+            entry_points = candidates.into_iter().map(|s| s.node).collect();
+        }
+
+        if level > top {
+            self.entry = Some(node);
+        }
+        node
+    }
+
+    // Keeps only the closest links once a node has too many
+    fn prune(&mut self, node: usize, layer: usize) {
+        let max = self.max_links(layer);
+        if self.links[node][layer].len() <= max {
+            return;
+        }
+
+        let base = &self.vectors[node];
+        let mut scored: Vec<Scored> = self.links[node][layer]
+            .iter()
+            .map(|&n| Scored {
+                distance: self.space.distance(base, &self.vectors[n]),
+                node: n,
+            })
+            .collect();
+        scored.sort();
+        self.links[node][layer] = scored.into_iter().take(max).map(|s| s.node).collect();
+    }
+
+    fn greedy(&self, query: &[f32], start: usize, layer: usize) -> usize {
+        let mut current = start;
+        let mut best = self.distance(query, current);
+
+        loop {
+            let mut improved = false;
+            for &neighbour in &self.links[current][layer] {
+                let distance = self.distance(query, neighbour);
+                if distance < best {
+                    best = distance;
+                    current = neighbour;
+                    improved = true;
+                }
+            }
+            if !improved {
+                return current;
+            }
+        }
+    }
+
+    // Best-first search on one layer. Nodes failing `accept` are still walked
+    // through, they are only kept out of the results.
+    fn search_layer(
+        &self,
+        query: &[f32],
+        entry_points: &[usize],
+        ef: usize,
+        layer: usize,
+        accept: &dyn Fn(usize) -> bool,
+    ) -> Vec<Scored> {
+        let mut visited: HashSet<usize> = entry_points.iter().copied().collect();
+        let mut candidates = BinaryHeap::new();
+        let mut results: BinaryHeap<Scored> = BinaryHeap::new();
+
+        for &node in entry_points {
+            let scored = Scored {
+                distance: self.distance(query, node),
+                node,
+            };
+            candidates.push(Reverse(scored));
+            if accept(node) {
+                results.push(scored);
+            }
+        }
+
+        while let Some(Reverse(current)) = candidates.pop() {
+            if results.len() >= ef {
+                if let Some(worst) = results.peek() {
+                    if current.distance > worst.distance {
+                        break;
+                    }
+                }
+            }
+
+            for &neighbour in &self.links[current.node][layer] {
+                if !visited.insert(neighbour) {
+                    continue;
+                }
+
+                let scored = Scored {
+                    distance: self.distance(query, neighbour),
+                    node: neighbour,
+                };
+                let worst = results.peek().map(|w| w.distance).unwrap_or(f32::INFINITY);
+                if results.len() < ef || scored.distance < worst {
+                    candidates.push(Reverse(scored));
+                    if accept(neighbour) {
+                        results.push(scored);
+                        if results.len() > ef {
+                            results.pop();
+                        }
+                    }
+                }
+            }
+        }
+
+        results.into_sorted_vec()
+    }
+
+    /// The `k` accepted nodes closest to `query` as `(node, distance)`,
+    /// searching with a candidate list of `ef`
+    pub fn search(
+        &self,
+        query: &[f32],
+        k: usize,
+        ef: usize,
+        accept: &dyn Fn(usize) -> bool,
+    ) -> Vec<(usize, f32)> {
+        let entry = match self.entry {
+            Some(entry) => entry,
+            None => return Vec::new(),
+        };
+
+        let mut nearest = entry;
+        for layer in (1..=self.top_layer()).rev() {
+            nearest = self.greedy(query, nearest, layer);
+        }
+
+        self.search_layer(query, &[nearest], ef.max(k), 0, accept)
+            .into_iter()
+            .take(k)
+            .map(|s| (s.node, s.distance))
+            .collect()
+    }
+}
@@ -0,0 +1,93 @@
+pub mod chroma;
+pub mod embedded;
+pub mod filter;
+pub mod hnsw;
+pub mod segment;
+
+use std::error::Error;
+use std::sync::OnceLock;
+
+use crate::chroma::chrust::{chroma_url, EmbeddedRecords};
+use crate::chroma::{
+    ChromaClient, CollectionInfo, DeleteRequest, Filter, GetRequest, GetResult, Metadata,
+    QueryResult,
+};
+use crate::util::{db_path, StoreBackend};
+
+pub use embedded::EmbeddedStore;
+
+/// Storage for embedded records, independent of where the vectors live.
+/// Requests and results use Chroma's shapes so callers don't care which
+/// backend is configured.
+pub trait VectorStore: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    fn list_collections(&self) -> Result<Vec<CollectionInfo>, Box<dyn Error>>;
+
+    /// Returns the collection as stored, creating it with `metadata` first
+    /// if it doesn't exist. An existing collection's metadata is left alone.
+    fn get_or_create_collection(
+        &self,
+        collection: &str,
+        metadata: &Metadata,
+    ) -> Result<CollectionInfo, Box<dyn Error>>;
+
+    fn delete_collection(&self, collection: &str) -> Result<(), Box<dyn Error>>;
+
+    fn count(&self, collection: &str) -> Result<usize, Box<dyn Error>>;
+
+    /// Inserts new ids and replaces existing ones, creating the collection
+    /// on first use
+    fn upsert(&self, collection: &str, records: &EmbeddedRecords) -> Result<(), Box<dyn Error>>;
+
+    /// The `n_results` nearest records for each embedding, closest first
+    fn query(
+        &self,
+        collection: &str,
+        embeddings: &[Vec<f32>],
+        n_results: usize,
+        filter: &Filter,
+    ) -> Result<QueryResult, Box<dyn Error>>;
+
+    fn get(&self, request: &GetRequest) -> Result<GetResult, Box<dyn Error>>;
+
+    fn delete(&self, request: &DeleteRequest) -> Result<(), Box<dyn Error>>;
+
+    fn peek(&self, collection: &str, limit: usize) -> Result<GetResult, Box<dyn Error>> {
+        self.get(&GetRequest {
+            collection_name: collection.to_string(),
+            ids: None,
+            filter: Filter::default(),
+            limit: Some(limit),
+            offset: None,
+            include: None,
+        })
+    }
+}
+
+pub fn open_store(backend: StoreBackend) -> Result<Box<dyn VectorStore>, Box<dyn Error>> {
+    Ok(match backend {
+        StoreBackend::Chroma => Box::new(ChromaClient::new(&chroma_url())?),
+        StoreBackend::Embedded => Box::new(EmbeddedStore::new(db_path().join("embedded"))),
+    })
+}
+
+/// The backend picked in the config, the default when it can't be read
+pub fn configured_backend() -> StoreBackend {
+    crate::util::load_config()
+        .map(|cfg| cfg.store.backend)
+        .unwrap_or_default()
+}
+
+/// The store picked in the config, opened once per process. Changing the
+/// backend takes effect on the next start.
+pub fn shared() -> &'static dyn VectorStore {
+    static STORE: OnceLock<Box<dyn VectorStore>> = OnceLock::new();
+    STORE
+        .get_or_init(|| {
+            let backend = configured_backend();
+            println!("STORE:INFO: Using the {:?} vector store", backend);
+            open_store(backend).expect("Failed to open the vector store")
+        })
+        .as_ref()
+}
@@ -0,0 +1,155 @@
+use serde_json::{json, Value};
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, ErrorKind, Read, Write};
+use std::path::{Path, PathBuf};
+
+use crate::chroma::Metadata;
+
+const MAGIC: &[u8; 8] = b"SIFTSEG1";
+
+const TAG_UPSERT: u8 = 1;
+const TAG_DELETE: u8 = 2;
+
+#[derive(Debug, Clone)]
+pub struct StoredRecord {
+    pub id: String,
+    pub embedding: Vec<f32>,
+    pub document: Option<String>,
+    pub metadata: Option<Metadata>,
+}
+
+/// One write to a collection. A segment is an ordered batch of these, and a
+/// collection is the replay of its segments in order.
+#[derive(Debug, Clone)]
+pub enum SegmentOp {
+    Upsert(StoredRecord),
+    Delete(String),
+}
+
+pub fn segment_path(dir: &Path, number: u64) -> PathBuf {
+    dir.join(format!("{:08}.seg", number))
+}
+
+/// Segment numbers found in `dir`, oldest first
+pub fn list_segments(dir: &Path) -> io::Result<Vec<u64>> {
+    let mut numbers = Vec::new();
+
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.extension().map(|e| e == "seg").unwrap_or(false) {
+            if let Some(number) = path
+                .file_stem()
+                .and_then(|s| s.to_str())
+                .and_then(|s| s.parse().ok())
+            {
+                numbers.push(number);
+            }
+        }
+    }
+
+    numbers.sort_unstable();
+    Ok(numbers)
+}
+
+fn write_bytes(out: &mut impl Write, bytes: &[u8]) -> io::Result<()> {
+    out.write_all(&(bytes.len() as u32).to_le_bytes())?;
+    out.write_all(bytes)
+}
+
+fn read_u32(input: &mut impl Read) -> io::Result<u32> {
+    let mut buffer = [0u8; 4];
+    input.read_exact(&mut buffer)?;
+    Ok(u32::from_le_bytes(buffer))
+}
+
+fn read_bytes(input: &mut impl Read) -> io::Result<Vec<u8>> {
+    let len = read_u32(input)? as usize;
+    let mut buffer = vec![0u8; len];
+    input.read_exact(&mut buffer)?;
+    Ok(buffer)
+}
+
+fn invalid(message: impl ToString) -> io::Error {
+    io::Error::new(ErrorKind::InvalidData, message.to_string())
+}
+
+/// Writes the segment next to its final name and renames it into place, so
+/// a segment is either complete or absent
+pub fn write_segment(path: &Path, ops: &[SegmentOp]) -> io::Result<()> {
+    let tmp_path = path.with_extension("seg.tmp");
+    let mut out = BufWriter::new(File::create(&tmp_path)?);
+    out.write_all(MAGIC)?;
+
+    for op in ops {
+        match op {
+            SegmentOp::Upsert(record) => {
+                out.write_all(&[TAG_UPSERT])?;
+                write_bytes(&mut out, record.id.as_bytes())?;
+                out.write_all(&(record.embedding.len() as u32).to_le_bytes())?;
+                for value in &record.embedding {
+                    out.write_all(&value.to_le_bytes())?;
+                }
+                let payload = json!({
+                    "document": record.document,
+                    "metadata": record.metadata,
+                });
+                write_bytes(&mut out, payload.to_string().as_bytes())?;
+            }
+            SegmentOp::Delete(id) => {
+                out.write_all(&[TAG_DELETE])?;
+                write_bytes(&mut out, id.as_bytes())?;
+            }
+        }
+    }
+
+    out.into_inner().map_err(|e| e.into_error())?.sync_all()?;
+    std::fs::rename(tmp_path, path)
+}
+
+pub fn read_segment(path: &Path) -> io::Result<Vec<SegmentOp>> {
+    let mut input = BufReader::new(File::open(path)?);
+    let mut magic = [0u8; 8];
+    input.read_exact(&mut magic)?;
+    if &magic != MAGIC {
+        return Err(invalid(format!("{} is not a segment", path.display())));
+    }
+
+    let mut ops = Vec::new();
+    loop {
+        let mut tag = [0u8; 1];
+        match input.read_exact(&mut tag) {
+            Ok(()) => {}
+            Err(e) if e.kind() == ErrorKind::UnexpectedEof => break,
+            Err(e) => return Err(e),
+        }
+
+        let id = String::from_utf8(read_bytes(&mut input)?).map_err(invalid)?;
+        match tag[0] {
+            TAG_UPSERT => {
+                let dimension = read_u32(&mut input)? as usize;
+                let mut raw = vec![0u8; dimension * 4];
+                input.read_exact(&mut raw)?;
+                let embedding = raw
+                    .chunks_exact(4)
+                    .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+                    .collect();
+
+                let payload: Value =
+                    serde_json::from_slice(&read_bytes(&mut input)?).map_err(invalid)?;
+                let document = payload["document"].as_str().map(str::to_string);
+                let metadata = payload["metadata"].as_object().cloned();
+
+                ops.push(SegmentOp::Upsert(StoredRecord {
+                    id,
+                    embedding,
+                    document,
+                    metadata,
+                }));
+            }
+            TAG_DELETE => ops.push(SegmentOp::Delete(id)),
+            other => return Err(invalid(format!("Unknown segment op {}", other))),
+        }
+    }
+
+    Ok(ops)
+}
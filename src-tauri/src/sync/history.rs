@@ -0,0 +1,87 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fs::File;
+use std::io::{BufReader, BufWriter};
+use std::path::PathBuf;
+
+use crate::util::{db_path, SyncSource};
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum SyncTrigger {
+    Scheduled,
+    /// The run was missed, usually because the machine was asleep
+    CatchUp,
+    Manual,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "status", rename_all = "lowercase")]
+pub enum SyncOutcome {
+    Ok,
+    Failed { reason: String },
+}
+
+/// How the last sync of a source went, times in seconds since the epoch
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SyncRecord {
+    pub source: SyncSource,
+    pub trigger: SyncTrigger,
+    pub started_at: i64,
+    pub finished_at: i64,
+    #[serde(flatten)]
+    pub outcome: SyncOutcome,
+}
+
+/// The last run of every source, kept in `sync/history.json` in the
+/// datastore so schedules carry on across restarts
+#[derive(Debug, Default)]
+pub struct SyncHistory {
+    path: PathBuf,
+    last_runs: BTreeMap<SyncSource, SyncRecord>,
+}
+
+pub fn history_path() -> PathBuf {
+    db_path().join("sync").join("history.json")
+}
+
+impl SyncHistory {
+    /// A missing or unreadable file starts an empty history
+    pub fn load(path: PathBuf) -> SyncHistory {
+        let last_runs = match File::open(&path) {
+            Ok(file) => serde_json::from_reader(BufReader::new(file)).unwrap_or_else(|e| {
+                eprintln!(
+                    "SYNC:WARN: Ignoring bad history in {}: {}",
+                    path.display(),
+                    e
+                );
+                BTreeMap::new()
+            }),
+            Err(_) => BTreeMap::new(),
+        };
+        SyncHistory { path, last_runs }
+    }
+
+    pub fn last_run(&self, source: SyncSource) -> Option<&SyncRecord> {
+        self.last_runs.get(&source)
+    }
+
+    pub fn record(&mut self, record: SyncRecord) -> Result<(), Box<dyn Error>> {
+        self.last_runs.insert(record.source, record);
+        self.save()
+    }
+
+    // Written to a temporary file first so a crash can't leave half a file
+    fn save(&self) -> Result<(), Box<dyn Error>> {
+        if let Some(parent) = self.path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let tmp_path = self.path.with_extension("json.tmp");
+        let mut out = BufWriter::new(File::create(&tmp_path)?);
+        serde_json::to_writer_pretty(&mut out, &self.last_runs)?;
+        out.into_inner().map_err(|e| e.into_error())?.sync_all()?;
+        std::fs::rename(tmp_path, &self.path)?;
+        Ok(())
+    }
+}
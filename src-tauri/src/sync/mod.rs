@@ -0,0 +1,6 @@
+pub mod history;
+pub mod schedule;
+pub mod scheduler;
+
+pub use history::SyncTrigger;
+pub use scheduler::{sync_job, Scheduler, SourceStatus};
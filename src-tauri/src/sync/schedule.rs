@@ -0,0 +1,296 @@
+use chrono::{DateTime, Datelike, Duration as ChronoDuration, NaiveDate, TimeZone, Timelike};
+use std::str::FromStr;
+use std::time::Duration;
+
+// Shorter intervals would keep the connectors busy around the clock
+const MIN_INTERVAL: Duration = Duration::from_secs(60);
+// How far ahead a cron line is searched before it's considered unsatisfiable,
+// e.g. `0 0 31 2 *`
+const SEARCH_YEARS: i64 = 5;
+
+/// When a sync runs, parsed from the `schedule` of a source
+#[derive(Debug, Clone, PartialEq)]
+pub enum Schedule {
+    Every(Duration),
+    Cron(Cron),
+}
+
+/// `minute hour day-of-month month day-of-week`, each a `*`, a number, a
+/// range `a-b` or a list of those, with an optional `/step`. Sunday is 0 or
+/// 7. Like cron, when both day fields are restricted either one matching is
+/// enough.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cron {
+    minutes: u64,
+    hours: u64,
+    days: u64,
+    months: u64,
+    weekdays: u64,
+    any_day: bool,
+    any_weekday: bool,
+}
+
+fn parse_interval(spec: &str) -> Result<Duration, String> {
+    let spec = spec.trim();
+    let split = spec
+        .find(|c: char| !c.is_ascii_digit())
+        .ok_or_else(|| format!("Missing unit in '{}', use s, m, h or d", spec))?;
+    let (count, unit) = spec.split_at(split);
+    let count: u64 = count
+        .parse()
+        .map_err(|_| format!("Bad interval '{}'", spec))?;
+
+    let seconds = match unit.trim() {
+        "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        other => return Err(format!("Unknown unit '{}', use s, m, h or d", other)),
+    };
+    let interval = Duration::from_secs(count.saturating_mul(seconds));
+    if interval < MIN_INTERVAL {
+        return Err(format!("'{}' is too often, the minimum is 1m", spec));
+    }
+    Ok(interval)
+}
+
+// Bit `n` is set when value `n` is allowed
+fn parse_field(spec: &str, min: u32, max: u32) -> Result<u64, String> {
+    let mut mask = 0u64;
+
+    for part in spec.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => {
+                let step: u32 = step
+                    .parse()
+                    .map_err(|_| format!("Bad step in '{}'", part))?;
+                if step == 0 {
+                    return Err(format!("Bad step in '{}'", part));
+                }
+                (range, step)
+            }
+            None => (part, 1),
+        };
+
+        let number = |s: &str| -> Result<u32, String> {
+            let n: u32 = s.parse().map_err(|_| format!("Bad value '{}'", s))?;
+            if n < min || n > max {
+                return Err(format!("'{}' is outside {}-{}", n, min, max));
+            }
+            Ok(n)
+        };
+        let (start, end) = match range {
+            "*" => (min, max),
+            _ => match range.split_once('-') {
+                Some((a, b)) => (number(a)?, number(b)?),
+                // `5/15` means from 5 to the end in steps of 15
+                None if step > 1 => (number(range)?, max),
+                None => {
+                    let n = number(range)?;
+                    (n, n)
+                }
+            },
+        };
+        if start > end {
+            return Err(format!("Empty range '{}'", range));
+        }
+
+        for value in (start..=end).step_by(step as usize) {
+            mask |= 1 << value;
+        }
+    }
+
+    Ok(mask)
+}
+
+impl FromStr for Cron {
+    type Err = String;
+
+    fn from_str(spec: &str) -> Result<Cron, String> {
+        let fields: Vec<&str> = spec.split_whitespace().collect();
+        if fields.len() != 5 {
+            return Err(format!(
+                "'{}' needs five fields: minute hour day month weekday",
+                spec
+            ));
+        }
+
+        let mut weekdays = parse_field(fields[4], 0, 7)?;
+        // 7 is another Sunday
+        if weekdays & (1 << 7) != 0 {
+            weekdays = (weekdays | 1) & !(1 << 7);
+        }
+
+        Ok(Cron {
+            minutes: parse_field(fields[0], 0, 59)?,
+            hours: parse_field(fields[1], 0, 23)?,
+            days: parse_field(fields[2], 1, 31)?,
+            months: parse_field(fields[3], 1, 12)?,
+            weekdays,
+            any_day: fields[2] == "*",
+            any_weekday: fields[4] == "*",
+        })
+    }
+}
+
+impl Cron {
+    fn matches_day(&self, date: NaiveDate) -> bool {
+        let day = self.days & (1 << date.day()) != 0;
+        let weekday = self.weekdays & (1 << date.weekday().num_days_from_sunday()) != 0;
+        match (self.any_day, self.any_weekday) {
+            (true, true) => true,
+            (true, false) => weekday,
+            (false, true) => day,
+            (false, false) => day || weekday,
+        }
+    }
+
+    /// The first matching minute after `after`, in its time zone. Minutes
+    /// skipped by a DST change don't exist and aren't returned.
+    pub fn next_after<Tz: TimeZone>(&self, after: &DateTime<Tz>) -> Option<DateTime<Tz>> {
+        let zone = after.timezone();
+        let start = after.naive_local().with_second(0)?.with_nanosecond(0)?;
+        let limit = start + ChronoDuration::days(366 * SEARCH_YEARS);
+        let mut t = start + ChronoDuration::minutes(1);
+
+        while t < limit {
+            if self.months & (1 << t.month()) == 0 {
+                let (year, month) = match t.month() {
+                    12 => (t.year() + 1, 1),
+                    m => (t.year(), m + 1),
+                };
+                t = NaiveDate::from_ymd_opt(year, month, 1)?.and_hms_opt(0, 0, 0)?;
+            } else if !self.matches_day(t.date()) {
+                t = t.date().succ_opt()?.and_hms_opt(0, 0, 0)?;
+            } else if self.hours & (1 << t.hour()) == 0 {
+                t = t.date().and_hms_opt(t.hour(), 0, 0)? + ChronoDuration::hours(1);
+            } else if self.minutes & (1 << t.minute()) == 0 {
+                t += ChronoDuration::minutes(1);
+            } else {
+                match zone.from_local_datetime(&t).earliest() {
+                    Some(time) if time > *after => return Some(time),
+                    _ => t += ChronoDuration::minutes(1),
+                }
+            }
+        }
+        None
+    }
+}
+
+impl FromStr for Schedule {
+    type Err = String;
+
+    fn from_str(spec: &str) -> Result<Schedule, String> {
+        let spec = spec.trim();
+        match spec.strip_prefix("every ") {
+            Some(interval) => parse_interval(interval).map(Schedule::Every),
+            None => spec.parse().map(Schedule::Cron),
+        }
+    }
+}
+
+impl Schedule {
+    /// When the next run is due after one at `after`
+    pub fn next_after<Tz: TimeZone>(&self, after: &DateTime<Tz>) -> Option<DateTime<Tz>> {
+        match self {
+            Schedule::Every(interval) => {
+                Some(after.clone() + ChronoDuration::from_std(*interval).ok()?)
+            }
+            Schedule::Cron(cron) => cron.next_after(after),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+
+    // A Saturday
+    fn saturday_night() -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2026, 10, 17, 23, 50, 12).unwrap()
+    }
+
+    fn next(spec: &str, after: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        spec.parse::<Schedule>().unwrap().next_after(&after)
+    }
+
+    #[test]
+    fn parses_intervals() {
+        assert_eq!(
+            "every 30m".parse(),
+            Ok(Schedule::Every(Duration::from_secs(30 * 60)))
+        );
+        assert_eq!(
+            " every 6h ".parse(),
+            Ok(Schedule::Every(Duration::from_secs(6 * 60 * 60)))
+        );
+        assert!("every 10s".parse::<Schedule>().is_err());
+        assert!("every 5".parse::<Schedule>().is_err());
+        assert!("every 2w".parse::<Schedule>().is_err());
+    }
+
+    #[test]
+    fn rejects_bad_cron_lines() {
+        for spec in [
+            "* * *",
+            "61 * * * *",
+            "* 24 * * *",
+            "0 0 0 * *",
+            "5-1 * * * *",
+            "*/0 * * * *",
+        ] {
+            assert!(spec.parse::<Schedule>().is_err(), "{}", spec);
+        }
+    }
+
+    #[test]
+    fn interval_counts_from_the_last_run() {
+        let after = saturday_night();
+        assert_eq!(
+            next("every 30m", after),
+            Some(after + ChronoDuration::minutes(30))
+        );
+    }
+
+    #[test]
+    fn cron_finds_the_next_matching_minute() {
+        let after = saturday_night();
+        assert_eq!(
+            next("0 3 * * *", after),
+            Some(Utc.with_ymd_and_hms(2026, 10, 18, 3, 0, 0).unwrap())
+        );
+        // Weekdays only, so Monday morning
+        assert_eq!(
+            next("*/15 9-17 * * 1-5", after),
+            Some(Utc.with_ymd_and_hms(2026, 10, 19, 9, 0, 0).unwrap())
+        );
+        // `5/20` is 5, 25 and 45
+        assert_eq!(
+            next("5/20 * * * *", after),
+            Some(Utc.with_ymd_and_hms(2026, 10, 18, 0, 5, 0).unwrap())
+        );
+    }
+
+    #[test]
+    fn cron_day_fields_match_either_way() {
+        // The 1st or the 15th or a Sunday, and 7 is Sunday too
+        let after = saturday_night();
+        assert_eq!(
+            next("0 0 1,15 * 7", after),
+            Some(Utc.with_ymd_and_hms(2026, 10, 18, 0, 0, 0).unwrap())
+        );
+        assert_eq!(
+            next(
+                "0 0 1,15 * 7",
+                Utc.with_ymd_and_hms(2026, 10, 25, 0, 0, 0).unwrap()
+            ),
+            Some(Utc.with_ymd_and_hms(2026, 11, 1, 0, 0, 0).unwrap())
+        );
+    }
+
+    #[test]
+    fn cron_that_never_matches_has_no_next_run() {
+        assert_eq!(next("0 0 31 2 *", saturday_night()), None);
+    }
+}
@@ -0,0 +1,412 @@
+use chrono::{DateTime, Local, TimeZone};
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::collections::HashSet;
+use std::error::Error;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::thread;
+use std::time::Duration;
+
+use crate::chroma::chrust::chroma_url;
+use crate::chroma::Filter;
+use crate::files::indexer::COLLECTION_NAME;
+use crate::jobs::process::run_script;
+use crate::jobs::{Job, JobControl, JobKind, JobManager, JobState, Stop};
+use crate::search::{self, SearchCache};
+use crate::store;
+use crate::sync::history::{history_path, SyncHistory, SyncOutcome, SyncRecord, SyncTrigger};
+use crate::sync::schedule::Schedule;
+use crate::util::{
+    config_path, db_path, load_config, AppConfig, EmbedderConfig, EmbedderProvider, StoreBackend,
+    StoreConfig, SyncConfig, SyncSource,
+};
+
+const TICK: Duration = Duration::from_secs(30);
+// Lets the app finish starting before the first syncs
+const FIRST_TICK_DELAY: Duration = Duration::from_secs(60);
+// A run due longer ago than this was missed, not just picked up late
+const MISSED_AFTER_SECS: i64 = 5 * 60;
+
+/// Called after every finished sync
+pub type SyncListener = Arc<dyn Fn(&SyncRecord) + Send + Sync>;
+
+impl SyncSource {
+    pub fn name(self) -> &'static str {
+        match self {
+            SyncSource::Github => "github",
+            SyncSource::Notion => "notion",
+            SyncSource::Slack => "slack",
+            SyncSource::Google => "google",
+        }
+    }
+
+    /// The connector script in `pybindings`
+    pub fn script(self) -> &'static str {
+        match self {
+            SyncSource::Github => "init_gh.py",
+            SyncSource::Notion => "init_notion.py",
+            SyncSource::Slack => "slack.py",
+            SyncSource::Google => "google.py",
+        }
+    }
+
+    fn is_connected(self, cfg: &AppConfig) -> bool {
+        let token = match self {
+            SyncSource::Github => &cfg.github_token,
+            SyncSource::Notion => &cfg.notion_token,
+            SyncSource::Slack => &cfg.slack_token,
+            SyncSource::Google => &cfg.google_token,
+        };
+        !token.is_empty()
+    }
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct SourceStatus {
+    pub source: SyncSource,
+    pub schedule: String,
+    pub enabled: bool,
+    /// Why the source can't sync on `schedule`
+    pub error: Option<String>,
+    pub connected: bool,
+    pub running: bool,
+    /// Seconds since the epoch
+    pub next_run: Option<i64>,
+    pub last_run: Option<SyncRecord>,
+}
+
+/// Runs the connector scripts on their schedules. The next run is due one
+/// schedule step after the last one finished, so a run that overlaps the
+/// next slot makes it skip rather than queue, and a machine waking up from
+/// sleep runs each overdue source once. Runs are jobs, so they can be
+/// followed, paused and cancelled like any other.
+pub struct Scheduler {
+    history: Mutex<SyncHistory>,
+    listener: OnceLock<SyncListener>,
+    stopping: AtomicBool,
+    // Bad schedules are reported once, not on every tick
+    warned: Mutex<HashSet<String>>,
+}
+
+fn local_time(seconds: i64) -> Option<DateTime<Local>> {
+    Local.timestamp_opt(seconds, 0).single()
+}
+
+impl Scheduler {
+    pub fn new(history: SyncHistory) -> Scheduler {
+        Scheduler {
+            history: Mutex::new(history),
+            listener: OnceLock::new(),
+            stopping: AtomicBool::new(false),
+            warned: Mutex::new(HashSet::new()),
+        }
+    }
+
+    pub fn shared() -> &'static Scheduler {
+        static SCHEDULER: OnceLock<Scheduler> = OnceLock::new();
+        SCHEDULER.get_or_init(|| Scheduler::new(SyncHistory::load(history_path())))
+    }
+
+    /// Starts checking the schedules on a background thread. Only the first
+    /// call does anything.
+    pub fn spawn<F>(&'static self, on_run: F)
+    where
+        F: Fn(&SyncRecord) + Send + Sync + 'static,
+    {
+        if self.listener.set(Arc::new(on_run)).is_err() {
+            return;
+        }
+
+        thread::spawn(move || {
+            thread::sleep(FIRST_TICK_DELAY);
+            while !self.stopping.load(Ordering::SeqCst) {
+                self.tick();
+                thread::sleep(TICK);
+            }
+        });
+    }
+
+    fn tick(&'static self) {
+        let cfg = match load_config() {
+            Ok(cfg) => cfg,
+            Err(e) => {
+                eprintln!("SYNC:WARN: Can't read the config, not syncing: {}", e);
+                return;
+            }
+        };
+        if !cfg.sync.enabled {
+            return;
+        }
+        if let Some(reason) = sync_unsupported(&cfg.store, &cfg.embedder) {
+            if self.warned.lock().unwrap().insert(reason.to_string()) {
+                eprintln!("SYNC:WARN: Not syncing, {}", reason);
+            }
+            return;
+        }
+
+        let now = Local::now();
+        for source_cfg in cfg.sync.sources.iter().filter(|s| s.enabled) {
+            let source = source_cfg.source;
+            if !source.is_connected(&cfg) || self.is_running(source) {
+                continue;
+            }
+            let schedule = match self.schedule(&source_cfg.schedule) {
+                Some(schedule) => schedule,
+                None => continue,
+            };
+            let due = match self.due_at(source, &schedule) {
+                Some(due) if due <= now => due,
+                _ => continue,
+            };
+
+            let trigger = if (now - due).num_seconds() > MISSED_AFTER_SECS {
+                println!(
+                    "SYNC:INFO: {} was due at {}, catching up",
+                    source.name(),
+                    due.format("%Y-%m-%d %H:%M")
+                );
+                SyncTrigger::CatchUp
+            } else {
+                SyncTrigger::Scheduled
+            };
+            if let Err(e) = self.run(source, trigger) {
+                eprintln!("SYNC:WARN: Can't sync {}: {}", source.name(), e);
+            }
+            // One start per tick, so sources that are all due at once (the
+            // first launch, after a sleep) don't all load their models together
+            break;
+        }
+    }
+
+    fn schedule(&self, spec: &str) -> Option<Schedule> {
+        match spec.parse() {
+            Ok(schedule) => Some(schedule),
+            Err(e) => {
+                if self.warned.lock().unwrap().insert(spec.to_string()) {
+                    eprintln!("SYNC:WARN: Ignoring schedule '{}': {}", spec, e);
+                }
+                None
+            }
+        }
+    }
+
+    // Right away for a source that never synced, never for a cron line that
+    // can't match
+    fn due_at(&self, source: SyncSource, schedule: &Schedule) -> Option<DateTime<Local>> {
+        let history = self.history.lock().unwrap();
+        match history.last_run(source) {
+            Some(last) => schedule.next_after(&local_time(last.finished_at)?),
+            None => Some(Local::now()),
+        }
+    }
+
+    // A paused sync isn't running, the next run resumes it
+    pub fn is_running(&self, source: SyncSource) -> bool {
+        JobManager::shared()
+            .active(&sync_kind(source))
+            .is_some_and(|job| job.state.is_running())
+    }
+
+    /// Syncs `source` now, unless it's already syncing
+    pub fn sync_now(&'static self, source: SyncSource) -> Result<Job, String> {
+        let cfg = load_config().map_err(|e| e.to_string())?;
+        if !source.is_connected(&cfg) {
+            return Err(format!("Connect {} before syncing it", source.name()));
+        }
+        if let Some(reason) = sync_unsupported(&cfg.store, &cfg.embedder) {
+            return Err(reason.to_string());
+        }
+        self.run(source, SyncTrigger::Manual)
+    }
+
+    fn run(&self, source: SyncSource, trigger: SyncTrigger) -> Result<Job, String> {
+        if self.stopping.load(Ordering::SeqCst) {
+            return Err("shutting down".to_string());
+        }
+        let jobs = JobManager::shared();
+        // Starting next to a paused sync is refused, so it would block every
+        // run after it
+        let paused = jobs
+            .active(&sync_kind(source))
+            .filter(|job| job.state == JobState::Paused);
+        if let Some(job) = paused {
+            println!("SYNC:INFO: Resuming the paused {} sync", source.name());
+            return jobs.resume(&job.id);
+        }
+        jobs.start(JobKind::Sync { source, trigger })
+    }
+
+    fn finish(&self, record: SyncRecord) {
+        if let Err(e) = self.history.lock().unwrap().record(record.clone()) {
+            eprintln!("SYNC:WARN: Failed to save the sync history: {}", e);
+        }
+        if let Some(listener) = self.listener.get() {
+            listener(&record);
+        }
+    }
+
+    pub fn statuses(&self) -> Result<Vec<SourceStatus>, Box<dyn Error>> {
+        let cfg = load_config()?;
+        let unsupported = sync_unsupported(&cfg.store, &cfg.embedder);
+
+        let statuses = cfg
+            .sync
+            .sources
+            .iter()
+            .map(|source_cfg| {
+                let source = source_cfg.source;
+                let schedule = source_cfg.schedule.parse::<Schedule>();
+                let enabled = cfg.sync.enabled && source_cfg.enabled;
+                let connected = source.is_connected(&cfg);
+                let next_run = match &schedule {
+                    Ok(schedule) if enabled && connected && unsupported.is_none() => {
+                        self.due_at(source, schedule).map(|due| due.timestamp())
+                    }
+                    _ => None,
+                };
+
+                SourceStatus {
+                    source,
+                    schedule: source_cfg.schedule.clone(),
+                    enabled,
+                    error: unsupported.map(str::to_string).or(schedule.err()),
+                    connected,
+                    running: self.is_running(source),
+                    next_run,
+                    last_run: self.history.lock().unwrap().last_run(source).cloned(),
+                }
+            })
+            .collect();
+        Ok(statuses)
+    }
+
+    /// Stops starting syncs, the running ones are stopped with the other jobs
+    pub fn shutdown(&self) {
+        self.stopping.store(true, Ordering::SeqCst);
+    }
+}
+
+// The scripts write OpenCLIP vectors to the Chroma server, with another store
+// there's nothing to write to and another embedder would mix its vectors with
+// theirs in one collection
+fn sync_unsupported(store: &StoreConfig, embedder: &EmbedderConfig) -> Option<&'static str> {
+    match (store.backend, embedder.provider) {
+        (StoreBackend::Chroma, EmbedderProvider::Clip) => None,
+        _ => Some("syncing needs the chroma backend with the clip embedder"),
+    }
+}
+
+// The scripts only write to the vector store, the keyword index picks their
+// documents up from there by the `location` they're tagged with
+fn update_keywords(source: SyncSource) {
+    let filter = Filter {
+        where_metadata: Some(json!({ "location": source.name() })),
+        where_document: None,
+    };
+    if let Err(e) = search::backfill(store::shared(), COLLECTION_NAME, &filter) {
+        eprintln!(
+            "SYNC:WARN: Failed to add {} to the keyword index: {}",
+            source.name(),
+            e
+        );
+    }
+}
+
+// Only the source matters when looking for a sync that's already going
+fn sync_kind(source: SyncSource) -> JobKind {
+    JobKind::Sync {
+        source,
+        trigger: SyncTrigger::Manual,
+    }
+}
+
+/// Runs the connector script of `source` as a job, with its output in
+/// `logs/sync-{name}.log`. The run is recorded unless it was paused, a
+/// resumed run is recorded when it ends.
+pub fn sync_job(
+    source: SyncSource,
+    trigger: SyncTrigger,
+    control: &JobControl,
+    checkpoint: Option<Value>,
+) -> Result<(), String> {
+    let timeout_minutes = load_config()
+        .map(|cfg| cfg.sync.timeout_minutes)
+        .unwrap_or_else(|_| SyncConfig::default().timeout_minutes);
+    let timeout = Duration::from_secs(timeout_minutes.max(1) * 60);
+
+    println!("SYNC:INFO: Syncing {} ({:?})", source.name(), trigger);
+    let started_at = Local::now().timestamp();
+    // Scripts take the Chroma url, the config file and the datastore directory
+    let args = [
+        chroma_url(),
+        config_path().display().to_string(),
+        db_path().display().to_string(),
+    ];
+    let result = run_script(
+        control,
+        source.script(),
+        &args,
+        &format!("sync-{}", source.name()),
+        timeout,
+        checkpoint,
+    );
+    // The script writes to the collection as it goes, so even a run that
+    // failed or was paused may have changed what searches return
+    update_keywords(source);
+    SearchCache::shared().bump_generation();
+
+    let outcome = match (&result, control.stopping()) {
+        (_, Some(Stop::Pause | Stop::Shutdown)) => return result,
+        (_, Some(Stop::Cancel)) => SyncOutcome::Failed {
+            reason: "cancelled".to_string(),
+        },
+        (Ok(()), None) => SyncOutcome::Ok,
+        (Err(reason), None) => SyncOutcome::Failed {
+            reason: reason.clone(),
+        },
+    };
+    Scheduler::shared().finish(SyncRecord {
+        source,
+        trigger,
+        started_at,
+        finished_at: Local::now().timestamp(),
+        outcome,
+    });
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn unsupported(backend: StoreBackend, provider: EmbedderProvider) -> Option<&'static str> {
+        let embedder = EmbedderConfig {
+            provider,
+            ..EmbedderConfig::default()
+        };
+        sync_unsupported(&StoreConfig { backend }, &embedder)
+    }
+
+    #[test]
+    fn syncs_need_chroma_with_clip() {
+        assert_eq!(
+            unsupported(StoreBackend::Chroma, EmbedderProvider::Clip),
+            None
+        );
+        for (backend, provider) in [
+            (StoreBackend::Embedded, EmbedderProvider::Clip),
+            (StoreBackend::Chroma, EmbedderProvider::Http),
+            (StoreBackend::Chroma, EmbedderProvider::Hashing),
+            (StoreBackend::Embedded, EmbedderProvider::Hashing),
+        ] {
+            assert_eq!(
+                unsupported(backend, provider),
+                Some("syncing needs the chroma backend with the clip embedder"),
+                "{:?} with {:?}",
+                backend,
+                provider
+            );
+        }
+    }
+}
@@ -14,6 +14,239 @@ pub struct AppConfig {
     pub atlassian_token: String,
     pub slack_token: String,
     pub discord_token: String,
+    #[serde(default)]
+    pub indexing: IndexingConfig,
+    #[serde(default)]
+    pub store: StoreConfig,
+    #[serde(default)]
+    pub embedder: EmbedderConfig,
+    #[serde(default)]
+    pub search_cache: SearchCacheConfig,
+    #[serde(default)]
+    pub sync: SyncConfig,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[serde(rename_all = "lowercase")]
+pub enum SyncSource {
+    Github,
+    Notion,
+    Slack,
+    Google,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SyncSourceConfig {
+    pub source: SyncSource,
+    /// `every 30m` (units s, m, h, d) or a five field cron line in local
+    /// time, e.g. `0 3 * * *`
+    pub schedule: String,
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+}
+
+/// Connector syncs run in the background. A source is only synced once its
+/// token is set.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct SyncConfig {
+    /// Turns every scheduled sync off, syncing by hand still works
+    pub enabled: bool,
+    /// A sync still running after this many minutes is killed
+    pub timeout_minutes: u64,
+    pub sources: Vec<SyncSourceConfig>,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+impl Default for SyncConfig {
+    fn default() -> Self {
+        let source = |source, schedule: &str| SyncSourceConfig {
+            source,
+            schedule: schedule.to_string(),
+            enabled: true,
+        };
+
+        SyncConfig {
+            enabled: true,
+            timeout_minutes: 60,
+            // Slack and Google aren't scheduled until their scripts really sync
+            sources: vec![
+                source(SyncSource::Github, "every 6h"),
+                source(SyncSource::Notion, "every 6h"),
+            ],
+        }
+    }
+}
+
+/// Query embeddings and ranked results kept between searches. Cached
+/// results are dropped on every write to the index.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct SearchCacheConfig {
+    /// Entries kept of each kind, 0 turns the cache off
+    pub capacity: usize,
+    /// Keep the cache on disk across restarts
+    pub persist: bool,
+}
+
+impl Default for SearchCacheConfig {
+    fn default() -> Self {
+        SearchCacheConfig {
+            capacity: 256,
+            persist: false,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum StoreBackend {
+    /// The `chroma run` server started alongside the app
+    #[default]
+    Chroma,
+    /// In-process store under the datastore directory, no Python needed
+    Embedded,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct StoreConfig {
+    pub backend: StoreBackend,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum EmbedderProvider {
+    /// OpenCLIP inside the Python scripts, only works with the Chroma store
+    #[default]
+    Clip,
+    /// An OpenAI-compatible `/embeddings` endpoint, e.g. Ollama or llama.cpp
+    Http,
+    /// A sentence-transformers style ONNX model run on the CPU, needs the ONNX
+    /// Runtime library from `ORT_DYLIB_PATH` or the system library path
+    Onnx,
+    /// Feature hashing, deterministic and dependency free, meant for tests
+    Hashing,
+}
+
+/// How documents and queries are turned into vectors. Collections remember
+/// the model they were written with, so switching models means re-indexing.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct EmbedderConfig {
+    pub provider: EmbedderProvider,
+    /// Model name sent to the endpoint, or the `.onnx` file for `onnx`
+    pub model: String,
+    /// Base URL of the OpenAI-compatible API, without `/embeddings`
+    pub url: String,
+    pub api_key: String,
+    /// The WordPiece `vocab.txt` that goes with the ONNX model
+    pub vocab: String,
+    /// Vector size, probed from the model when left out
+    pub dimension: Option<usize>,
+    /// Texts sent per request or inference run
+    pub batch_size: usize,
+}
+
+impl Default for EmbedderConfig {
+    fn default() -> Self {
+        EmbedderConfig {
+            provider: EmbedderProvider::default(),
+            model: "nomic-embed-text".to_string(),
+            url: "http://localhost:11434/v1".to_string(),
+            api_key: String::new(),
+            vocab: String::new(),
+            dimension: None,
+            batch_size: 32,
+        }
+    }
+}
+
+/// Which local files get indexed. Globs use `.gitignore` syntax and are
+/// matched case-insensitively against paths relative to each root.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct IndexingConfig {
+    /// Directories to index, `~` expands to the home directory
+    pub roots: Vec<String>,
+    /// When non-empty, only files matching one of these are indexed
+    pub include: Vec<String>,
+    /// Files and directories to leave out, these win over `include`
+    pub exclude: Vec<String>,
+    /// Files larger than this many bytes are skipped
+    pub max_file_size: u64,
+    pub index_hidden: bool,
+    pub respect_gitignore: bool,
+}
+
+impl Default for IndexingConfig {
+    fn default() -> Self {
+        let dirs = [
+            "node_modules/",
+            "venv/",
+            ".venv/",
+            "__pycache__/",
+            ".git/",
+            "target/",
+            "library/",
+            "libraries/",
+        ];
+        let extensions = [
+            "dmg",
+            "zip",
+            "tar",
+            "gz",
+            "bz2",
+            "xz",
+            "7z",
+            "rar",
+            "iso",
+            "exe",
+            "dll",
+            "bin",
+            "so",
+            "obj",
+            "class",
+            "o",
+            "pyc",
+            "lock",
+            "lockb",
+            "log",
+            "tmp",
+            "db",
+            "db-wal",
+            "db-shm",
+            "plist",
+            "mp4",
+            "mpeg4",
+            "mov",
+            "avi",
+            "mkv",
+            "flv",
+            "wmv",
+            "webm",
+            "ico",
+            "svg",
+            "img",
+            "timestamp",
+        ];
+
+        IndexingConfig {
+            roots: vec!["~/Documents".to_string(), "~/Desktop".to_string()],
+            include: Vec::new(),
+            exclude: dirs
+                .iter()
+                .map(|d| d.to_string())
+                .chain(extensions.iter().map(|e| format!("*.{}", e)))
+                .collect(),
+            max_file_size: 50 * 1024 * 1024,
+            index_hidden: false,
+            respect_gitignore: true,
+        }
+    }
 }
 
 pub fn db_path() -> PathBuf {
@@ -30,15 +263,6 @@ pub fn db_path() -> PathBuf {
     }
 }
 
-pub fn db_formatted_path() -> String {
-    let path = db_path();
-    let path_str = path.display().to_string();
-
-    let escaped_path = path_str.replace(' ', r"\ ");
-
-    escaped_path
-}
-
 pub fn config_path() -> PathBuf {
     // if let Some(mut cfg_pt) = config_dir() {
     //     cfg_pt = cfg_pt.join("sift.config.json");
@@ -81,7 +305,12 @@ pub fn load_config() -> Result<AppConfig, Box<dyn Error>> {
             google_token: "".to_string(),
             atlassian_token: "".to_string(),
             slack_token: "".to_string(),
-            discord_token: "".to_string(), 
+            discord_token: "".to_string(),
+            indexing: IndexingConfig::default(),
+            store: StoreConfig::default(),
+            embedder: EmbedderConfig::default(),
+            search_cache: SearchCacheConfig::default(),
+            sync: SyncConfig::default(),
         };
 
         write_config(default_cfg.clone())?;
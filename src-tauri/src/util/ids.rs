@@ -0,0 +1,44 @@
+use sha2::{Digest, Sha256};
+use std::path::Path;
+
+// Must match pybindings/ids.py so every source lands in the same id space
+const HASH_CHARS: usize = 32;
+
+/// Deterministic id for a document, `{source}-{hash}` where the hash covers
+/// the source and its canonical locator (a path, a URL). The same document
+/// always gets the same id, across runs and machines.
+pub fn document_id(source: &str, locator: &str) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(source.as_bytes());
+    hasher.update(b"\n");
+    hasher.update(locator.as_bytes());
+
+    let digest = format!("{:x}", hasher.finalize());
+    format!("{}-{}", source, &digest[..HASH_CHARS])
+}
+
+/// Chunks are addressed relative to their document so they can be found and
+/// dropped together
+pub fn chunk_id(document_id: &str, index: usize) -> String {
+    format!("{}:{}", document_id, index)
+}
+
+/// Splits a chunk id back into its document id and chunk index
+pub fn parse_chunk_id(id: &str) -> (&str, Option<usize>) {
+    match id.rsplit_once(':') {
+        Some((document, index)) => match index.parse() {
+            Ok(index) => (document, Some(index)),
+            Err(_) => (id, None),
+        },
+        None => (id, None),
+    }
+}
+
+/// Absolute path with symlinks and `..` resolved, falling back to the path as
+/// given for files that no longer exist
+pub fn file_locator(path: &Path) -> String {
+    std::fs::canonicalize(path)
+        .unwrap_or_else(|_| path.to_path_buf())
+        .display()
+        .to_string()
+}
@@ -1,3 +1,8 @@
 pub mod config;
+pub mod ids;
+pub mod ports;
+pub mod resources;
 
 pub use config::*;
+pub use ports::{PortRegistry, Service};
+pub use resources::{pybinding, set_pybindings_dir};
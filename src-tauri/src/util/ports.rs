@@ -0,0 +1,142 @@
+use serde::Serialize;
+use std::collections::HashMap;
+use std::net::TcpListener;
+use std::sync::{Mutex, OnceLock};
+
+/// Local services and the ports they'd like to be on
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum Service {
+    Chroma,
+    Files,
+    Query,
+    GithubOauth,
+    SlackOauth,
+}
+
+impl Service {
+    pub const ALL: [Service; 5] = [
+        Service::Chroma,
+        Service::Files,
+        Service::Query,
+        Service::GithubOauth,
+        Service::SlackOauth,
+    ];
+
+    pub fn preferred_port(self) -> u16 {
+        match self {
+            Service::GithubOauth => 35435,
+            Service::Chroma => 35436,
+            Service::Files => 35438,
+            Service::SlackOauth => 35439,
+            Service::Query => 35443,
+        }
+    }
+
+    pub fn scheme(self) -> &'static str {
+        match self {
+            Service::SlackOauth => "https",
+            _ => "http",
+        }
+    }
+
+    /// OAuth callbacks are registered with the provider, so their port
+    /// can't move
+    pub fn is_fixed(self) -> bool {
+        matches!(self, Service::GithubOauth | Service::SlackOauth)
+    }
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct ServiceEndpoint {
+    pub service: Service,
+    pub port: u16,
+    pub url: String,
+    /// Whether the preferred port was busy and another one was picked
+    pub relocated: bool,
+}
+
+/// Which service is on which port. A port is picked the first time a
+/// service asks for it: its preferred one when that is free, otherwise one
+/// the OS hands out, so a second instance or a squatter doesn't stop the
+/// app from starting.
+pub struct PortRegistry {
+    ports: Mutex<HashMap<Service, u16>>,
+}
+
+fn is_free(port: u16) -> bool {
+    TcpListener::bind(("127.0.0.1", port)).is_ok()
+}
+
+fn any_free_port() -> Option<u16> {
+    TcpListener::bind(("127.0.0.1", 0))
+        .and_then(|listener| listener.local_addr())
+        .map(|addr| addr.port())
+        .ok()
+}
+
+impl PortRegistry {
+    pub fn new() -> PortRegistry {
+        PortRegistry {
+            ports: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn shared() -> &'static PortRegistry {
+        static REGISTRY: OnceLock<PortRegistry> = OnceLock::new();
+        REGISTRY.get_or_init(PortRegistry::new)
+    }
+
+    pub fn port(&self, service: Service) -> u16 {
+        let mut ports = self.ports.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(port) = ports.get(&service) {
+            return *port;
+        }
+
+        let preferred = service.preferred_port();
+        let taken = |port: u16| ports.values().any(|&p| p == port);
+        let port = if service.is_fixed() || (!taken(preferred) && is_free(preferred)) {
+            preferred
+        } else {
+            // Another service may already hold a port it hasn't bound yet
+            let port = (0..8)
+                .filter_map(|_| any_free_port())
+                .find(|&port| !taken(port))
+                .unwrap_or(preferred);
+            eprintln!(
+                "PORTS:WARN: Port {} is busy, {:?} moves to {}",
+                preferred, service, port
+            );
+            port
+        };
+
+        ports.insert(service, port);
+        port
+    }
+
+    pub fn url(&self, service: Service) -> String {
+        format!("{}://localhost:{}", service.scheme(), self.port(service))
+    }
+
+    /// Every service with its port, picking the ports not picked yet
+    pub fn endpoints(&self) -> Vec<ServiceEndpoint> {
+        Service::ALL
+            .iter()
+            .map(|&service| {
+                let port = self.port(service);
+                ServiceEndpoint {
+                    service,
+                    port,
+                    url: format!("{}://localhost:{}", service.scheme(), port),
+                    relocated: port != service.preferred_port(),
+                }
+            })
+            .collect()
+    }
+}
+
+impl Default for PortRegistry {
+    fn default() -> Self {
+        PortRegistry::new()
+    }
+}
@@ -0,0 +1,20 @@
+use std::path::PathBuf;
+use std::sync::OnceLock;
+
+static PYBINDINGS: OnceLock<PathBuf> = OnceLock::new();
+
+/// Remembers where the bundled `pybindings` scripts ended up, called once
+/// from `setup` before anything runs them
+pub fn set_pybindings_dir(dir: PathBuf) {
+    let _ = PYBINDINGS.set(dir);
+}
+
+/// Path to `pybindings/{script}`, next to the working directory until
+/// `set_pybindings_dir` has run
+pub fn pybinding(script: &str) -> PathBuf {
+    PYBINDINGS
+        .get()
+        .cloned()
+        .unwrap_or_else(|| PathBuf::from("pybindings"))
+        .join(script)
+}